@@ -105,7 +105,9 @@ library Tendermint {
         verifyNewHeaderAndVals(untrustedHeader, untrustedVals, trustedHeader, currentTime, maxClockDrift);
 
         // Ensure that +`trustLevel` (default 1/3) or more of last trusted validators signed correctly.
-        verifyCommitLightTrusting(trustedVals, trustedHeader.header.chain_id, untrustedHeader.commit, trustLevel);
+        if (!verifyCommitLightTrusting(trustedVals, trustedHeader.header.chain_id, untrustedHeader.commit, trustLevel)) {
+            return false;
+        }
 
         // Ensure that +2/3 of new validators signed correctly.
         bool ok = verifyCommitLight(
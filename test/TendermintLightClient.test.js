@@ -12,9 +12,22 @@ contract('TendermintLightClient', () => {
   it('verifies ingestion of valid non-continuous headers', async () => {
       await ingest(8619996, 8619998)
   })
+
+  it('rejects non-continuous headers signed by less than 1/3 of the trusted voting power', async () => {
+      // synthetic chain (`synth --validators 4 --churn 3 --start-height 101`): one of the four
+      // validators trusted after 101 (the validator set of 102) signed 103
+      let error
+      try {
+        await ingest(101, 103, 102)
+      } catch (e) {
+        error = e
+      }
+      assert(error && error.message.includes('LC: failed to verify header'), 'expected the update to be rejected, got: ' + error)
+  })
 })
 
-async function ingest(h1, h2) {
+// trustedValsHeight: height of the validator set trusted after h1 (its next validators)
+async function ingest(h1, h2, trustedValsHeight = h1) {
     const root = new protobuf.Root()
     let Any
 
@@ -39,6 +52,7 @@ async function ingest(h1, h2) {
       // core structs
       const [sh, vs] = await lib.readHeader(h1)
       const [ssh, svs] = await lib.readHeader(h2)
+      const [, tvs] = await lib.readHeader(trustedValsHeight)
 
       // args
       const clientStateObj = ClientState.create({
@@ -116,7 +130,7 @@ async function ingest(h1, h2) {
         validator_set: svs,
 
         trusted_height: sh.header.height.low,
-        trusted_validators: vs
+        trusted_validators: tvs
       })
 
       const all = Any.create({
@@ -1,11 +1,541 @@
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Contracts typed bindings are generated for, with the module they end up in
+const CONTRACTS: &[(&str, &str)] = &[
+    ("IBCHandler", "ibc_handler"),
+    ("IBCHost", "ibc_host"),
+    ("TendermintLightClient", "tendermint_light_client"),
+];
+
+/// `Channel.proto` declares no package, which prost requires (adding one to the file would
+/// rename the Solidity libraries generated from it), so a copy declaring `ibc.channel` is
+/// compiled instead. Returns the include dir of the copy.
+fn packaged_channel_proto() -> PathBuf {
+    let source = "../../proto/ibc/Channel.proto";
+    println!("cargo:rerun-if-changed={}", source);
+
+    let dir = Path::new(&env::var("OUT_DIR").unwrap()).join("proto");
+    fs::create_dir_all(dir.join("ibc")).unwrap();
+    let proto = fs::read_to_string(source).unwrap().replacen(
+        "syntax = \"proto3\";",
+        "syntax = \"proto3\";\npackage ibc.channel;",
+        1,
+    );
+    fs::write(dir.join("ibc/Channel.proto"), proto).unwrap();
+
+    dir
+}
+
 fn main() {
+    let channel_proto_dir = packaged_channel_proto();
     tonic_build::configure()
         .type_attribute(".", "#[derive(::serde::Serialize, ::serde::Deserialize)]")
         .build_server(false)
         .build_client(true)
         .compile(
-            &["../../proto/TendermintLight.proto"],
-            &["../../proto", "third_party/proto/"],
+            &[
+                "../../proto/TendermintLight.proto",
+                channel_proto_dir
+                    .join("ibc/Channel.proto")
+                    .to_str()
+                    .unwrap(),
+            ],
+            &[
+                // first, so that the copy shadows the original
+                channel_proto_dir.to_str().unwrap(),
+                "../../proto",
+                "third_party/proto/",
+            ],
+        )
+        .unwrap();
+
+    let mut bindings = String::new();
+    for (name, module) in CONTRACTS {
+        bindings.push_str(&generate_contract(name, module));
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("contracts.rs");
+    fs::write(out, bindings).unwrap();
+}
+
+fn read_json_abi(path: &str) -> Value {
+    let json: Value = serde_json::from_str(&fs::read_to_string(path).unwrap())
+        .unwrap_or_else(|e| panic!("{} is not valid JSON: {}", path, e));
+
+    json["abi"].clone()
+}
+
+/// ABI from the truffle artifact (`truffle compile`), falling back to the snapshot checked
+/// in under `abi/` so that the crate builds without the contracts being compiled. When both
+/// exist they must match, so that a stale snapshot cannot go unnoticed.
+fn read_abi(name: &str) -> (String, Value) {
+    let artifact = format!("../../build/contracts/{}.json", name);
+    let snapshot = format!("abi/{}.json", name);
+    println!("cargo:rerun-if-changed={}", artifact);
+    println!("cargo:rerun-if-changed={}", snapshot);
+
+    let snapshot_abi = read_json_abi(&snapshot);
+    if !Path::new(&artifact).exists() {
+        return (snapshot, snapshot_abi);
+    }
+
+    let artifact_abi = read_json_abi(&artifact);
+    if artifact_abi != snapshot_abi {
+        panic!(
+            "the ABI snapshot {} differs from the artifact {}, refresh it with \
+             `cp build/contracts/{}.json test/demo/abi/`",
+            snapshot, artifact, name
+        );
+    }
+
+    (artifact, artifact_abi)
+}
+
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.trim_end_matches('_').chars().collect();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            if prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+
+    match out.as_str() {
+        "type" | "ref" | "in" | "fn" | "mod" | "move" | "self" | "struct" | "use" | "match" => {
+            format!("{}_", out)
+        }
+        _ => out,
+    }
+}
+
+/// Rust name of the param, `arg<i>` for unnamed ones
+fn param_name(param: &Value, i: usize) -> String {
+    match param["name"].as_str().unwrap_or_default() {
+        "" => format!("arg{}", i),
+        name => snake_case(name),
+    }
+}
+
+/// `struct IBCMsgs.MsgCreateClient` -> `MsgCreateClient`, `struct Packet.Data` -> `Packet`
+fn struct_name(param: &Value) -> String {
+    let internal = param["internalType"].as_str().unwrap();
+    let qualified = internal
+        .trim_start_matches("struct ")
+        .split('[')
+        .next()
+        .unwrap();
+
+    match qualified.rsplit_once('.') {
+        Some((library, "Data")) => library.to_string(),
+        Some((_, name)) => name.to_string(),
+        None => qualified.to_string(),
+    }
+}
+
+/// Rust type of the param, collecting the structs it refers to
+fn rust_type(param: &Value, structs: &mut BTreeMap<String, Vec<Value>>) -> String {
+    let kind = param["type"].as_str().unwrap();
+
+    if let Some(element) = kind.strip_suffix("[]") {
+        let mut element_param = param.clone();
+        element_param["type"] = Value::from(element);
+        return format!("Vec<{}>", rust_type(&element_param, structs));
+    }
+
+    if kind == "tuple" {
+        let name = struct_name(param);
+        if !structs.contains_key(&name) {
+            let components = param["components"].as_array().unwrap().clone();
+            structs.insert(name.clone(), components.clone());
+            for component in &components {
+                rust_type(component, structs);
+            }
+        }
+        return name;
+    }
+
+    let bits = |prefix: &str| kind[prefix.len()..].parse::<usize>().unwrap_or(256);
+    match kind {
+        "address" => "Address".to_string(),
+        "bool" => "bool".to_string(),
+        "string" => "String".to_string(),
+        "bytes" => "Vec<u8>".to_string(),
+        "bytes32" => "H256".to_string(),
+        _ if kind.starts_with("bytes") => format!("[u8; {}]", bits("bytes")),
+        _ if kind.starts_with("uint") => match bits("uint") {
+            8 => "u8".to_string(),
+            16 => "u16".to_string(),
+            32 => "u32".to_string(),
+            64 => "u64".to_string(),
+            128 => "u128".to_string(),
+            _ => "U256".to_string(),
+        },
+        _ if kind.starts_with("int") => match bits("int") {
+            8 => "i8".to_string(),
+            16 => "i16".to_string(),
+            32 => "i32".to_string(),
+            64 => "i64".to_string(),
+            128 => "i128".to_string(),
+            _ => panic!("unsupported ABI type {}", kind),
+        },
+        _ => panic!("unsupported ABI type {}", kind),
+    }
+}
+
+/// Canonical signature type of the param (`(string,uint64)` for tuples)
+fn signature_type(param: &Value) -> String {
+    let kind = param["type"].as_str().unwrap();
+    match kind.strip_prefix("tuple") {
+        Some(suffix) => {
+            let components: Vec<String> = param["components"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(signature_type)
+                .collect();
+            format!("({}){}", components.join(","), suffix)
+        }
+        None => kind.to_string(),
+    }
+}
+
+fn signature(item: &Value) -> String {
+    let inputs: Vec<String> = item["inputs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(signature_type)
+        .collect();
+
+    format!("{}({})", item["name"].as_str().unwrap(), inputs.join(","))
+}
+
+fn generate_struct(
+    out: &mut String,
+    name: &str,
+    components: &[Value],
+    structs: &mut BTreeMap<String, Vec<Value>>,
+) {
+    let fields: Vec<(String, String)> = components
+        .iter()
+        .enumerate()
+        .map(|(i, component)| (param_name(component, i), rust_type(component, structs)))
+        .collect();
+
+    writeln!(out, "    #[derive(Clone, Debug, Default, PartialEq)]").unwrap();
+    writeln!(out, "    pub struct {} {{", name).unwrap();
+    for (field, ty) in &fields {
+        writeln!(out, "        pub {}: {},", field, ty).unwrap();
+    }
+    writeln!(out, "    }}\n").unwrap();
+
+    writeln!(out, "    impl Tokenizable for {} {{", name).unwrap();
+    writeln!(
+        out,
+        "        fn from_token(token: Token) -> Result<Self, Error> {{"
+    )
+    .unwrap();
+    writeln!(out, "            match token {{").unwrap();
+    writeln!(
+        out,
+        "                Token::Tuple(tokens) if tokens.len() == {} => {{",
+        fields.len()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "                    let mut tokens = tokens.into_iter();"
+    )
+    .unwrap();
+    writeln!(out, "                    Ok({} {{", name).unwrap();
+    for (field, _) in &fields {
+        writeln!(
+            out,
+            "                        {}: Tokenizable::from_token(tokens.next().unwrap())?,",
+            field
+        )
+        .unwrap();
+    }
+    writeln!(out, "                    }})").unwrap();
+    writeln!(out, "                }}").unwrap();
+    writeln!(out, "                other => Err(Error::InvalidOutputType(format!(\"expected `{}`, got {{:?}}\", other))),", name).unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "        }}\n").unwrap();
+    writeln!(out, "        fn into_token(self) -> Token {{").unwrap();
+    let tokens: Vec<String> = fields
+        .iter()
+        .map(|(field, _)| format!("self.{}.into_token()", field))
+        .collect();
+    writeln!(out, "            Token::Tuple(vec![{}])", tokens.join(", ")).unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}\n").unwrap();
+    writeln!(out, "    impl TokenizableItem for {} {{}}\n", name).unwrap();
+}
+
+fn generate_contract(name: &str, module: &str) -> String {
+    let (path, abi) = read_abi(name);
+    let items = abi.as_array().unwrap();
+    let mut structs = BTreeMap::new();
+    let mut body = String::new();
+    let mut calls = String::new();
+
+    // functions (overloads get a numeric suffix)
+    let mut seen = BTreeMap::new();
+    for item in items.iter().filter(|item| item["type"] == "function") {
+        let function = item["name"].as_str().unwrap();
+        let count = seen.entry(function.to_string()).or_insert(0);
+        let overload = *count;
+        let method = match overload {
+            0 => snake_case(function),
+            n => format!("{}{}", snake_case(function), n),
+        };
+        *count += 1;
+
+        let inputs = item["inputs"].as_array().unwrap();
+        let args: Vec<(String, String)> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| (param_name(input, i), rust_type(input, &mut structs)))
+            .collect();
+        let params: String = args
+            .iter()
+            .map(|(arg, ty)| format!("{}: {}", arg, ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let tokens: String = args
+            .iter()
+            .map(|(arg, _)| format!("{}.into_token()", arg))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        writeln!(calls, "    /// `{}` calldata", signature(item)).unwrap();
+        writeln!(
+            calls,
+            "    pub fn {}_call({}) -> Vec<u8> {{",
+            method, params
+        )
+        .unwrap();
+        writeln!(
+            calls,
+            "        encode_call({:?}, {}, &[{}])",
+            function, overload, tokens
+        )
+        .unwrap();
+        writeln!(calls, "    }}\n").unwrap();
+
+        let outputs: Vec<String> = item["outputs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|output| rust_type(output, &mut structs))
+            .collect();
+        let view = matches!(
+            item["stateMutability"].as_str(),
+            Some("view") | Some("pure")
+        );
+        if view && !outputs.is_empty() {
+            let output = match outputs.len() {
+                1 => outputs[0].clone(),
+                _ => format!("({})", outputs.join(", ")),
+            };
+            let self_params = match params.is_empty() {
+                true => "&self".to_string(),
+                false => format!("&self, {}", params),
+            };
+            writeln!(body, "        /// Calls `{}`", signature(item)).unwrap();
+            writeln!(
+                body,
+                "        pub async fn {}({}) -> web3::contract::Result<{}> {{",
+                method, self_params, output
+            )
+            .unwrap();
+            writeln!(body, "            self.contract").unwrap();
+            writeln!(
+                body,
+                "                .query({:?}, &[{}][..], None, Options::default(), None)",
+                function, tokens
+            )
+            .unwrap();
+            writeln!(body, "                .await").unwrap();
+            writeln!(body, "        }}\n").unwrap();
+        }
+    }
+
+    // events
+    let mut events = String::new();
+    for item in items.iter().filter(|item| item["type"] == "event") {
+        let event = item["name"].as_str().unwrap();
+        let fields: Vec<(String, String)> = item["inputs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, input)| (param_name(input, i), rust_type(input, &mut structs)))
+            .collect();
+
+        writeln!(events, "        /// `{}` event", signature(item)).unwrap();
+        writeln!(events, "        #[derive(Clone, Debug, PartialEq)]").unwrap();
+        writeln!(events, "        pub struct {} {{", event).unwrap();
+        for (field, ty) in &fields {
+            writeln!(events, "            pub {}: {},", field, ty).unwrap();
+        }
+        writeln!(events, "        }}\n").unwrap();
+        writeln!(events, "        impl {} {{", event).unwrap();
+        writeln!(
+            events,
+            "            pub const NAME: &'static str = {:?};\n",
+            event
+        )
+        .unwrap();
+        writeln!(events, "            /// Topic 0 of the logs of the event").unwrap();
+        writeln!(events, "            pub fn topic() -> H256 {{").unwrap();
+        writeln!(
+            events,
+            "                abi().event(Self::NAME).unwrap().signature()"
+        )
+        .unwrap();
+        writeln!(events, "            }}\n").unwrap();
+        writeln!(
+            events,
+            "            /// Decodes the event from a log of the contract"
+        )
+        .unwrap();
+        writeln!(
+            events,
+            "            pub fn from_log(log: &Log) -> Result<Self, Error> {{"
         )
         .unwrap();
+        writeln!(events, "                let raw = ethabi::RawLog {{").unwrap();
+        writeln!(events, "                    topics: log.topics.clone(),").unwrap();
+        writeln!(events, "                    data: log.data.0.clone(),").unwrap();
+        writeln!(events, "                }};").unwrap();
+        writeln!(events, "                let mut params = abi().event(Self::NAME)?.parse_log(raw)?.params.into_iter();\n").unwrap();
+        writeln!(events, "                Ok({} {{", event).unwrap();
+        for (field, _) in &fields {
+            writeln!(
+                events,
+                "                    {}: Tokenizable::from_token(params.next().unwrap().value)?,",
+                field
+            )
+            .unwrap();
+        }
+        writeln!(events, "                }})").unwrap();
+        writeln!(events, "            }}").unwrap();
+        writeln!(events, "        }}\n").unwrap();
+    }
+
+    // structs, including the ones only referenced by other structs
+    let mut types = String::new();
+    let mut generated = BTreeMap::new();
+    while let Some((struct_name, components)) = structs
+        .iter()
+        .find(|(name, _)| !generated.contains_key(*name))
+        .map(|(name, components)| (name.clone(), components.clone()))
+    {
+        generate_struct(&mut types, &struct_name, &components, &mut structs);
+        generated.insert(struct_name, ());
+    }
+
+    let abi_json = serde_json::to_string(&abi).unwrap();
+    let mut out = String::new();
+    writeln!(
+        out,
+        "/// Typed bindings of `{}`, generated from `{}`",
+        name, path
+    )
+    .unwrap();
+    writeln!(out, "pub mod {} {{", module).unwrap();
+    writeln!(out, "    #![allow(dead_code, unused_imports, clippy::all)]").unwrap();
+    writeln!(out, "    use std::sync::OnceLock;").unwrap();
+    writeln!(out, "    use web3::{{").unwrap();
+    writeln!(
+        out,
+        "        contract::{{tokens::{{Tokenizable, TokenizableItem}}, Contract, Error, Options}},"
+    )
+    .unwrap();
+    writeln!(out, "        ethabi::{{self, Token}},").unwrap();
+    writeln!(out, "        types::{{Address, Log, H256, U256}},").unwrap();
+    writeln!(out, "        Transport,").unwrap();
+    writeln!(out, "    }};\n").unwrap();
+    writeln!(out, "    /// Artifact the bindings were generated from").unwrap();
+    writeln!(out, "    pub const ABI_SOURCE: &str = {:?};\n", path).unwrap();
+    writeln!(out, "    /// JSON ABI of the contract").unwrap();
+    writeln!(out, "    pub const ABI: &str = {:?};\n", abi_json).unwrap();
+    writeln!(out, "    pub fn abi() -> &'static ethabi::Contract {{").unwrap();
+    writeln!(
+        out,
+        "        static ABI_CONTRACT: OnceLock<ethabi::Contract> = OnceLock::new();"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        ABI_CONTRACT.get_or_init(|| ethabi::Contract::load(ABI.as_bytes()).unwrap())"
+    )
+    .unwrap();
+    writeln!(out, "    }}\n").unwrap();
+    writeln!(
+        out,
+        "    fn encode_call(name: &str, overload: usize, tokens: &[Token]) -> Vec<u8> {{"
+    )
+    .unwrap();
+    writeln!(out, "        abi()").unwrap();
+    writeln!(out, "            .functions_by_name(name)").unwrap();
+    writeln!(out, "            .ok()").unwrap();
+    writeln!(
+        out,
+        "            .and_then(|functions| functions[overload].encode_input(tokens).ok())"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "            .expect(\"arguments are typed after the ABI\")"
+    )
+    .unwrap();
+    writeln!(out, "    }}\n").unwrap();
+    out.push_str(&types);
+    out.push_str(&calls);
+    writeln!(out, "    pub struct {}<T: Transport> {{", name).unwrap();
+    writeln!(out, "        contract: Contract<T>,").unwrap();
+    writeln!(out, "    }}\n").unwrap();
+    writeln!(out, "    impl<T: Transport> {}<T> {{", name).unwrap();
+    writeln!(
+        out,
+        "        pub fn new(eth: web3::api::Eth<T>, address: Address) -> Self {{"
+    )
+    .unwrap();
+    writeln!(out, "            {} {{", name).unwrap();
+    writeln!(
+        out,
+        "                contract: Contract::new(eth, address, abi().clone()),"
+    )
+    .unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "        }}\n").unwrap();
+    writeln!(out, "        pub fn address(&self) -> Address {{").unwrap();
+    writeln!(out, "            self.contract.address()").unwrap();
+    writeln!(out, "        }}\n").unwrap();
+    writeln!(out, "        pub fn contract(&self) -> &Contract<T> {{").unwrap();
+    writeln!(out, "            &self.contract").unwrap();
+    writeln!(out, "        }}\n").unwrap();
+    out.push_str(&body);
+    writeln!(out, "    }}\n").unwrap();
+    writeln!(out, "    pub mod events {{").unwrap();
+    writeln!(out, "        use super::*;\n").unwrap();
+    out.push_str(&events);
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}\n").unwrap();
+
+    out
 }
@@ -0,0 +1,5 @@
+//! Typed bindings of the IBC contracts, generated at build time (see `build.rs`) from the
+//! truffle artifacts in `build/contracts`, or from the ABI snapshot in `abi/` when the
+//! contracts are not compiled. The ABI is embedded, nothing is read at runtime.
+
+include!(concat!(env!("OUT_DIR"), "/contracts.rs"));
@@ -0,0 +1,293 @@
+use ethabi::{ParamType, Token};
+use rlp::RlpStream;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use web3::{
+    signing::{keccak256, Key, Signature},
+    types::{Address, Bytes, CallRequest, H256, U256, U64},
+    Transport,
+};
+
+/// Address of the Celo core contracts registry (same on every Celo network)
+pub const CELO_REGISTRY_ADDRESS: &str = "0x000000000000000000000000000000000000ce10";
+
+/// Celo (pre CIP-64) transaction
+///
+/// On top of the Ethereum legacy fields, Celo transactions carry the fee currency (`None` means
+/// the fees are paid in CELO) and an optional gateway fee paid to a full node operator.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CeloTransaction {
+    pub nonce: U256,
+    pub gas_price: U256,
+    pub gas: U256,
+    pub fee_currency: Option<Address>,
+    pub gateway_fee_recipient: Option<Address>,
+    pub gateway_fee: U256,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub chain_id: u64,
+}
+
+/// Signed, RLP encoded Celo transaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignedCeloTransaction {
+    pub raw_transaction: Bytes,
+    pub transaction_hash: H256,
+}
+
+fn append_address(rlp: &mut RlpStream, address: &Option<Address>) {
+    match address {
+        Some(address) => rlp.append(address),
+        None => rlp.append(&""),
+    };
+}
+
+impl CeloTransaction {
+    fn rlp_append_fields(&self, rlp: &mut RlpStream) {
+        rlp.append(&self.nonce);
+        rlp.append(&self.gas_price);
+        rlp.append(&self.gas);
+        append_address(rlp, &self.fee_currency);
+        append_address(rlp, &self.gateway_fee_recipient);
+        rlp.append(&self.gateway_fee);
+        append_address(rlp, &self.to);
+        rlp.append(&self.value);
+        rlp.append(&self.data);
+    }
+
+    /// RLP encode the unsigned transaction (EIP-155 style, chain id in place of the signature)
+    fn rlp_append_unsigned(&self, rlp: &mut RlpStream) {
+        rlp.begin_list(12);
+        self.rlp_append_fields(rlp);
+        rlp.append(&self.chain_id);
+        rlp.append(&0u8);
+        rlp.append(&0u8);
+    }
+
+    fn rlp_append_signed(&self, rlp: &mut RlpStream, signature: &Signature) {
+        rlp.begin_list(12);
+        self.rlp_append_fields(rlp);
+        rlp.append(&signature.v);
+        rlp.append(&U256::from_big_endian(signature.r.as_bytes()));
+        rlp.append(&U256::from_big_endian(signature.s.as_bytes()));
+    }
+
+    /// Hash the signer has to sign
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut rlp = RlpStream::new();
+        self.rlp_append_unsigned(&mut rlp);
+
+        keccak256(rlp.as_raw())
+    }
+
+    /// Sign and return the raw signed transaction
+    pub fn sign(&self, key: impl Key) -> web3::Result<SignedCeloTransaction> {
+        let signature = key
+            .sign(&self.signing_hash(), Some(self.chain_id))
+            .map_err(|e| web3::Error::Decoder(e.to_string()))?;
+
+        let mut rlp = RlpStream::new();
+        self.rlp_append_signed(&mut rlp, &signature);
+
+        Ok(SignedCeloTransaction {
+            transaction_hash: keccak256(rlp.as_raw()).into(),
+            raw_transaction: rlp.out().to_vec().into(),
+        })
+    }
+}
+
+/// Transaction as returned by the Celo node RPC (`eth_getTransactionByHash`, `txpool_content`)
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcCeloTransaction {
+    pub hash: H256,
+    pub nonce: U256,
+    pub block_number: Option<U64>,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub gas_price: U256,
+    pub gas: U256,
+    pub input: Bytes,
+    pub fee_currency: Option<Address>,
+    pub gateway_fee_recipient: Option<Address>,
+    #[serde(default)]
+    pub gateway_fee: U256,
+}
+
+impl RpcCeloTransaction {
+    pub fn into_transaction(self, chain_id: u64) -> CeloTransaction {
+        CeloTransaction {
+            nonce: self.nonce,
+            gas_price: self.gas_price,
+            gas: self.gas,
+            fee_currency: self.fee_currency,
+            gateway_fee_recipient: self.gateway_fee_recipient,
+            gateway_fee: self.gateway_fee,
+            to: self.to,
+            value: self.value,
+            data: self.input.0,
+            chain_id,
+        }
+    }
+}
+
+pub async fn get_transaction<T: Transport>(
+    transport: &T,
+    hash: H256,
+) -> web3::Result<Option<RpcCeloTransaction>> {
+    let response = transport
+        .execute("eth_getTransactionByHash", vec![json!(hash)])
+        .await?;
+
+    serde_json::from_value(response).map_err(|e| web3::Error::Decoder(e.to_string()))
+}
+
+async fn eth_call<T: Transport>(
+    transport: &T,
+    to: Address,
+    data: Vec<u8>,
+    output: &[ParamType],
+) -> web3::Result<Vec<Token>> {
+    let result = web3::Web3::new(transport)
+        .eth()
+        .call(
+            CallRequest {
+                to: Some(to),
+                data: Some(data.into()),
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+
+    ethabi::decode(output, &result.0).map_err(|e| web3::Error::Decoder(e.to_string()))
+}
+
+/// Resolves a fee currency given either as an address or a stable token name (cusd, ceur, creal)
+pub async fn resolve_fee_currency<T: Transport>(
+    transport: &T,
+    currency: &str,
+) -> web3::Result<Address> {
+    if currency.starts_with("0x") {
+        return currency
+            .parse()
+            .map_err(|_| web3::Error::Decoder(format!("invalid fee currency: {}", currency)));
+    }
+
+    let identifier = match currency.to_lowercase().as_str() {
+        "cusd" => "StableToken",
+        "ceur" => "StableTokenEUR",
+        "creal" => "StableTokenBRL",
+        _ => {
+            return Err(web3::Error::Decoder(format!(
+                "unknown fee currency: {}",
+                currency
+            )))
+        }
+    };
+
+    // Registry.getAddressForString(string)
+    let mut data = keccak256(b"getAddressForString(string)")[..4].to_vec();
+    data.extend(ethabi::encode(&[Token::String(identifier.to_string())]));
+
+    let registry = CELO_REGISTRY_ADDRESS.parse().unwrap();
+    match eth_call(transport, registry, data, &[ParamType::Address])
+        .await?
+        .pop()
+    {
+        Some(Token::Address(address)) if !address.is_zero() => Ok(address),
+        _ => Err(web3::Error::Decoder(format!(
+            "{} is not registered in the Celo registry",
+            identifier
+        ))),
+    }
+}
+
+/// Symbol of the currency the fees are debited in
+pub async fn fee_currency_symbol<T: Transport>(
+    transport: &T,
+    fee_currency: Option<Address>,
+) -> String {
+    let currency = match fee_currency {
+        Some(currency) => currency,
+        None => return "CELO".to_string(),
+    };
+
+    let data = keccak256(b"symbol()")[..4].to_vec();
+    match eth_call(transport, currency, data, &[ParamType::String]).await {
+        Ok(mut tokens) => match tokens.pop() {
+            Some(Token::String(symbol)) => symbol,
+            _ => format!("{:?}", currency),
+        },
+        Err(_) => format!("{:?}", currency),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::key::SecretKey;
+    use web3::signing::SecretKeyRef;
+
+    // vectors derived independently (RLP, keccak256 and RFC 6979 secp256k1 signatures) from the
+    // key of the EIP-155 example, whose Ethereum vector the derivation reproduces
+    fn key() -> SecretKey {
+        SecretKey::from_slice(&[0x46; 32]).unwrap()
+    }
+
+    fn check(tx: &CeloTransaction, signing_hash: &str, raw: &str, hash: &str) {
+        assert_eq!(hex::encode(tx.signing_hash()), signing_hash);
+
+        let signed = tx.sign(SecretKeyRef::new(&key())).unwrap();
+        assert_eq!(hex::encode(&signed.raw_transaction.0), raw);
+        assert_eq!(hex::encode(signed.transaction_hash.as_bytes()), hash);
+    }
+
+    #[test]
+    fn sign_fees_in_celo() {
+        let tx = CeloTransaction {
+            nonce: 9.into(),
+            gas_price: 20_000_000_000u64.into(),
+            gas: 21_000.into(),
+            to: Some(Address::repeat_byte(0x35)),
+            value: 1_000_000_000_000_000_000u64.into(),
+            chain_id: 44787,
+            ..Default::default()
+        };
+
+        check(
+            &tx,
+            "459986bc3f7a478e32c058aabff8351376396380dcf663c98a1832166c7f5947",
+            "f872098504a817c800825208808080943535353535353535353535353535353535353535880de0b6b3a76400008083015e0aa04ecc112bb75d27f664030e2e576adf6399576b3e5d2f7076ecf2644a6f46b812a001b1b100b7b6f3b13be752e7bafccfe7a18cd2dc6597cf6b5dbb85aa5a7ddc7b",
+            "230153e829474bb582bef5e120fc9acb893e10e9b7c849639aabcc66366db0ec",
+        );
+    }
+
+    #[test]
+    fn sign_fee_currency_and_gateway_fee() {
+        let tx = CeloTransaction {
+            nonce: 7.into(),
+            gas_price: 500_000_000.into(),
+            gas: 250_000.into(),
+            fee_currency: Some(
+                "0x874069fa1eb16d44d622f2e0ca25eea172369bc1"
+                    .parse()
+                    .unwrap(),
+            ),
+            gateway_fee_recipient: Some(Address::repeat_byte(0x12)),
+            gateway_fee: 10_000_000_000_000_000u64.into(),
+            to: Some(Address::repeat_byte(0x35)),
+            value: U256::zero(),
+            data: vec![0xde, 0xad, 0xbe, 0xef],
+            chain_id: 44787,
+        };
+
+        check(
+            &tx,
+            "baacb182072ca4b80db90e312ea443a1acf43dc639c8116f6f459435af5ea0e8",
+            "f89d07841dcd65008303d09094874069fa1eb16d44d622f2e0ca25eea172369bc1941212121212121212121212121212121212121212872386f26fc100009435353535353535353535353535353535353535358084deadbeef83015e0aa0e1e01f13200adb2b10012ba4f732f441d196f5e8fc2eef2e3d36e41881cc35e6a073259a699ba49afd85c464dcfc0c7018babc5ba0347c51ad32187998e86f70cf",
+            "09367b5d3e6a90bb6dec1ea7bbaad6f54a8472864535a16e3aec4966a32929fe",
+        );
+    }
+}
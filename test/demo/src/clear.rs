@@ -0,0 +1,718 @@
+//! Clearing of the packets left behind on a channel between Celo and the Cosmos chain, e.g.
+//! while the relayer was down.
+//!
+//! The sequences, receipts and commitments IBCHost stores for the channel, and the packets Celo
+//! sent on it according to the event indexer, are compared with the state of the counterparty
+//! channel on the Cosmos chain (gRPC), which gives:
+//! - the packets the Cosmos chain sent that Celo has not received (`recvPacket`),
+//! - the packets Celo sent that the Cosmos chain received, whose acknowledgement Celo has not
+//!   received (`acknowledgePacket`),
+//! - the packets and acknowledgements the Cosmos chain has not received. Those are reported
+//!   only, the relayer submits to Celo only.
+//!
+//! Packets and acknowledgements are read from the `send_packet` and `write_acknowledgement`
+//! events of the Cosmos txs, their proofs from the `ibc` store (`abci_query` of
+//! `store/ibc/key`) at the height before the proof height, whose header carries the app hash
+//! they are verified against. Once every message is built, the client is updated to the proof
+//! height if needed, and the messages are queued until the delay period of the connection has
+//! passed (see `delay`).
+//!
+//! NOTE: TendermintLightClient verifies a single ICS-23 proof keyed by the IBCHost storage slot
+//! of the commitment (`IBCIdentifier`), while a Cosmos SDK chain proves its commitments with a
+//! chain of proofs (the `ibc` store, then the multistore) keyed by their ICS-24 path. `plan`
+//! probes the proof format with the counterparty channel end and reports such a chain as
+//! unsupported, `clear` then refuses to send anything.
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::TryFrom,
+    error::Error,
+    str::FromStr,
+};
+use tendermint::{abci::Path, merkle::proof::ProofOp};
+use tendermint_rpc::{query::Query, Client, HttpClient, Order};
+use tracing::{info, warn};
+use web3::{types::H256, Transport};
+
+use crate::{
+    bindings::ibc_handler::{Height, MsgPacketAcknowledgement, MsgPacketRecv, Packet},
+    commitment::{self, PacketCheck},
+    delay::DelayPeriod,
+    grpc::CosmosQuery,
+    indexer::{self, IbcEvent, Indexer},
+    proof::CommitmentPath,
+    proto::ibc::channel::channel::{Order as ChannelOrder, State as ChannelState},
+    relay::Relayer,
+};
+
+/// Channel state on both chains and what is left to relay on it
+#[derive(Clone, Debug, Serialize)]
+pub struct ClearPlan {
+    pub port_id: String,
+    pub channel_id: String,
+    pub counterparty_port_id: String,
+    pub counterparty_channel_id: String,
+    pub connection_id: String,
+    /// Client of the Cosmos chain on Celo, verifying the proofs
+    pub client_id: String,
+    pub ordered: bool,
+    /// Delay period of the connection, proofs are accepted once it has passed since the client
+    /// stored the consensus state of the proof height
+    pub delay_period: DelayPeriod,
+    pub next_sequence_send: u64,
+    pub next_sequence_recv: u64,
+    pub next_sequence_ack: u64,
+    /// Packets sent by the Cosmos chain that Celo has not received
+    pub recv: Vec<u64>,
+    /// Packets sent by Celo whose acknowledgement Celo has not received
+    pub ack: Vec<u64>,
+    /// Packets sent by Celo that the Cosmos chain has not received
+    pub unreceived_on_cosmos: Vec<u64>,
+    /// Packets sent by the Cosmos chain whose acknowledgement the Cosmos chain has not received
+    pub unacknowledged_on_cosmos: Vec<u64>,
+    /// Why the proofs of the Cosmos chain cannot be verified on Celo, `clear` refuses the plan
+    /// then. Probed with the proof of the counterparty channel end.
+    pub unsupported: Option<String>,
+}
+
+/// Packet message relayed to Celo
+#[derive(Clone, Debug, Serialize)]
+pub struct Relayed {
+    pub message: &'static str,
+    pub sequence: u64,
+    pub tx_hash: H256,
+}
+
+/// Packet message left out, and why
+#[derive(Clone, Debug, Serialize)]
+pub struct Skipped {
+    pub message: &'static str,
+    pub sequence: u64,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ClearReport {
+    pub proof_height: Option<u64>,
+    pub relayed: Vec<Relayed>,
+    pub skipped: Vec<Skipped>,
+}
+
+/// Compares the state of the channel on Celo with the one of its counterparty on the Cosmos chain
+pub async fn plan<T: Transport>(
+    indexer: &Indexer<'_, T>,
+    rpc: &HttpClient,
+    cosmos: &CosmosQuery,
+    port_id: &str,
+    channel_id: &str,
+) -> Result<ClearPlan, Box<dyn Error>> {
+    let contracts = indexer.contracts();
+    let channel = contracts
+        .channel(port_id, channel_id)
+        .await?
+        .ok_or_else(|| format!("channel {}/{} not found on Celo", port_id, channel_id))?;
+    if channel.state != ChannelState::Open as u8 {
+        return Err(format!("channel {}/{} is not open on Celo", port_id, channel_id).into());
+    }
+    let connection_id = channel
+        .connection_hops
+        .first()
+        .ok_or_else(|| format!("channel {}/{} has no connection", port_id, channel_id))?
+        .clone();
+    let connection = contracts
+        .connection(&connection_id)
+        .await?
+        .ok_or_else(|| format!("connection {} not found on Celo", connection_id))?;
+
+    let counterparty = channel.counterparty;
+    if cosmos
+        .channel(&counterparty.port_id, &counterparty.channel_id)
+        .await?
+        .is_none()
+    {
+        return Err(format!(
+            "counterparty channel {}/{} not found on the Cosmos chain",
+            counterparty.port_id, counterparty.channel_id
+        )
+        .into());
+    }
+
+    let ordered = channel.ordering == ChannelOrder::Ordered as u8;
+    let delay_period = DelayPeriod::new(
+        connection.delay_period,
+        contracts.expected_time_per_block().await?,
+    );
+    let next_sequence_send = contracts.next_sequence_send(port_id, channel_id).await?;
+    let next_sequence_recv = contracts.next_sequence_recv(port_id, channel_id).await?;
+    let next_sequence_ack = contracts.next_sequence_ack(port_id, channel_id).await?;
+
+    // packets of the Cosmos chain: a commitment is stored until the acknowledgement is received
+    let mut recv = Vec::new();
+    let mut received = Vec::new();
+    let mut cosmos_sent = cosmos
+        .packet_commitments(&counterparty.port_id, &counterparty.channel_id)
+        .await?;
+    cosmos_sent.sort_unstable();
+    for sequence in cosmos_sent {
+        let is_received = match ordered {
+            true => sequence < next_sequence_recv,
+            false => {
+                contracts
+                    .has_packet_receipt(port_id, channel_id, sequence)
+                    .await?
+            }
+        };
+        match is_received {
+            true => received.push(sequence),
+            false => recv.push(sequence),
+        }
+    }
+    let unacknowledged_on_cosmos = match received.is_empty() {
+        true => Vec::new(),
+        false => {
+            cosmos
+                .unreceived_acks(&counterparty.port_id, &counterparty.channel_id, &received)
+                .await?
+        }
+    };
+
+    // packets of Celo: the indexed ones without an acknowledgement, of which timed out ones have
+    // no commitment anymore
+    indexer.sync().await?;
+    let (sent, unacknowledged) = indexed_sends(
+        indexer.events(0)?.into_iter().map(|indexed| indexed.event),
+        port_id,
+        channel_id,
+    );
+    if sent + 1 < next_sequence_send {
+        warn!(
+            indexed = sent,
+            sent = next_sequence_send - 1,
+            "the indexer has not seen every packet sent on the channel, start it before the channel was opened"
+        );
+    }
+    let first = if ordered { next_sequence_ack } else { 1 };
+    let mut celo_sent = Vec::new();
+    for sequence in unacknowledged
+        .into_iter()
+        .filter(|&sequence| sequence >= first)
+    {
+        if contracts
+            .packet_commitment(port_id, channel_id, sequence)
+            .await?
+            .is_some()
+        {
+            celo_sent.push(sequence);
+        }
+    }
+    let unreceived_on_cosmos = match celo_sent.is_empty() {
+        true => Vec::new(),
+        false => {
+            cosmos
+                .unreceived_packets(&counterparty.port_id, &counterparty.channel_id, &celo_sent)
+                .await?
+        }
+    };
+    let ack = celo_sent
+        .into_iter()
+        .filter(|sequence| !unreceived_on_cosmos.contains(sequence))
+        .collect();
+
+    let channel_path = CommitmentPath::Channel {
+        port_id: counterparty.port_id.clone(),
+        channel_id: counterparty.channel_id.clone(),
+    };
+    let unsupported = unsupported_proof(
+        &channel_path,
+        &query_proof_ops(rpc, &channel_path, None).await?,
+    );
+    if let Some(reason) = &unsupported {
+        warn!(%reason, "packets cannot be cleared");
+    }
+
+    Ok(ClearPlan {
+        port_id: port_id.to_string(),
+        channel_id: channel_id.to_string(),
+        counterparty_port_id: counterparty.port_id,
+        counterparty_channel_id: counterparty.channel_id,
+        connection_id,
+        client_id: connection.client_id,
+        ordered,
+        delay_period,
+        next_sequence_send,
+        next_sequence_recv,
+        next_sequence_ack,
+        recv,
+        ack,
+        unreceived_on_cosmos,
+        unacknowledged_on_cosmos,
+        unsupported,
+    })
+}
+
+/// Number of the packets sent on the channel by Celo among the indexed events, and the
+/// sequences of those without an acknowledgement, ascending. A sequence sent again after its
+/// acknowledgement counts once and is unacknowledged again.
+fn indexed_sends(
+    events: impl IntoIterator<Item = IbcEvent>,
+    port_id: &str,
+    channel_id: &str,
+) -> (u64, BTreeSet<u64>) {
+    let on_channel = |packet: &indexer::Packet| {
+        packet.source_port == port_id && packet.source_channel == channel_id
+    };
+
+    let mut sent = BTreeSet::new();
+    let mut unacknowledged = BTreeSet::new();
+    for event in events {
+        match event {
+            IbcEvent::SendPacket { packet } if on_channel(&packet) => {
+                sent.insert(packet.sequence);
+                unacknowledged.insert(packet.sequence);
+            }
+            IbcEvent::AcknowledgePacket { packet, .. } if on_channel(&packet) => {
+                unacknowledged.remove(&packet.sequence);
+            }
+            _ => {}
+        }
+    }
+
+    (sent.len() as u64, unacknowledged)
+}
+
+/// Packet message waiting to be sent
+enum PacketMsg {
+    Recv(MsgPacketRecv),
+    Ack(MsgPacketAcknowledgement),
+}
+
+/// Relays the packets and acknowledgements of the plan to Celo, in sequence order, once the
+/// delay period of the connection has passed for the proof height.
+///
+/// Every message is built and its proof checked before anything is sent, the client is updated
+/// only if there is a message to relay. On an ordered channel, an acknowledgement the Cosmos
+/// chain has not written yet holds back the following ones; on an unordered channel it is
+/// skipped.
+pub async fn clear<T: Transport>(
+    relayer: &Relayer<'_, T>,
+    plan: &ClearPlan,
+) -> Result<ClearReport, Box<dyn Error>> {
+    let mut report = ClearReport::default();
+    if plan.recv.is_empty() && plan.ack.is_empty() {
+        return Ok(report);
+    }
+    if let Some(reason) = &plan.unsupported {
+        return Err(reason.clone().into());
+    }
+
+    let rpc = relayer.source().client();
+    let proof_height = relayer.source().latest_height().await?;
+    let query_height = proof_height - 1;
+
+    let mut queue = Vec::new();
+    for &sequence in &plan.recv {
+        let attributes = match packet_event(
+            rpc,
+            "send_packet",
+            &plan.counterparty_port_id,
+            &plan.counterparty_channel_id,
+            sequence,
+        )
+        .await?
+        {
+            Some(attributes) => attributes,
+            None => {
+                return Err(format!(
+                    "send_packet event of packet {} not found on the Cosmos chain",
+                    sequence
+                )
+                .into())
+            }
+        };
+        let packet = packet_from_event(&attributes, sequence)?;
+        let path = CommitmentPath::Packet {
+            port_id: plan.counterparty_port_id.clone(),
+            channel_id: plan.counterparty_channel_id.clone(),
+            sequence,
+        };
+        let proof = query_proof(rpc, &path, query_height).await?;
+
+        queue.push(PacketMsg::Recv(MsgPacketRecv {
+            packet,
+            proof,
+            proof_height,
+        }));
+    }
+
+    for &sequence in &plan.ack {
+        let attributes = match packet_event(
+            rpc,
+            "write_acknowledgement",
+            &plan.port_id,
+            &plan.channel_id,
+            sequence,
+        )
+        .await?
+        {
+            Some(attributes) => attributes,
+            None => {
+                warn!(
+                    sequence,
+                    "acknowledgement not written on the Cosmos chain yet"
+                );
+                report.skipped.push(Skipped {
+                    message: "acknowledgePacket",
+                    sequence,
+                    reason: "acknowledgement not written on the Cosmos chain yet".to_string(),
+                });
+                match plan.ordered {
+                    true => break,
+                    false => continue,
+                }
+            }
+        };
+        let packet = packet_from_event(&attributes, sequence)?;
+        let acknowledgement = attribute_bytes(&attributes, "packet_ack")?;
+
+        let sent = indexer::Packet::from(packet.clone());
+        match commitment::check_packet(relayer.contracts(), &(&sent).into()).await? {
+            PacketCheck::Pending => {}
+            check => {
+                return Err(format!(
+                    "packet {} found on the Cosmos chain does not match Celo: {}",
+                    sequence, check
+                )
+                .into())
+            }
+        }
+
+        let path = CommitmentPath::PacketAcknowledgement {
+            port_id: plan.counterparty_port_id.clone(),
+            channel_id: plan.counterparty_channel_id.clone(),
+            sequence,
+        };
+        let proof = query_proof(rpc, &path, query_height).await?;
+
+        queue.push(PacketMsg::Ack(MsgPacketAcknowledgement {
+            packet,
+            acknowledgement,
+            proof,
+            proof_height,
+        }));
+    }
+
+    if queue.is_empty() {
+        return Ok(report);
+    }
+    relayer
+        .update_client(&plan.client_id, proof_height, None)
+        .await?;
+    report.proof_height = Some(proof_height);
+
+    // the messages share the proof height: the relayer holds the first one back until the delay
+    // period of the connection has passed, the following ones are sent right after it
+    for msg in queue {
+        let relayed = match msg {
+            PacketMsg::Recv(msg) => Relayed {
+                message: "recvPacket",
+                sequence: msg.packet.sequence,
+                tx_hash: relayer.recv_packet(&plan.client_id, msg).await?,
+            },
+            PacketMsg::Ack(msg) => Relayed {
+                message: "acknowledgePacket",
+                sequence: msg.packet.sequence,
+                tx_hash: relayer.acknowledge_packet(&plan.client_id, msg).await?,
+            },
+        };
+        report.relayed.push(relayed);
+    }
+
+    info!(
+        relayed = report.relayed.len(),
+        skipped = report.skipped.len(),
+        "channel cleared"
+    );
+    Ok(report)
+}
+
+/// Attributes of the packet event (`send_packet` or `write_acknowledgement`) of the packet sent
+/// on the port and channel, `None` if no tx emitted it
+async fn packet_event(
+    rpc: &HttpClient,
+    event: &str,
+    source_port: &str,
+    source_channel: &str,
+    sequence: u64,
+) -> Result<Option<HashMap<String, String>>, Box<dyn Error>> {
+    let query = Query::eq(format!("{}.packet_src_port", event), source_port)
+        .and_eq(format!("{}.packet_src_channel", event), source_channel)
+        .and_eq(format!("{}.packet_sequence", event), sequence.to_string());
+    let response = rpc.tx_search(query, false, 1, 1, Order::Ascending).await?;
+
+    // a tx may carry the events of several packets
+    let sequence = sequence.to_string();
+    Ok(response
+        .txs
+        .into_iter()
+        .flat_map(|tx| tx.tx_result.events)
+        .filter(|e| e.type_str == event)
+        .map(|e| {
+            e.attributes
+                .into_iter()
+                .map(|tag| (tag.key.to_string(), tag.value.to_string()))
+                .collect::<HashMap<_, _>>()
+        })
+        .find(|attributes| {
+            attributes.get("packet_src_port").map(String::as_str) == Some(source_port)
+                && attributes.get("packet_src_channel").map(String::as_str) == Some(source_channel)
+                && attributes.get("packet_sequence") == Some(&sequence)
+        }))
+}
+
+fn attribute<'m>(
+    attributes: &'m HashMap<String, String>,
+    key: &str,
+) -> Result<&'m str, Box<dyn Error>> {
+    Ok(attributes
+        .get(key)
+        .ok_or_else(|| format!("packet event without {}", key))?)
+}
+
+/// Bytes of the attribute, from its `_hex` variant if the chain emits one (ibc-go v2 on)
+fn attribute_bytes(
+    attributes: &HashMap<String, String>,
+    key: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    match attributes.get(&format!("{}_hex", key)) {
+        Some(value) => Ok(hex::decode(value)?),
+        None => Ok(attribute(attributes, key)?.as_bytes().to_vec()),
+    }
+}
+
+fn packet_from_event(
+    attributes: &HashMap<String, String>,
+    sequence: u64,
+) -> Result<Packet, Box<dyn Error>> {
+    // `<revision number>-<revision height>`
+    let timeout_height = attribute(attributes, "packet_timeout_height")?;
+    let (revision_number, revision_height) = timeout_height
+        .split_once('-')
+        .ok_or_else(|| format!("invalid timeout height {}", timeout_height))?;
+
+    Ok(Packet {
+        sequence,
+        source_port: attribute(attributes, "packet_src_port")?.to_string(),
+        source_channel: attribute(attributes, "packet_src_channel")?.to_string(),
+        destination_port: attribute(attributes, "packet_dst_port")?.to_string(),
+        destination_channel: attribute(attributes, "packet_dst_channel")?.to_string(),
+        data: attribute_bytes(attributes, "packet_data")?,
+        timeout_height: Height {
+            revision_number: revision_number.parse()?,
+            revision_height: revision_height.parse()?,
+        },
+        timeout_timestamp: attribute(attributes, "packet_timeout_timestamp")?.parse()?,
+    })
+}
+
+/// Proof ops of the entry at `path` in the `ibc` store at `height` (the latest height if not
+/// set), verifiable against the app hash of the next header
+async fn query_proof_ops(
+    rpc: &HttpClient,
+    path: &CommitmentPath,
+    height: Option<u64>,
+) -> Result<Vec<ProofOp>, Box<dyn Error>> {
+    let height = match height {
+        Some(height) => Some(tendermint::block::Height::try_from(height)?),
+        None => None,
+    };
+    let response = rpc
+        .abci_query(
+            Some(Path::from_str("store/ibc/key")?),
+            path.to_string().into_bytes(),
+            height,
+            true,
+        )
+        .await?;
+    if response.code.is_err() {
+        return Err(format!("abci query of {} failed: {}", path, response.log).into());
+    }
+    if response.value.is_empty() {
+        return Err(format!("{} not found on the Cosmos chain", path).into());
+    }
+
+    match response.proof {
+        Some(proof) if !proof.ops.is_empty() => Ok(proof.ops),
+        _ => Err(format!("abci query of {} returned no proof", path).into()),
+    }
+}
+
+/// Why the proof ops cannot be verified by TendermintLightClient (see the module doc), `None`
+/// if they can
+fn unsupported_proof(path: &CommitmentPath, ops: &[ProofOp]) -> Option<String> {
+    match ops {
+        [op] if op.key == path.slot().as_bytes() => None,
+        _ => Some(format!(
+            "clearing packets from this chain is unsupported: TendermintLightClient verifies a \
+             single ICS-23 proof keyed by the IBCHost storage slot of {}, the chain proves it \
+             with {} proof op(s) ({}) keyed by its path",
+            path,
+            ops.len(),
+            ops.iter()
+                .map(|op| op.field_type.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Proof (the ICS-23 commitment proof of the `ibc` store) of the entry at `path` at `height`,
+/// verifiable against the app hash of the header at `height + 1`. Fails unless it is in the
+/// form TendermintLightClient verifies.
+async fn query_proof(
+    rpc: &HttpClient,
+    path: &CommitmentPath,
+    height: u64,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let ops = query_proof_ops(rpc, path, Some(height)).await?;
+    if let Some(reason) = unsupported_proof(path, &ops) {
+        return Err(reason.into());
+    }
+
+    Ok(ops[0].data.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_packet_attributes() -> HashMap<String, String> {
+        [
+            ("packet_src_port", "transfer"),
+            ("packet_src_channel", "channel-1"),
+            ("packet_dst_port", "transfer"),
+            ("packet_dst_channel", "channel-0"),
+            ("packet_data", r#"{"amount":"1"}"#),
+            ("packet_timeout_height", "1-1200"),
+            ("packet_timeout_timestamp", "1650000000000000000"),
+            ("packet_sequence", "7"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+    }
+
+    fn packet(sequence: u64, channel: &str) -> indexer::Packet {
+        indexer::Packet {
+            sequence,
+            source_port: "transfer".to_string(),
+            source_channel: channel.to_string(),
+            destination_port: "transfer".to_string(),
+            destination_channel: "channel-1".to_string(),
+            data: Default::default(),
+            timeout_revision_number: 0,
+            timeout_revision_height: 0,
+            timeout_timestamp: 0,
+        }
+    }
+
+    fn send(sequence: u64, channel: &str) -> IbcEvent {
+        IbcEvent::SendPacket {
+            packet: packet(sequence, channel),
+        }
+    }
+
+    fn ack(sequence: u64, channel: &str) -> IbcEvent {
+        IbcEvent::AcknowledgePacket {
+            packet: packet(sequence, channel),
+            acknowledgement: Default::default(),
+        }
+    }
+
+    #[test]
+    fn packet_from_send_packet_event() {
+        let packet = packet_from_event(&send_packet_attributes(), 7).unwrap();
+
+        assert_eq!(packet.sequence, 7);
+        assert_eq!(packet.source_port, "transfer");
+        assert_eq!(packet.source_channel, "channel-1");
+        assert_eq!(packet.destination_port, "transfer");
+        assert_eq!(packet.destination_channel, "channel-0");
+        assert_eq!(packet.data, br#"{"amount":"1"}"#.to_vec());
+        assert_eq!(packet.timeout_height.revision_number, 1);
+        assert_eq!(packet.timeout_height.revision_height, 1200);
+        assert_eq!(packet.timeout_timestamp, 1_650_000_000_000_000_000);
+    }
+
+    #[test]
+    fn prefers_hex_attributes() {
+        let mut attributes = send_packet_attributes();
+        attributes.insert("packet_data_hex".to_string(), "00ff10".to_string());
+
+        assert_eq!(
+            attribute_bytes(&attributes, "packet_data").unwrap(),
+            vec![0x00, 0xff, 0x10]
+        );
+        assert_eq!(
+            packet_from_event(&attributes, 7).unwrap().data,
+            vec![0x00, 0xff, 0x10]
+        );
+
+        attributes.insert("packet_data_hex".to_string(), "not hex".to_string());
+        assert!(attribute_bytes(&attributes, "packet_data").is_err());
+    }
+
+    #[test]
+    fn rejects_missing_attributes() {
+        let mut attributes = send_packet_attributes();
+        attributes.remove("packet_dst_channel");
+        assert!(packet_from_event(&attributes, 7).is_err());
+
+        assert!(attribute_bytes(&attributes, "packet_ack").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_timeout_height() {
+        for timeout_height in &["1200", "1-", "-1200", "a-1200", "1-1200-3"] {
+            let mut attributes = send_packet_attributes();
+            attributes.insert(
+                "packet_timeout_height".to_string(),
+                timeout_height.to_string(),
+            );
+
+            assert!(
+                packet_from_event(&attributes, 7).is_err(),
+                "accepted {}",
+                timeout_height
+            );
+        }
+    }
+
+    #[test]
+    fn indexed_sends_of_the_channel() {
+        let events = vec![
+            send(1, "channel-0"),
+            send(2, "channel-0"),
+            send(1, "channel-5"),
+            ack(1, "channel-0"),
+            send(3, "channel-0"),
+            ack(3, "channel-5"),
+        ];
+
+        let (sent, unacknowledged) = indexed_sends(events, "transfer", "channel-0");
+        assert_eq!(sent, 3);
+        assert_eq!(unacknowledged.into_iter().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn indexed_sends_of_a_resent_sequence() {
+        let events = vec![
+            send(1, "channel-0"),
+            ack(1, "channel-0"),
+            send(1, "channel-0"),
+        ];
+
+        let (sent, unacknowledged) = indexed_sends(events, "transfer", "channel-0");
+        assert_eq!(sent, 1);
+        assert_eq!(unacknowledged.into_iter().collect::<Vec<_>>(), vec![1]);
+    }
+}
@@ -0,0 +1,593 @@
+use clap::ArgMatches;
+use serde_json::json;
+use std::{error::Error, net::SocketAddr, path::Path, sync::Arc};
+use tracing::{info, warn};
+use web3::{types::H256, Transport};
+
+use client::{
+    bindings::ibc_handler,
+    celo, clear, commitment,
+    contracts::IbcContracts,
+    encoder::{self, ClientParams},
+    evm::{Artifacts, EmbeddedChain},
+    grpc::CosmosQuery,
+    header,
+    indexer::{IbcEvent, Indexer},
+    inspect,
+    mock_rpc::{self, Fixtures},
+    proof::{self, CommitmentPath},
+    query,
+    relay::Relayer,
+    scenario::{self, Devnet, Scenario, StepReport},
+    status::Status,
+    store::Store,
+    synth::{ChainConfig, SyntheticChain},
+    txmgr::TxManager,
+};
+
+/// Handles the `query` subcommand, printing the requested state as JSON
+pub async fn query<T: Transport>(
+    transport: &T,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let contracts = IbcContracts::new(transport);
+
+    let output = match matches.subcommand() {
+        ("client-state", Some(matches)) => {
+            let client_id = matches.value_of("client-id").unwrap();
+            let client_state = contracts
+                .client_state(client_id)
+                .await?
+                .ok_or_else(|| format!("client {} not found", client_id))?;
+
+            query::client_state_json(&client_state)
+        }
+        ("consensus-state", Some(matches)) => {
+            let client_id = matches.value_of("client-id").unwrap();
+            let height = matches.value_of("height").unwrap().parse::<u64>()?;
+            let consensus_state = contracts
+                .consensus_state(client_id, height)
+                .await?
+                .ok_or_else(|| {
+                    format!(
+                        "consensus state of client {} at height {} not found",
+                        client_id, height
+                    )
+                })?;
+
+            query::consensus_state_json(&consensus_state)
+        }
+        _ => unreachable!("query subcommand is required"),
+    };
+
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+/// Handles the `celo-proof` subcommand, printing the proofs as JSON
+pub async fn celo_proof<T: Transport>(
+    transport: &T,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let paths = matches
+        .values_of("paths")
+        .unwrap()
+        .map(str::parse::<CommitmentPath>)
+        .collect::<Result<Vec<_>, _>>()?;
+    let block = matches
+        .value_of("block")
+        .map(str::parse::<u64>)
+        .transpose()?;
+
+    let contracts = IbcContracts::new(transport);
+    let state_proof = proof::prove(transport, contracts.host.address(), &paths, block).await?;
+    println!("{}", serde_json::to_string_pretty(&state_proof)?);
+
+    Ok(())
+}
+
+/// Handles the `cosmos-query` subcommand, printing the requested state as JSON
+pub async fn cosmos_query(url: &str, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let cosmos = CosmosQuery::connect(url).await?;
+    let port_channel = |matches: &ArgMatches<'_>| {
+        (
+            matches.value_of("port-id").unwrap().to_string(),
+            matches.value_of("channel-id").unwrap().to_string(),
+        )
+    };
+    let sequences = |matches: &ArgMatches<'_>| {
+        matches
+            .values_of("sequences")
+            .unwrap()
+            .map(|sequence| sequence.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let output = match matches.subcommand() {
+        ("client-states", Some(_)) => json!(cosmos.client_states().await?),
+        ("client-state", Some(matches)) => {
+            let client_id = matches.value_of("client-id").unwrap();
+            json!(cosmos
+                .client_state(client_id)
+                .await?
+                .ok_or_else(|| format!("client {} not found", client_id))?)
+        }
+        ("connections", Some(_)) => json!(cosmos.connections().await?),
+        ("connection", Some(matches)) => {
+            let connection_id = matches.value_of("connection-id").unwrap();
+            json!(cosmos
+                .connection(connection_id)
+                .await?
+                .ok_or_else(|| format!("connection {} not found", connection_id))?)
+        }
+        ("channels", Some(_)) => json!(cosmos.channels().await?),
+        ("channel", Some(matches)) => {
+            let (port_id, channel_id) = port_channel(matches);
+            json!(cosmos
+                .channel(&port_id, &channel_id)
+                .await?
+                .ok_or_else(|| format!("channel {}/{} not found", port_id, channel_id))?)
+        }
+        ("packet-commitments", Some(matches)) => {
+            let (port_id, channel_id) = port_channel(matches);
+            json!(cosmos.packet_commitments(&port_id, &channel_id).await?)
+        }
+        ("unreceived-packets", Some(matches)) => {
+            let (port_id, channel_id) = port_channel(matches);
+            json!(
+                cosmos
+                    .unreceived_packets(&port_id, &channel_id, &sequences(matches)?)
+                    .await?
+            )
+        }
+        ("unreceived-acks", Some(matches)) => {
+            let (port_id, channel_id) = port_channel(matches);
+            json!(
+                cosmos
+                    .unreceived_acks(&port_id, &channel_id, &sequences(matches)?)
+                    .await?
+            )
+        }
+        _ => unreachable!("cosmos-query subcommand is required"),
+    };
+
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+fn rfc3339(seconds: i64) -> serde_json::Value {
+    match tendermint::Time::from_unix_timestamp(seconds, 0) {
+        Ok(time) => json!(time.as_rfc3339()),
+        Err(_) => json!(seconds),
+    }
+}
+
+/// Handles the `status` subcommand
+pub async fn status<T: Transport>(
+    transport: &T,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let contracts = IbcContracts::new(transport);
+    let client_id = matches.value_of("client-id").unwrap();
+
+    let (client_state, status) = contracts.client_status(client_id).await?;
+
+    let note = match (status.status, status.recoverable) {
+        (Status::Active, _) => "headers can be submitted",
+        (Status::Expired, true) => {
+            "trusting period elapsed, allow_update_after_expiry is set so the client may be recovered"
+        }
+        (Status::Expired, false) => "trusting period elapsed, the client can't be recovered",
+        (Status::Frozen, true) => {
+            "misbehaviour detected, allow_update_after_misbehaviour is set so the client may be recovered"
+        }
+        (Status::Frozen, false) => "misbehaviour detected, the client can't be recovered",
+    };
+
+    let output = json!({
+        "client_id": client_id,
+        "status": status.status,
+        "note": note,
+        "latest_height": status.latest_height,
+        "frozen_height": status.frozen_height,
+        "latest_timestamp": rfc3339(status.latest_timestamp),
+        "celo_block_time": rfc3339(status.now),
+        "expires_at": rfc3339(status.expires_at),
+        "seconds_to_expiry": status.seconds_to_expiry,
+        "clock_drift_exceeded": status.clock_drift_exceeded,
+        "client_state": query::client_state_json(&client_state),
+    });
+
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+/// Handles the `inspect-tx` subcommand
+pub async fn inspect_tx<T: Transport>(
+    transport: &T,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let hash: H256 = matches
+        .value_of("tx-hash")
+        .unwrap()
+        .parse()
+        .map_err(|e| format!("invalid tx hash: {}", e))?;
+    let tx = celo::get_transaction(transport, hash)
+        .await?
+        .ok_or_else(|| format!("transaction {:?} not found", hash))?;
+
+    let mut report = inspect::inspect_input(ibc_handler::abi(), &tx.input.0)?;
+    report["tx_hash"] = json!(hash);
+    report["block_number"] = json!(tx.block_number);
+    report["gas"] = json!(tx.gas);
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    Ok(())
+}
+
+/// Handles the `evm-gas` subcommand
+pub fn evm_gas(params: &ClientParams, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let fixtures = Path::new(matches.value_of("fixtures").unwrap());
+    let heights = matches
+        .values_of("heights")
+        .unwrap()
+        .map(|height| height.parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let first = header::read_light_block(fixtures, heights[0])?;
+
+    let mut chain = EmbeddedChain::deploy(Artifacts::new(matches.value_of("artifacts").unwrap())?)?;
+    let mut report = Vec::new();
+
+    let (client_id, execution) = chain.create_client(&first, params)?;
+    report.push(json!({
+        "method": "createClient",
+        "height": heights[0],
+        "execution": execution,
+    }));
+    let client_id = client_id.ok_or("createClient emitted no client identifier")?;
+
+    for trusted in heights.windows(2) {
+        let (trusted_height, height) = (trusted[0], trusted[1]);
+        let trusted_validators = if height == trusted_height + 1 {
+            None
+        } else {
+            Some(header::read_validator_set(fixtures, trusted_height + 1)?)
+        };
+        let tm_header = encoder::update_header(
+            &header::read_light_block(fixtures, height)?,
+            trusted_height,
+            trusted_validators,
+        );
+
+        let execution = chain.update_client(&client_id, &tm_header)?;
+        report.push(json!({
+            "method": "updateClient",
+            "height": height,
+            "trusted_height": trusted_height,
+            "execution": execution,
+        }));
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "client_id": client_id,
+            "transactions": report,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Handles the `register-client` subcommand
+pub async fn register_client<T: Transport>(relayer: &Relayer<'_, T>) -> Result<(), Box<dyn Error>> {
+    let sent = relayer.register_client().await?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "client_type": encoder::CLIENT_TYPE,
+            "light_client": relayer.contracts().client_impl().await?,
+            "sent": sent,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Handles the `create-client` subcommand
+pub async fn create_client<T: Transport>(
+    relayer: &Relayer<'_, T>,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let height = match matches.value_of("height") {
+        Some(height) => height.parse::<u64>()?,
+        None => relayer.source().latest_height().await?,
+    };
+    let tm_header = relayer.source().light_block(height as i64).await?;
+    let client_id = relayer.create_client(&tm_header).await?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "client_id": client_id,
+            "height": height,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Handles the `update-client` subcommand
+pub async fn update_client<T: Transport>(
+    relayer: &Relayer<'_, T>,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let client_id = matches.value_of("client-id").unwrap();
+    let height = matches.value_of("height").unwrap().parse::<u64>()?;
+    let trusted_height = matches
+        .value_of("trusted-height")
+        .map(|height| height.parse::<u64>())
+        .transpose()?;
+
+    let sent = relayer
+        .update_client(client_id, height, trusted_height)
+        .await?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "client_id": client_id,
+            "height": height,
+            "sent": sent,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Handles the `clear-packets` subcommand, printing what is outstanding on the channel and what
+/// was relayed as JSON
+pub async fn clear_packets<T: Transport>(
+    relayer: &Relayer<'_, T>,
+    cosmos_grpc_url: &str,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let port_id = matches.value_of("port-id").unwrap();
+    let channel_id = matches.value_of("channel-id").unwrap();
+
+    let cosmos = CosmosQuery::connect(cosmos_grpc_url).await?;
+    let plan = clear::plan(
+        relayer.indexer(),
+        relayer.source().client(),
+        &cosmos,
+        port_id,
+        channel_id,
+    )
+    .await?;
+    let report = match (matches.is_present("dry-run"), &plan.unsupported) {
+        (true, _) => None,
+        (false, Some(reason)) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({ "plan": plan }))?
+            );
+            return Err(reason.clone().into());
+        }
+        (false, None) => Some(clear::clear(relayer, &plan).await?),
+    };
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "plan": plan,
+            "report": report,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Handles the `index` subcommand, printing the indexed clients and events as JSON
+pub async fn index<T: Transport>(
+    indexer: &Indexer<'_, T>,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let since = matches.value_of("since").unwrap().parse::<u64>()?;
+
+    indexer.sync().await?;
+    let mut events = Vec::new();
+    for indexed in indexer.events(since)? {
+        let mut event = json!(indexed);
+        if let (IbcEvent::SendPacket { packet }, true) =
+            (&indexed.event, matches.is_present("check-packets"))
+        {
+            let check = commitment::check_packet(indexer.contracts(), &packet.into()).await?;
+            event["commitment"] = json!(check.to_string());
+        }
+        events.push(event);
+    }
+    let clients = indexer
+        .client_ids()?
+        .into_iter()
+        .map(|client_id| {
+            Ok(json!({
+                "chain_id": indexer.client_chain(&client_id)?,
+                "client_id": client_id,
+            }))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "next_block": indexer.cursor()?,
+            "clients": clients,
+            "events": events,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Handles the `history` subcommand, printing what the state db recorded as JSON
+pub fn history(state_db: Option<&str>, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let store = Store::open(state_db.ok_or("history needs --state-db")?)?;
+    let client_id = matches.value_of("client-id");
+
+    let clients = store
+        .clients()?
+        .into_iter()
+        .filter(|client| client_id.is_none_or(|id| id == client.client_id))
+        .map(|client| {
+            let trusted = store.trusted(&client.client_id)?;
+            Ok(json!({
+                "client": client,
+                "trusted": trusted,
+            }))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({
+            "clients": clients,
+            "submissions": store.submissions(client_id)?,
+        }))?
+    );
+
+    Ok(())
+}
+
+/// Handles the `mock-rpc` subcommand
+pub async fn mock_rpc(matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let fixtures = Fixtures::open(matches.value_of("fixtures").unwrap())?;
+    let addr = matches.value_of("listen").unwrap().parse::<SocketAddr>()?;
+
+    info!(heights = ?fixtures.heights(), "serving recorded headers");
+    mock_rpc::serve(Arc::new(fixtures), addr).await?;
+
+    Ok(())
+}
+
+/// Handles the `synth` subcommand
+pub fn synth(matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let out = Path::new(matches.value_of("out").unwrap());
+    let blocks = matches.value_of("blocks").unwrap().parse::<u64>()?;
+    let config = ChainConfig {
+        chain_id: matches.value_of("chain-id").unwrap().to_string(),
+        validators: matches.value_of("validators").unwrap().parse()?,
+        power: matches.value_of("power").unwrap().parse()?,
+        absent: matches.value_of("absent").unwrap().parse()?,
+        nil: matches.value_of("nil").unwrap().parse()?,
+        churn: matches.value_of("churn").unwrap().parse()?,
+        seed: matches.value_of("seed").unwrap().parse()?,
+        fork: matches.value_of("fork").unwrap().parse()?,
+        start_height: matches.value_of("start-height").unwrap().parse()?,
+        ..Default::default()
+    };
+
+    std::fs::create_dir_all(out)?;
+    let mut chain = SyntheticChain::new(config)?;
+    for _ in 0..blocks {
+        let block = chain.next_block()?;
+        header::write_light_block(out, &block.tm_header)?;
+        info!(
+            height = %block.signed_header.header.height,
+            validators = block.validators.validators().len(),
+            "generated block"
+        );
+    }
+
+    Ok(())
+}
+
+fn load_scenarios(matches: &ArgMatches<'_>) -> Result<Vec<Scenario>, Box<dyn Error>> {
+    matches
+        .values_of("files")
+        .unwrap()
+        .map(|path| Scenario::load(Path::new(path)))
+        .collect()
+}
+
+/// Prints the step reports of the scenarios as JSON, fails if any step went unexpectedly
+fn report_scenarios(results: Vec<(String, Vec<StepReport>)>) -> Result<(), Box<dyn Error>> {
+    let total: usize = results.iter().map(|(_, steps)| steps.len()).sum();
+    let failed: usize = results
+        .iter()
+        .map(|(_, steps)| steps.iter().filter(|step| !step.passed).count())
+        .sum();
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(
+            &results
+                .iter()
+                .map(|(name, steps)| json!({
+                    "scenario": name,
+                    "passed": steps.iter().all(|step| step.passed),
+                    "steps": steps,
+                }))
+                .collect::<Vec<_>>()
+        )?
+    );
+
+    if failed > 0 {
+        return Err(format!("{} of {} steps did not go as expected", failed, total).into());
+    }
+
+    Ok(())
+}
+
+/// Handles the `scenario` subcommand with `--evm`, every scenario on freshly deployed contracts
+pub async fn scenario_evm(
+    params: &ClientParams,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let scenarios = load_scenarios(matches)?;
+
+    let mut results = Vec::new();
+    for scenario in scenarios {
+        info!(name = %scenario.name, "running scenario");
+        let mut chain =
+            EmbeddedChain::deploy(Artifacts::new(matches.value_of("artifacts").unwrap())?)?;
+        let steps = scenario::run(&scenario, params, &mut chain).await?;
+        results.push((scenario.name, steps));
+    }
+
+    report_scenarios(results)
+}
+
+/// Handles the `scenario` subcommand against the Celo node, every scenario creating its own
+/// client
+pub async fn scenario_devnet<T: Transport>(
+    transport: &T,
+    txmgr: &TxManager<'_, T>,
+    params: &ClientParams,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let scenarios = load_scenarios(matches)?;
+    if scenarios.iter().any(|scenario| {
+        scenario
+            .steps
+            .iter()
+            .any(|step| matches!(step.action, scenario::Action::Sleep { .. }))
+    }) {
+        warn!("sleep steps wait in real time on a live chain");
+    }
+
+    let mut devnet = Devnet::new(transport, txmgr);
+    let mut results = Vec::new();
+    for scenario in scenarios {
+        info!(name = %scenario.name, "running scenario");
+        let steps = scenario::run(&scenario, params, &mut devnet).await?;
+        results.push((scenario.name, steps));
+    }
+
+    report_scenarios(results)
+}
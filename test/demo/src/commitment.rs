@@ -0,0 +1,161 @@
+//! Packet and acknowledgement commitments computed like IBCHost does, so that a packet can be
+//! checked against the commitment stored on Celo before it is relayed.
+//!
+//! `IBCHost.makePacketCommitment` hashes
+//! `abi.encodePacked(timeout_timestamp, timeout_height.revision_number,
+//! timeout_height.revision_height, sha256(data))` with sha256, the `uint64`s packed as 8
+//! big-endian bytes; `makePacketAcknowledgementCommitment` is `sha256(acknowledgement)`.
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt};
+use web3::{types::H256, Transport};
+
+use crate::{contracts::IbcContracts, proto::ibc::channel::Packet};
+
+/// `IBCHost.makePacketCommitment`
+pub fn packet_commitment(packet: &Packet) -> H256 {
+    let timeout_height = packet.timeout_height.clone().unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(packet.timeout_timestamp.to_be_bytes());
+    hasher.update(timeout_height.revision_number.to_be_bytes());
+    hasher.update(timeout_height.revision_height.to_be_bytes());
+    hasher.update(Sha256::digest(&packet.data));
+
+    H256::from_slice(&hasher.finalize())
+}
+
+/// `IBCHost.makePacketAcknowledgementCommitment`
+pub fn packet_acknowledgement_commitment(acknowledgement: &[u8]) -> H256 {
+    H256::from_slice(&Sha256::digest(acknowledgement))
+}
+
+/// State of a packet sent from Celo, according to the commitment IBCHost stores for it
+#[derive(Clone, Debug, PartialEq)]
+pub enum PacketCheck {
+    /// The stored commitment matches the packet, it still has to be relayed (or acknowledged)
+    Pending,
+    /// No commitment is stored: the packet was acknowledged or timed out already, or never sent
+    Cleared,
+    /// The stored commitment is not the one of the packet, which differs from the packet sent
+    Mismatch { stored: H256, computed: H256 },
+}
+
+impl fmt::Display for PacketCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketCheck::Pending => write!(f, "pending"),
+            PacketCheck::Cleared => write!(f, "cleared"),
+            PacketCheck::Mismatch { stored, computed } => {
+                write!(f, "mismatch (stored {:x}, computed {:x})", stored, computed)
+            }
+        }
+    }
+}
+
+/// Checks the packet (e.g. from a `SendPacket` event) against the commitment stored on Celo
+pub async fn check_packet<T: Transport>(
+    contracts: &IbcContracts<'_, T>,
+    packet: &Packet,
+) -> Result<PacketCheck, Box<dyn Error>> {
+    let stored = contracts
+        .packet_commitment(&packet.source_port, &packet.source_channel, packet.sequence)
+        .await?;
+    let computed = packet_commitment(packet);
+
+    Ok(match stored {
+        None => PacketCheck::Cleared,
+        Some(stored) if stored == computed => PacketCheck::Pending,
+        Some(stored) => PacketCheck::Mismatch { stored, computed },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bindings::ibc_host,
+        evm::{Artifacts, EmbeddedChain},
+        proto::ibc::channel::Height,
+    };
+    use std::path::Path;
+
+    fn transfer_packet() -> Packet {
+        Packet {
+            sequence: 1,
+            source_port: "transfer".to_string(),
+            source_channel: "channel-0".to_string(),
+            destination_port: "transfer".to_string(),
+            destination_channel: "channel-1".to_string(),
+            data: br#"{"amount":"100","denom":"uatom","receiver":"0x35","sender":"cosmos1"}"#
+                .to_vec(),
+            timeout_height: Some(Height {
+                revision_number: 1,
+                revision_height: 4200,
+            }),
+            timeout_timestamp: 1_700_000_000_000_000_000,
+        }
+    }
+
+    fn h256(hex: &str) -> H256 {
+        H256::from_slice(&hex::decode(hex).unwrap())
+    }
+
+    #[test]
+    fn packet_commitment_vectors() {
+        assert_eq!(
+            packet_commitment(&transfer_packet()),
+            h256("956da0bab332d5fc70925995c0577da9c9666e3a4eff11d938063c9035acde39")
+        );
+        // no timeout height is packed as zeros, like the default struct of the contract
+        assert_eq!(
+            packet_commitment(&Packet::default()),
+            h256("e6414172e184a44066320223590766e7ff9d758405e51f5cdddb546267a848f5")
+        );
+    }
+
+    #[test]
+    fn acknowledgement_commitment_vector() {
+        assert_eq!(
+            packet_acknowledgement_commitment(br#"{"result":"AQ=="}"#),
+            h256("08f7557ed51826fe18d84512bf24ec75001edbaf2123a477df72a0a9f3640a7c")
+        );
+    }
+
+    /// Compares with the contract in the in-process EVM, run with `cargo test -- --ignored`
+    /// after `truffle compile`
+    #[test]
+    #[ignore = "needs truffle compile"]
+    fn matches_ibc_host() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../build/contracts");
+        let mut chain = EmbeddedChain::deploy(Artifacts::new(dir).unwrap()).unwrap();
+
+        for packet in [transfer_packet(), Packet::default()] {
+            let timeout_height = packet.timeout_height.clone().unwrap_or_default();
+            let contract_packet = ibc_host::Packet {
+                sequence: packet.sequence,
+                source_port: packet.source_port.clone(),
+                source_channel: packet.source_channel.clone(),
+                destination_port: packet.destination_port.clone(),
+                destination_channel: packet.destination_channel.clone(),
+                data: packet.data.clone(),
+                timeout_height: ibc_host::Height {
+                    revision_number: timeout_height.revision_number,
+                    revision_height: timeout_height.revision_height,
+                },
+                timeout_timestamp: packet.timeout_timestamp,
+            };
+            assert_eq!(
+                chain.make_packet_commitment(contract_packet).unwrap(),
+                packet_commitment(&packet)
+            );
+        }
+
+        let acknowledgement = br#"{"result":"AQ=="}"#.to_vec();
+        assert_eq!(
+            chain
+                .make_packet_acknowledgement_commitment(acknowledgement.clone())
+                .unwrap(),
+            packet_acknowledgement_commitment(&acknowledgement)
+        );
+    }
+}
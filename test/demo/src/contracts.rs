@@ -0,0 +1,267 @@
+use std::error::Error;
+use web3::{
+    types::{Address, H256},
+    Transport, Web3,
+};
+
+use crate::{
+    bindings::{
+        ibc_handler::{self, IBCHandler, MsgPacketAcknowledgement, MsgPacketRecv},
+        ibc_host::{self, IBCHost},
+    },
+    consts::{IBC_HANDLER_ADDRESS, IBC_HOST_ADDRESS, TENDERMINT_LIGHT_CLIENT_ADDRESS},
+    encoder,
+    proto::tendermint::light::{ClientState, ConsensusState, TmHeader},
+    query,
+    status::{self, ClientStatus},
+    types,
+};
+
+/// IBC contracts deployed on Celo: IBCHandler takes the messages, IBCHost holds the state
+pub struct IbcContracts<'a, T: Transport> {
+    transport: &'a T,
+    pub handler: IBCHandler<&'a T>,
+    pub host: IBCHost<&'a T>,
+}
+
+impl<'a, T: Transport> IbcContracts<'a, T> {
+    /// Contracts at the addresses of the deployment (see `consts`)
+    pub fn new(transport: &'a T) -> Self {
+        let eth = Web3::new(transport).eth();
+
+        IbcContracts {
+            transport,
+            handler: IBCHandler::new(eth.clone(), types::to_addr(IBC_HANDLER_ADDRESS.to_string())),
+            host: IBCHost::new(eth, types::to_addr(IBC_HOST_ADDRESS.to_string())),
+        }
+    }
+
+    pub fn handler_address(&self) -> Address {
+        self.handler.address()
+    }
+
+    /// Latest client state, `None` if the client does not exist
+    pub async fn client_state(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<ClientState>, Box<dyn Error>> {
+        query::get_client_state(&self.host, client_id).await
+    }
+
+    /// Consensus state stored for the height, `None` if there is none
+    pub async fn consensus_state(
+        &self,
+        client_id: &str,
+        height: u64,
+    ) -> Result<Option<ConsensusState>, Box<dyn Error>> {
+        query::get_consensus_state(&self.host, client_id, height).await
+    }
+
+    /// Client state and status evaluated against the latest Celo block time
+    pub async fn client_status(
+        &self,
+        client_id: &str,
+    ) -> Result<(ClientState, ClientStatus), Box<dyn Error>> {
+        status::get_client_status(self.transport, &self.host, client_id).await
+    }
+
+    /// Celo block time (unix seconds) the consensus state at `height` was stored at, `None` if
+    /// there is none
+    pub async fn processed_time(
+        &self,
+        client_id: &str,
+        height: u64,
+    ) -> Result<Option<u64>, web3::contract::Error> {
+        let (time, found) = self
+            .host
+            .get_processed_time(client_id.to_string(), height)
+            .await?;
+
+        Ok(if found { Some(time.low_u64()) } else { None })
+    }
+
+    /// Celo block number the consensus state at `height` was stored at, `None` if there is none
+    pub async fn processed_height(
+        &self,
+        client_id: &str,
+        height: u64,
+    ) -> Result<Option<u64>, web3::contract::Error> {
+        let (processed_height, found) = self
+            .host
+            .get_processed_height(client_id.to_string(), height)
+            .await?;
+
+        Ok(if found {
+            Some(processed_height.low_u64())
+        } else {
+            None
+        })
+    }
+
+    /// Expected Celo block time (nanoseconds) the block delay of connections is derived from,
+    /// 0 if not set
+    pub async fn expected_time_per_block(&self) -> Result<u64, web3::contract::Error> {
+        self.host.get_expected_time_per_block().await
+    }
+
+    /// Channel end, `None` if the channel does not exist
+    pub async fn channel(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+    ) -> Result<Option<ibc_host::Channel>, web3::contract::Error> {
+        let (channel, found) = self
+            .host
+            .get_channel(port_id.to_string(), channel_id.to_string())
+            .await?;
+
+        Ok(if found { Some(channel) } else { None })
+    }
+
+    /// Connection end, `None` if the connection does not exist
+    pub async fn connection(
+        &self,
+        connection_id: &str,
+    ) -> Result<Option<ibc_host::ConnectionEnd>, web3::contract::Error> {
+        let (connection, found) = self.host.get_connection(connection_id.to_string()).await?;
+
+        Ok(if found { Some(connection) } else { None })
+    }
+
+    /// Sequence of the next packet sent on the channel
+    pub async fn next_sequence_send(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+    ) -> Result<u64, web3::contract::Error> {
+        self.host
+            .get_next_sequence_send(port_id.to_string(), channel_id.to_string())
+            .await
+    }
+
+    /// Sequence of the next packet received on the channel (ordered channels only)
+    pub async fn next_sequence_recv(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+    ) -> Result<u64, web3::contract::Error> {
+        self.host
+            .get_next_sequence_recv(port_id.to_string(), channel_id.to_string())
+            .await
+    }
+
+    /// Sequence of the next packet acknowledged on the channel (ordered channels only)
+    pub async fn next_sequence_ack(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+    ) -> Result<u64, web3::contract::Error> {
+        self.host
+            .get_next_sequence_ack(port_id.to_string(), channel_id.to_string())
+            .await
+    }
+
+    /// Whether the packet was received on the (unordered) channel
+    pub async fn has_packet_receipt(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+        sequence: u64,
+    ) -> Result<bool, web3::contract::Error> {
+        self.host
+            .has_packet_receipt(port_id.to_string(), channel_id.to_string(), sequence)
+            .await
+    }
+
+    /// Commitment of the packet sent on the channel, `None` once it is acknowledged or timed
+    /// out (or if it was never sent)
+    pub async fn packet_commitment(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+        sequence: u64,
+    ) -> Result<Option<H256>, web3::contract::Error> {
+        let (commitment, found) = self
+            .host
+            .get_packet_commitment(port_id.to_string(), channel_id.to_string(), sequence)
+            .await?;
+
+        Ok(if found { Some(commitment) } else { None })
+    }
+
+    /// Commitment of the acknowledgement written for the packet received on the channel,
+    /// `None` if there is none
+    pub async fn packet_acknowledgement_commitment(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+        sequence: u64,
+    ) -> Result<Option<H256>, web3::contract::Error> {
+        let (commitment, found) = self
+            .host
+            .get_packet_acknowledgement_commitment(
+                port_id.to_string(),
+                channel_id.to_string(),
+                sequence,
+            )
+            .await?;
+
+        Ok(if found { Some(commitment) } else { None })
+    }
+
+    /// Address of the light client contract registered for the tendermint client type,
+    /// `None` if the type is not registered
+    pub async fn client_impl(&self) -> Result<Option<Address>, web3::contract::Error> {
+        let (address, found) = self
+            .host
+            .get_client_impl(encoder::CLIENT_TYPE.to_string())
+            .await?;
+
+        Ok(if found { Some(address) } else { None })
+    }
+
+    /// Address of the light client contract of the deployment
+    pub fn light_client_address(&self) -> Address {
+        types::to_addr(TENDERMINT_LIGHT_CLIENT_ADDRESS.to_string())
+    }
+
+    /// `registerClient` calldata registering the tendermint light client
+    pub fn register_client_call(&self) -> Vec<u8> {
+        ibc_handler::register_client_call(
+            encoder::CLIENT_TYPE.to_string(),
+            self.light_client_address(),
+        )
+    }
+
+    /// `createClient` calldata
+    pub fn create_client_call(
+        &self,
+        client_state: &ClientState,
+        consensus_state: &ConsensusState,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let msg = encoder::create_client_msg(client_state, consensus_state)?;
+
+        Ok(ibc_handler::create_client_call(msg))
+    }
+
+    /// `updateClient` calldata
+    pub fn update_client_call(
+        &self,
+        client_id: &str,
+        tm_header: &TmHeader,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let msg = encoder::update_client_msg(client_id, tm_header)?;
+
+        Ok(ibc_handler::update_client_call(msg))
+    }
+
+    /// `recvPacket` calldata
+    pub fn recv_packet_call(&self, msg: MsgPacketRecv) -> Vec<u8> {
+        ibc_handler::recv_packet_call(msg)
+    }
+
+    /// `acknowledgePacket` calldata
+    pub fn acknowledge_packet_call(&self, msg: MsgPacketAcknowledgement) -> Vec<u8> {
+        ibc_handler::acknowledge_packet_call(msg)
+    }
+}
@@ -0,0 +1,243 @@
+//! Connection delay period of the messages carrying a proof (packets and acknowledgements).
+//!
+//! `TendermintLightClient.validateDelayPeriod` accepts a proof at `height` once
+//!
+//! ```solidity
+//! uint64 currentTime = uint64(block.timestamp * 1000 * 1000 * 1000);
+//! uint64 validTime = mustGetProcessedTime(host, clientId, height) + delayPeriodTime;
+//! ...
+//! uint64 currentHeight = uint64(block.number);
+//! uint64 validHeight = mustGetProcessedHeight(host, clientId, height) + delayPeriodBlocks;
+//! ```
+//!
+//! pass (`currentTime >= validTime`, `currentHeight >= validHeight`), `mustGetProcessedTime`
+//! returning `uint64(processedTime) * 1000 * 1000 * 1000`. Both times are therefore nanoseconds,
+//! like the `delay_period` of the connection: `processedTime`/`processedHeight` are the Celo
+//! `block.timestamp` (seconds) and `block.number` the consensus state at `height` was stored
+//! at (`IBCClient`), and `delayPeriodBlocks` the delay period in blocks of
+//! `expectedTimePerBlock` (`IBCConnection.calcBlockDelay`). Earlier the message reverts, so it
+//! is held back until the latest Celo block passes both checks.
+use serde::Serialize;
+use std::error::Error;
+use web3::{
+    types::{BlockId, BlockNumber},
+    Transport, Web3,
+};
+
+use crate::contracts::IbcContracts;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Delay period of a connection, as the contracts apply it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DelayPeriod {
+    /// Nanoseconds (`delay_period` of the connection)
+    pub time: u64,
+    pub blocks: u64,
+}
+
+impl DelayPeriod {
+    /// Delay of `time` nanoseconds, with the block delay derived from the expected block time
+    /// (nanoseconds, no block delay if 0)
+    pub fn new(time: u64, expected_time_per_block: u64) -> Self {
+        let blocks = match expected_time_per_block {
+            0 => 0,
+            per_block => time.div_ceil(per_block),
+        };
+
+        DelayPeriod { time, blocks }
+    }
+
+    /// Delay period of the connection of the channel on Celo
+    pub async fn of_channel<T: Transport>(
+        contracts: &IbcContracts<'_, T>,
+        port_id: &str,
+        channel_id: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let channel = contracts
+            .channel(port_id, channel_id)
+            .await?
+            .ok_or_else(|| format!("channel {}/{} not found on Celo", port_id, channel_id))?;
+        let connection_id = channel
+            .connection_hops
+            .first()
+            .ok_or_else(|| format!("channel {}/{} has no connection", port_id, channel_id))?;
+        let connection = contracts
+            .connection(connection_id)
+            .await?
+            .ok_or_else(|| format!("connection {} not found on Celo", connection_id))?;
+
+        Ok(DelayPeriod::new(
+            connection.delay_period,
+            contracts.expected_time_per_block().await?,
+        ))
+    }
+}
+
+/// How far a proof height is from passing the delay period checks, at a Celo block
+#[derive(Clone, Debug, Serialize)]
+pub struct ProofDelay {
+    pub proof_height: u64,
+    /// Celo block time (unix seconds) the consensus state was stored at
+    pub processed_time: u64,
+    /// Celo block number the consensus state was stored at
+    pub processed_height: u64,
+    /// Celo block time (unix seconds) the delay was evaluated at
+    pub now: u64,
+    /// Celo block number the delay was evaluated at
+    pub block_number: u64,
+    pub seconds_left: u64,
+    pub blocks_left: u64,
+}
+
+impl ProofDelay {
+    /// Whether a message proven at the height passes the delay period checks
+    pub fn elapsed(&self) -> bool {
+        self.seconds_left == 0 && self.blocks_left == 0
+    }
+}
+
+/// Evaluates the delay period checks of a proof height at the given Celo block
+pub fn proof_delay(
+    delay: DelayPeriod,
+    proof_height: u64,
+    processed_time: u64,
+    processed_height: u64,
+    now: u64,
+    block_number: u64,
+) -> ProofDelay {
+    // nanoseconds like the contract, whose block times are whole seconds
+    let valid_time = processed_time
+        .saturating_mul(NANOS_PER_SECOND)
+        .saturating_add(delay.time);
+    let nanos_left = valid_time.saturating_sub(now.saturating_mul(NANOS_PER_SECOND));
+
+    ProofDelay {
+        proof_height,
+        processed_time,
+        processed_height,
+        now,
+        block_number,
+        seconds_left: nanos_left.div_ceil(NANOS_PER_SECOND),
+        blocks_left: processed_height
+            .saturating_add(delay.blocks)
+            .saturating_sub(block_number),
+    }
+}
+
+/// Reads when the consensus state at `proof_height` was stored from IBCHost and evaluates the
+/// delay period checks at the latest Celo block
+pub async fn get_proof_delay<T: Transport>(
+    transport: &T,
+    contracts: &IbcContracts<'_, T>,
+    client_id: &str,
+    delay: DelayPeriod,
+    proof_height: u64,
+) -> Result<ProofDelay, Box<dyn Error>> {
+    let missing = || {
+        format!(
+            "client {} has no consensus state at the proof height {}",
+            client_id, proof_height
+        )
+    };
+    let processed_time = contracts
+        .processed_time(client_id, proof_height)
+        .await?
+        .ok_or_else(missing)?;
+    let processed_height = contracts
+        .processed_height(client_id, proof_height)
+        .await?
+        .ok_or_else(missing)?;
+
+    let block = Web3::new(transport)
+        .eth()
+        .block(BlockId::Number(BlockNumber::Latest))
+        .await?
+        .ok_or("latest block not found")?;
+    let block_number = block.number.ok_or("latest block has no number")?.as_u64();
+
+    Ok(proof_delay(
+        delay,
+        proof_height,
+        processed_time,
+        processed_height,
+        block.timestamp.low_u64(),
+        block_number,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 30 s and 6 blocks of 5 s
+    const DELAY: DelayPeriod = DelayPeriod {
+        time: 30 * NANOS_PER_SECOND,
+        blocks: 6,
+    };
+
+    #[test]
+    fn block_delay_rounds_up() {
+        assert_eq!(
+            DelayPeriod::new(30 * NANOS_PER_SECOND, 5 * NANOS_PER_SECOND),
+            DELAY
+        );
+        assert_eq!(
+            DelayPeriod::new(31 * NANOS_PER_SECOND, 5 * NANOS_PER_SECOND).blocks,
+            7
+        );
+        assert_eq!(DelayPeriod::new(30 * NANOS_PER_SECOND, 0).blocks, 0);
+    }
+
+    #[test]
+    fn already_valid() {
+        let delay = proof_delay(DELAY, 28, 1_000, 100, 1_030, 106);
+        assert!(delay.elapsed());
+        assert_eq!((delay.seconds_left, delay.blocks_left), (0, 0));
+
+        assert!(proof_delay(DelayPeriod::default(), 28, 1_000, 100, 1_000, 100).elapsed());
+    }
+
+    #[test]
+    fn time_bound() {
+        let delay = proof_delay(DELAY, 28, 1_000, 100, 1_010, 106);
+        assert!(!delay.elapsed());
+        assert_eq!((delay.seconds_left, delay.blocks_left), (20, 0));
+
+        // a delay in fractions of a second is waited for up to the next whole second
+        let delay = DelayPeriod { time: 1, blocks: 0 };
+        assert_eq!(
+            proof_delay(delay, 28, 1_000, 100, 1_000, 100).seconds_left,
+            1
+        );
+    }
+
+    #[test]
+    fn block_bound() {
+        let delay = proof_delay(DELAY, 28, 1_000, 100, 1_100, 103);
+        assert!(!delay.elapsed());
+        assert_eq!((delay.seconds_left, delay.blocks_left), (0, 3));
+    }
+
+    #[test]
+    fn saturates_before_processed_time() {
+        // the latest block is older than the one the consensus state was stored at (e.g. a
+        // lagging node)
+        let delay = proof_delay(DELAY, 28, 1_000, 100, 990, 98);
+        assert_eq!((delay.seconds_left, delay.blocks_left), (40, 8));
+
+        let delay = proof_delay(
+            DelayPeriod {
+                time: u64::MAX,
+                blocks: u64::MAX,
+            },
+            28,
+            u64::MAX,
+            u64::MAX,
+            0,
+            0,
+        );
+        assert_eq!(delay.seconds_left, u64::MAX / NANOS_PER_SECOND + 1);
+        assert_eq!(delay.blocks_left, u64::MAX);
+    }
+}
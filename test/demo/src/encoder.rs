@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use crate::{
+    bindings::ibc_handler::{MsgCreateClient, MsgUpdateClient},
+    proto::{
+        self,
+        tendermint::light::{
+            ClientState, ConsensusState, Fraction, LightHeader, MerkleRoot, TmHeader, ValidatorSet,
+        },
+    },
+    types,
+};
+
+/// Client type the tendermint light client is registered under
+pub const CLIENT_TYPE: &str = "07-tendermint";
+
+/// Parameters of the created clients
+#[derive(Clone, Debug)]
+pub struct ClientParams {
+    pub trusting_period: Duration,
+    pub unbonding_period: Duration,
+    pub max_clock_drift: Duration,
+}
+
+impl Default for ClientParams {
+    fn default() -> Self {
+        ClientParams {
+            trusting_period: Duration::from_secs(100000000000),
+            unbonding_period: Duration::from_secs(100000000000),
+            max_clock_drift: Duration::from_secs(100000000000),
+        }
+    }
+}
+
+/// Client state of a new client trusting `header`
+pub fn client_state(header: &LightHeader, params: &ClientParams) -> ClientState {
+    ClientState {
+        chain_id: header.chain_id.to_owned(),
+        trust_level: Some(Fraction {
+            numerator: 1,
+            denominator: 3,
+        }),
+        trusting_period: Some(types::to_duration(
+            params.trusting_period.as_secs() as i64,
+            0,
+        )),
+        unbonding_period: Some(types::to_duration(
+            params.unbonding_period.as_secs() as i64,
+            0,
+        )),
+        max_clock_drift: Some(types::to_duration(
+            params.max_clock_drift.as_secs() as i64,
+            0,
+        )),
+        frozen_height: 0,
+        latest_height: header.height,
+        allow_update_after_expiry: true,
+        allow_update_after_misbehaviour: true,
+    }
+}
+
+/// Consensus state the light client stores for `header`
+pub fn consensus_state(header: &LightHeader) -> ConsensusState {
+    ConsensusState {
+        root: Some(MerkleRoot {
+            hash: header.app_hash.to_owned(),
+        }),
+        timestamp: header.time.to_owned(),
+        next_validators_hash: header.next_validators_hash.to_owned(),
+    }
+}
+
+/// MsgCreateClient of IBCHandler
+pub fn create_client_msg(
+    client_state: &ClientState,
+    consensus_state: &ConsensusState,
+) -> Result<MsgCreateClient, prost::EncodeError> {
+    let consensus_state_bytes =
+        proto::prost_serialize_any(consensus_state, "/tendermint.types.ConsensusState")?;
+    let client_state_bytes =
+        proto::prost_serialize_any(client_state, "/tendermint.types.ClientState")?;
+
+    Ok(MsgCreateClient {
+        client_type: CLIENT_TYPE.to_string(),
+        height: client_state.latest_height as u64,
+        client_state_bytes,
+        consensus_state_bytes,
+    })
+}
+
+/// Header moving a client from `trusted_height` to the height of `tm_header`.
+///
+/// Trusted validators (the validator set at `trusted_height + 1`) are required only for
+/// non-adjacent updates, for adjacent ones `tm_header.validator_set.hash()` already equals
+/// `consensusState.next_validators_hash`.
+pub fn update_header(
+    tm_header: &TmHeader,
+    trusted_height: i64,
+    trusted_validators: Option<ValidatorSet>,
+) -> TmHeader {
+    TmHeader {
+        signed_header: tm_header.signed_header.to_owned(),
+        validator_set: tm_header.validator_set.to_owned(),
+        trusted_height,
+        trusted_validators: Some(trusted_validators.unwrap_or_default()),
+    }
+}
+
+/// MsgUpdateClient of IBCHandler
+pub fn update_client_msg(
+    client_id: &str,
+    tm_header: &TmHeader,
+) -> Result<MsgUpdateClient, prost::EncodeError> {
+    let serialized_header = proto::prost_serialize_any(tm_header, "/tendermint.types.TmHeader")?;
+
+    Ok(MsgUpdateClient {
+        client_id: client_id.to_string(),
+        header: serialized_header,
+    })
+}
+
+/// Height of the header (0 if the header is missing)
+pub fn header_height(tm_header: &TmHeader) -> i64 {
+    tm_header
+        .signed_header
+        .as_ref()
+        .and_then(|signed_header| signed_header.header.as_ref())
+        .map(|header| header.height)
+        .unwrap_or_default()
+}
+
+/// Unix time (seconds) of the header
+pub fn header_time(tm_header: &TmHeader) -> f64 {
+    let header = tm_header
+        .signed_header
+        .as_ref()
+        .and_then(|signed_header| signed_header.header.as_ref());
+    match header.and_then(|header| header.time.as_ref()) {
+        Some(time) => time.seconds as f64 + time.nanos as f64 / 1e9,
+        None => 0.0,
+    }
+}
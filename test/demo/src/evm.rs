@@ -0,0 +1,463 @@
+//! In-process EVM running the IBC contracts compiled by truffle (`build/contracts`), so the
+//! gas of createClient/updateClient can be measured without a Celo node.
+//!
+//! The contracts are deployed and initialized the way the migrations do it (libraries first,
+//! then `IBCHost`, `IBCHandler` and `TendermintLightClient`, `setIBCModule` and
+//! `registerClient`). Celo's ed25519 precompile, which the vanilla EVM lacks, is provided at
+//! the same address and charges the same gas, so the reported `gas_used` is what the receipt of
+//! the transaction would show.
+//!
+//! Blocks are 5 seconds apart and the first one is at the current wall clock time, like on a
+//! freshly started devnet, so headers from the future are rejected the same way.
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use revm::{
+    db::{CacheDB, EmptyDB},
+    primitives::{
+        Address, Bytes, ExecutionResult, Output, Precompile, PrecompileError, PrecompileOutput,
+        PrecompileResult, SpecId, TxKind, U256,
+    },
+    ContextPrecompile, Evm,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use web3::{
+    ethabi,
+    types::{H160, H256},
+};
+
+use crate::{
+    bindings::{ibc_handler, ibc_host},
+    encoder::{self, ClientParams},
+    proto::{
+        self,
+        tendermint::light::{ClientState, TmHeader},
+    },
+};
+
+/// Address of Celo's ed25519 verification precompile (see `Ed25519.sol`)
+pub const ED25519_PRECOMPILE_ADDRESS: u8 = 0xf3;
+/// Gas Celo charges for a call to the ed25519 precompile
+pub const ED25519_VERIFY_GAS: u64 = 2000;
+/// Block gas limit of the Celo networks
+pub const BLOCK_GAS_LIMIT: u64 = 20_000_000;
+/// Seconds between two blocks of the embedded chain (Celo block time)
+const BLOCK_TIME: u64 = 5;
+
+/// Celo's ed25519 precompile: input is `pubkey (32) ‖ signature (64) ‖ message`, the output
+/// word is zero if the signature is valid and one otherwise
+fn ed25519_verify(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+    if gas_limit < ED25519_VERIFY_GAS {
+        return Err(PrecompileError::OutOfGas.into());
+    }
+    if input.len() < 96 {
+        return Err(PrecompileError::Other("invalid input length".to_string()).into());
+    }
+
+    let valid = match (
+        PublicKey::from_bytes(&input[..32]),
+        Signature::try_from(&input[32..96]),
+    ) {
+        (Ok(public_key), Ok(signature)) => public_key.verify(&input[96..], &signature).is_ok(),
+        _ => false,
+    };
+
+    let mut output = [0u8; 32];
+    output[31] = (!valid) as u8;
+
+    Ok(PrecompileOutput::new(
+        ED25519_VERIFY_GAS,
+        Bytes::copy_from_slice(&output),
+    ))
+}
+
+fn to_h160(address: Address) -> H160 {
+    H160::from_slice(address.as_slice())
+}
+
+/// Outcome of a transaction executed on the embedded chain
+#[derive(Clone, Debug, Serialize)]
+pub struct Execution {
+    pub success: bool,
+    /// Gas the receipt reports (intrinsic gas and refunds included)
+    pub gas_used: u64,
+    pub calldata_bytes: usize,
+    /// Revert message (`Error(string)`) or halt reason of a failed transaction
+    pub error: Option<String>,
+    #[serde(skip)]
+    output: Output,
+    #[serde(skip)]
+    logs: Vec<revm::primitives::Log>,
+}
+
+impl Execution {
+    fn new(result: ExecutionResult, calldata_bytes: usize) -> Self {
+        match result {
+            ExecutionResult::Success {
+                gas_used,
+                logs,
+                output,
+                ..
+            } => Execution {
+                success: true,
+                gas_used,
+                calldata_bytes,
+                error: None,
+                output,
+                logs,
+            },
+            ExecutionResult::Revert { gas_used, output } => Execution {
+                success: false,
+                gas_used,
+                calldata_bytes,
+                error: Some(revert_reason(&output)),
+                output: Output::Call(output),
+                logs: Vec::new(),
+            },
+            ExecutionResult::Halt { reason, gas_used } => Execution {
+                success: false,
+                gas_used,
+                calldata_bytes,
+                error: Some(format!("{:?}", reason)),
+                output: Output::Call(Bytes::new()),
+                logs: Vec::new(),
+            },
+        }
+    }
+
+    fn into_result(self, what: &str) -> Result<Self, Box<dyn Error>> {
+        match &self.error {
+            Some(error) => Err(format!("{} failed: {}", what, error).into()),
+            None => Ok(self),
+        }
+    }
+}
+
+/// Message of an `Error(string)` revert, hex of the output otherwise
+fn revert_reason(output: &[u8]) -> String {
+    if output.len() > 4 && output[..4] == [0x08, 0xc3, 0x79, 0xa0] {
+        if let Ok(tokens) = ethabi::decode(&[ethabi::ParamType::String], &output[4..]) {
+            if let Some(ethabi::Token::String(reason)) = tokens.into_iter().next() {
+                return reason;
+            }
+        }
+    }
+
+    format!("0x{}", hex::encode(output))
+}
+
+/// Truffle artifacts (`<name>.json` with the `bytecode` field) of a `build/contracts` directory
+pub struct Artifacts {
+    dir: PathBuf,
+}
+
+impl Artifacts {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(format!(
+                "{} is not a directory, compile the contracts first (`truffle compile`)",
+                dir.display()
+            )
+            .into());
+        }
+
+        Ok(Artifacts { dir })
+    }
+
+    /// Unlinked creation bytecode of the contract, library placeholders left in place
+    pub fn bytecode(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        let path = self.dir.join(format!("{}.json", name));
+        let artifact: Value = serde_json::from_str(
+            &fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        )?;
+
+        match artifact["bytecode"].as_str() {
+            Some(bytecode) if bytecode.len() > 2 => {
+                Ok(bytecode.trim_start_matches("0x").to_string())
+            }
+            _ => Err(format!("{} has no bytecode", path.display()).into()),
+        }
+    }
+}
+
+/// Names of the libraries referenced by truffle placeholders (`__Name______…`, 40 characters)
+fn library_placeholders(bytecode: &str) -> Vec<(String, String)> {
+    let mut placeholders: Vec<(String, String)> = Vec::new();
+    let mut rest = bytecode;
+
+    while let Some(start) = rest.find("__") {
+        let end = (start + 40).min(rest.len());
+        let placeholder = &rest[start..end];
+        let name = placeholder.trim_matches('_').to_string();
+        if !placeholders.iter().any(|(p, _)| p == placeholder) {
+            placeholders.push((placeholder.to_string(), name));
+        }
+        rest = &rest[end..];
+    }
+
+    placeholders
+}
+
+/// Chain with the IBC contracts deployed, executing transactions in process
+pub struct EmbeddedChain {
+    evm: Evm<'static, (), CacheDB<EmptyDB>>,
+    artifacts: Artifacts,
+    deployer: Address,
+    libraries: HashMap<String, Address>,
+    pub host: Address,
+    pub handler: Address,
+    pub light_client: Address,
+}
+
+impl EmbeddedChain {
+    /// Deploys and initializes the contracts like `migrations/` do
+    pub fn deploy(artifacts: Artifacts) -> Result<Self, Box<dyn Error>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let evm = Evm::builder()
+            .with_db(CacheDB::new(EmptyDB::default()))
+            // Celo has the Berlin and London gas schedule since the Espresso hard fork
+            .with_spec_id(SpecId::LONDON)
+            .modify_block_env(|block| {
+                block.gas_limit = U256::from(BLOCK_GAS_LIMIT);
+                block.basefee = U256::ZERO;
+                block.timestamp = U256::from(now);
+            })
+            .modify_tx_env(|tx| {
+                tx.gas_limit = BLOCK_GAS_LIMIT;
+                tx.gas_price = U256::ZERO;
+                tx.nonce = None;
+            })
+            .append_handler_register(|handler| {
+                let precompiles = handler.pre_execution.load_precompiles();
+                handler.pre_execution.load_precompiles = Arc::new(move || {
+                    let mut precompiles = precompiles.clone();
+                    precompiles.extend([(
+                        Address::with_last_byte(ED25519_PRECOMPILE_ADDRESS),
+                        ContextPrecompile::Ordinary(Precompile::Standard(ed25519_verify)),
+                    )]);
+                    precompiles
+                });
+            })
+            .build();
+
+        let mut chain = EmbeddedChain {
+            evm,
+            artifacts,
+            deployer: Address::with_last_byte(0x01),
+            libraries: HashMap::new(),
+            host: Address::ZERO,
+            handler: Address::ZERO,
+            light_client: Address::ZERO,
+        };
+
+        chain.host = chain.deploy_contract("IBCHost", &[])?;
+        chain.handler =
+            chain.deploy_contract("IBCHandler", &[ethabi::Token::Address(to_h160(chain.host))])?;
+        chain.light_client = chain.deploy_contract("TendermintLightClient", &[])?;
+
+        chain
+            .execute(
+                chain.host,
+                ibc_host::set_ibc_module_call(to_h160(chain.handler)),
+            )?
+            .into_result("setIBCModule")?;
+        chain
+            .execute(
+                chain.handler,
+                ibc_handler::register_client_call(
+                    encoder::CLIENT_TYPE.to_string(),
+                    to_h160(chain.light_client),
+                ),
+            )?
+            .into_result("registerClient")?;
+
+        Ok(chain)
+    }
+
+    /// Deploys the contract, deploying the libraries it links to first
+    fn deploy_contract(
+        &mut self,
+        name: &str,
+        constructor_args: &[ethabi::Token],
+    ) -> Result<Address, Box<dyn Error>> {
+        let mut bytecode = self.artifacts.bytecode(name)?;
+
+        for (placeholder, library) in library_placeholders(&bytecode) {
+            let address = match self.libraries.get(&library) {
+                Some(address) => *address,
+                None => {
+                    let address = self.deploy_contract(&library, &[])?;
+                    self.libraries.insert(library, address);
+                    address
+                }
+            };
+            bytecode = bytecode.replace(&placeholder, &hex::encode(address.as_slice()));
+        }
+
+        let mut data = hex::decode(&bytecode).map_err(|e| format!("{}: {}", name, e))?;
+        data.extend(ethabi::encode(constructor_args));
+
+        let execution = self
+            .transact(TxKind::Create, data)?
+            .into_result(&format!("deployment of {}", name))?;
+        match execution.output {
+            Output::Create(_, Some(address)) => Ok(address),
+            _ => Err(format!("deployment of {} returned no address", name).into()),
+        }
+    }
+
+    /// Executes a transaction in a new block
+    pub fn execute(&mut self, to: Address, data: Vec<u8>) -> Result<Execution, Box<dyn Error>> {
+        self.transact(TxKind::Call(to), data)
+    }
+
+    fn transact(&mut self, to: TxKind, data: Vec<u8>) -> Result<Execution, Box<dyn Error>> {
+        let calldata_bytes = data.len();
+        let block = self.evm.block_mut();
+        block.number += U256::from(1);
+        block.timestamp += U256::from(BLOCK_TIME);
+
+        let tx = self.evm.tx_mut();
+        tx.caller = self.deployer;
+        tx.transact_to = to;
+        tx.data = data.into();
+
+        let result = self
+            .evm
+            .transact_commit()
+            .map_err(|e| format!("invalid transaction: {:?}", e))?;
+
+        Ok(Execution::new(result, calldata_bytes))
+    }
+
+    /// Executes a call without committing it (and without a new block), the output it returns
+    fn call(&mut self, to: Address, data: Vec<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let tx = self.evm.tx_mut();
+        tx.caller = self.deployer;
+        tx.transact_to = TxKind::Call(to);
+        tx.data = data.into();
+
+        let result = self
+            .evm
+            .transact()
+            .map_err(|e| format!("invalid call: {:?}", e))?
+            .result;
+
+        match result {
+            ExecutionResult::Success { output, .. } => Ok(output.into_data().to_vec()),
+            ExecutionResult::Revert { output, .. } => Err(revert_reason(&output).into()),
+            ExecutionResult::Halt { reason, .. } => Err(format!("{:?}", reason).into()),
+        }
+    }
+
+    /// Lets `seconds` pass before the next block
+    pub fn sleep(&mut self, seconds: u64) {
+        self.evm.block_mut().timestamp += U256::from(seconds);
+    }
+
+    /// Latest client state stored in IBCHost, `None` if the client does not exist
+    pub fn client_state(&mut self, client_id: &str) -> Result<Option<ClientState>, Box<dyn Error>> {
+        let output = self.call(
+            self.host,
+            ibc_host::get_client_state_call(client_id.to_string()),
+        )?;
+        let tokens = ibc_host::abi()
+            .function("getClientState")?
+            .decode_output(&output)?;
+
+        match tokens.as_slice() {
+            [ethabi::Token::Bytes(_), ethabi::Token::Bool(false)] => Ok(None),
+            [ethabi::Token::Bytes(bytes), ethabi::Token::Bool(true)] => Ok(Some(
+                proto::prost_deserialize_any(bytes, "/tendermint.types.ClientState")?,
+            )),
+            _ => Err(format!("unexpected getClientState output {:?}", tokens).into()),
+        }
+    }
+
+    /// Commitment `IBCHost.makePacketCommitment` computes for the packet
+    pub fn make_packet_commitment(
+        &mut self,
+        packet: ibc_host::Packet,
+    ) -> Result<H256, Box<dyn Error>> {
+        let output = self.call(self.host, ibc_host::make_packet_commitment_call(packet))?;
+
+        bytes32(&output)
+    }
+
+    /// Commitment `IBCHost.makePacketAcknowledgementCommitment` computes for the acknowledgement
+    pub fn make_packet_acknowledgement_commitment(
+        &mut self,
+        acknowledgement: Vec<u8>,
+    ) -> Result<H256, Box<dyn Error>> {
+        let output = self.call(
+            self.host,
+            ibc_host::make_packet_acknowledgement_commitment_call(acknowledgement),
+        )?;
+
+        bytes32(&output)
+    }
+
+    /// Creates a client trusting `tm_header`, returns its identifier
+    pub fn create_client(
+        &mut self,
+        tm_header: &TmHeader,
+        params: &ClientParams,
+    ) -> Result<(Option<String>, Execution), Box<dyn Error>> {
+        let header = tm_header
+            .signed_header
+            .as_ref()
+            .and_then(|signed_header| signed_header.header.as_ref())
+            .ok_or("header is missing")?;
+        let msg = encoder::create_client_msg(
+            &encoder::client_state(header, params),
+            &encoder::consensus_state(header),
+        )?;
+
+        let execution = self.execute(self.handler, ibc_handler::create_client_call(msg))?;
+
+        let event = ibc_host::abi().event("GeneratedClientIdentifier")?;
+        let client_id = execution
+            .logs
+            .iter()
+            .filter(|log| log.address == self.host)
+            .find_map(|log| {
+                event
+                    .parse_log(ethabi::RawLog {
+                        topics: log.topics().iter().map(|topic| topic.0.into()).collect(),
+                        data: log.data.data.to_vec(),
+                    })
+                    .ok()
+            })
+            .and_then(|log| log.params.into_iter().next())
+            .and_then(|param| param.value.into_string());
+
+        Ok((client_id, execution))
+    }
+
+    /// Updates the client with `tm_header` (already carrying the trusted height and validators)
+    pub fn update_client(
+        &mut self,
+        client_id: &str,
+        tm_header: &TmHeader,
+    ) -> Result<Execution, Box<dyn Error>> {
+        let msg = encoder::update_client_msg(client_id, tm_header)?;
+
+        self.execute(self.handler, ibc_handler::update_client_call(msg))
+    }
+}
+
+fn bytes32(output: &[u8]) -> Result<H256, Box<dyn Error>> {
+    match output.len() {
+        32 => Ok(H256::from_slice(output)),
+        len => Err(format!("expected a bytes32 output, got {} bytes", len).into()),
+    }
+}
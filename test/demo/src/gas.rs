@@ -0,0 +1,278 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::{error::Error, fmt};
+use web3::{
+    types::{Address, BlockId, BlockNumber, Bytes, U256},
+    Transport, Web3,
+};
+
+/// Gas estimation settings
+#[derive(Clone, Debug)]
+pub struct GasConfig {
+    /// Safety margin (in percent) added on top of `eth_estimateGas`
+    pub margin_percent: u64,
+    /// Fixed gas limit, disables the estimation (the block gas limit guard still applies)
+    pub gas_limit: Option<U256>,
+    /// Fixed gas price, disables the gas price lookup
+    pub gas_price: Option<U256>,
+    /// Priority fee percentile used with `eth_feeHistory`
+    pub reward_percentile: f64,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        GasConfig {
+            margin_percent: 20,
+            gas_limit: None,
+            gas_price: None,
+            reward_percentile: 50.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GasError {
+    Rpc(web3::Error),
+    /// The call needs more gas than a single block can fit, so it can never be mined
+    ExceedsBlockGasLimit {
+        method: String,
+        gas: U256,
+        block_gas_limit: U256,
+    },
+}
+
+impl fmt::Display for GasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GasError::Rpc(e) => write!(f, "gas estimation failed: {}", e),
+            GasError::ExceedsBlockGasLimit {
+                method,
+                gas,
+                block_gas_limit,
+            } => write!(
+                f,
+                "refusing to send {}: it needs {} gas (incl. safety margin), but the current block gas limit is {}. \
+                 The tx can never be mined and would only burn fees. Large validator sets in non-adjacent \
+                 (skipping) mode are the usual cause, try adjacent updates or a closer trusted height",
+                method, gas, block_gas_limit
+            ),
+        }
+    }
+}
+
+impl Error for GasError {}
+
+/// Whether the node rejected the call because its execution fails (a revert, or no gas limit
+/// makes it succeed)
+pub fn is_revert(e: &web3::Error) -> bool {
+    match e {
+        web3::Error::Rpc(e) => {
+            let message = e.message.to_lowercase();
+            e.code.code() == 3
+                || message.contains("revert")
+                || message.contains("always failing")
+                || message.contains("gas required exceeds allowance")
+        }
+        _ => false,
+    }
+}
+
+/// Whether the estimation failed on the nonce or balance of the sender, which the txs still in
+/// flight change
+pub fn is_nonce_or_balance(e: &web3::Error) -> bool {
+    match e {
+        web3::Error::Rpc(e) => {
+            let message = e.message.to_lowercase();
+            message.contains("nonce") || message.contains("insufficient funds")
+        }
+        _ => false,
+    }
+}
+
+impl From<web3::Error> for GasError {
+    fn from(e: web3::Error) -> Self {
+        GasError::Rpc(e)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FeeHistory {
+    base_fee_per_gas: Vec<U256>,
+    #[serde(default)]
+    reward: Vec<Vec<U256>>,
+}
+
+/// Estimates gas limits and prices of the relayer transactions
+pub struct GasOracle<T: Transport> {
+    web3: Web3<T>,
+    config: GasConfig,
+    fee_currency: Option<Address>,
+}
+
+impl<T: Transport> GasOracle<T> {
+    pub fn new(transport: T, config: GasConfig, fee_currency: Option<Address>) -> Self {
+        GasOracle {
+            web3: Web3::new(transport),
+            config,
+            fee_currency,
+        }
+    }
+
+    /// Gas limit of the latest block
+    pub async fn block_gas_limit(&self) -> web3::Result<U256> {
+        let block = self
+            .web3
+            .eth()
+            .block(BlockId::Number(BlockNumber::Latest))
+            .await?
+            .ok_or_else(|| web3::Error::InvalidResponse("latest block not found".to_string()))?;
+
+        Ok(block.gas_limit)
+    }
+
+    /// Raw `eth_estimateGas` result against the latest block, reverts are reported with the
+    /// node's message
+    pub async fn estimate(
+        &self,
+        from: Address,
+        to: Address,
+        data: &[u8],
+        value: U256,
+    ) -> web3::Result<U256> {
+        self.estimate_at(from, to, data, value, BlockNumber::Latest)
+            .await
+    }
+
+    /// Raw `eth_estimateGas` result on top of the txs in the node's pool
+    pub async fn estimate_pending(
+        &self,
+        from: Address,
+        to: Address,
+        data: &[u8],
+        value: U256,
+    ) -> web3::Result<U256> {
+        self.estimate_at(from, to, data, value, BlockNumber::Pending)
+            .await
+    }
+
+    async fn estimate_at(
+        &self,
+        from: Address,
+        to: Address,
+        data: &[u8],
+        value: U256,
+        block: BlockNumber,
+    ) -> web3::Result<U256> {
+        let mut request = json!({
+            "from": from,
+            "to": to,
+            "data": Bytes(data.to_vec()),
+            "value": value,
+        });
+        if let Some(currency) = self.fee_currency {
+            request["feeCurrency"] = json!(currency);
+        }
+
+        let response = self
+            .web3
+            .transport()
+            .execute("eth_estimateGas", vec![request, json!(block)])
+            .await?;
+
+        serde_json::from_value(response).map_err(|e| web3::Error::Decoder(e.to_string()))
+    }
+
+    /// Adds the safety margin to the estimate (or takes the configured gas limit) and
+    /// refuses anything that does not fit into a block.
+    pub async fn gas_limit(&self, method: &str, estimate: Option<U256>) -> Result<U256, GasError> {
+        let gas = match (self.config.gas_limit, estimate) {
+            (Some(gas), _) => gas,
+            (None, Some(estimate)) => estimate * (100 + self.config.margin_percent) / 100,
+            (None, None) => {
+                return Err(GasError::Rpc(web3::Error::InvalidResponse(format!(
+                    "no gas estimate for {}",
+                    method
+                ))))
+            }
+        };
+
+        let block_gas_limit = self.block_gas_limit().await?;
+        if gas > block_gas_limit {
+            // a margin that pushes a fitting estimate over the limit is capped instead
+            match estimate {
+                Some(estimate)
+                    if self.config.gas_limit.is_none() && estimate <= block_gas_limit =>
+                {
+                    return Ok(block_gas_limit)
+                }
+                _ => {
+                    return Err(GasError::ExceedsBlockGasLimit {
+                        method: method.to_string(),
+                        gas,
+                        block_gas_limit,
+                    })
+                }
+            }
+        }
+
+        Ok(gas)
+    }
+
+    /// Gas price in the fee currency.
+    ///
+    /// For CELO the price is derived from `eth_feeHistory` (next block base fee plus the
+    /// median tip) when the node supports it, otherwise (and for stable token fee
+    /// currencies) `eth_gasPrice` is used.
+    pub async fn gas_price(&self) -> web3::Result<U256> {
+        if let Some(price) = self.config.gas_price {
+            return Ok(price);
+        }
+
+        if self.fee_currency.is_none() {
+            if let Some(price) = self.fee_history_price().await {
+                return Ok(price);
+            }
+        }
+
+        let params = match self.fee_currency {
+            Some(currency) => vec![json!(currency)],
+            None => vec![],
+        };
+        let response = self
+            .web3
+            .transport()
+            .execute("eth_gasPrice", params)
+            .await?;
+
+        serde_json::from_value(response).map_err(|e| web3::Error::Decoder(e.to_string()))
+    }
+
+    async fn fee_history_price(&self) -> Option<U256> {
+        let response = self
+            .web3
+            .transport()
+            .execute(
+                "eth_feeHistory",
+                vec![
+                    json!(U256::from(4)),
+                    json!("latest"),
+                    json!([self.config.reward_percentile]),
+                ],
+            )
+            .await
+            .ok()?;
+        let history: FeeHistory = serde_json::from_value(response).ok()?;
+
+        let base_fee = *history.base_fee_per_gas.last()?;
+        let mut tips: Vec<U256> = history
+            .reward
+            .iter()
+            .filter_map(|rewards| rewards.first().cloned())
+            .collect();
+        tips.sort();
+        let tip = tips.get(tips.len() / 2).cloned().unwrap_or_default();
+
+        Some(base_fee + tip)
+    }
+}
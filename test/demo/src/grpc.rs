@@ -0,0 +1,306 @@
+//! gRPC queries of the IBC state of the Cosmos chain (`ibc.core.client.v1`,
+//! `ibc.core.connection.v1` and `ibc.core.channel.v1`), to find out which handshakes and packets
+//! are pending instead of guessing from tendermint RPC data.
+//!
+//! Raw responses are converted to the domain types of the `ibc` crate, which serialize to JSON.
+//! Lists are fetched page by page until the node reports no next page.
+use ibc::{
+    ics02_client::client_state::AnyClientState,
+    ics03_connection::connection::{ConnectionEnd, IdentifiedConnectionEnd},
+    ics04_channel::channel::{ChannelEnd, IdentifiedChannelEnd},
+};
+use ibc_proto::{
+    cosmos::base::query::v1beta1::{PageRequest, PageResponse},
+    ibc::core::{
+        channel::v1::{
+            query_client::QueryClient as ChannelQueryClient, QueryChannelRequest,
+            QueryChannelsRequest, QueryPacketCommitmentsRequest, QueryUnreceivedAcksRequest,
+            QueryUnreceivedPacketsRequest,
+        },
+        client::v1::{
+            query_client::QueryClient as ClientQueryClient, QueryClientStateRequest,
+            QueryClientStatesRequest,
+        },
+        connection::v1::{
+            query_client::QueryClient as ConnectionQueryClient, QueryConnectionRequest,
+            QueryConnectionsRequest,
+        },
+    },
+};
+use serde::Serialize;
+use std::{convert::TryFrom, error::Error, future::Future};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code, Status,
+};
+
+/// Items per page of the list queries
+const PAGE_LIMIT: u64 = 100;
+
+/// Client state of a client hosted by the Cosmos chain, decoded if it is of a client type the
+/// `ibc` crate knows (e.g. not the Celo light client)
+#[derive(Clone, Debug, Serialize)]
+pub struct CosmosClientState {
+    pub client_id: String,
+    pub type_url: String,
+    pub client_state: Option<AnyClientState>,
+}
+
+/// `None` for a `NotFound` status, the status as an error otherwise
+fn not_found<T>(result: Result<T, Status>) -> Result<Option<T>, Box<dyn Error>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(status) if status.code() == Code::NotFound => Ok(None),
+        Err(status) => Err(status.into()),
+    }
+}
+
+fn page(key: Vec<u8>) -> Option<PageRequest> {
+    Some(PageRequest {
+        key,
+        limit: PAGE_LIMIT,
+        ..Default::default()
+    })
+}
+
+/// Items of every page, `query` fetching the page of the given key
+async fn paginate<I, F, Fut>(mut query: F) -> Result<Vec<I>, Box<dyn Error>>
+where
+    F: FnMut(Option<PageRequest>) -> Fut,
+    Fut: Future<Output = Result<(Vec<I>, Option<PageResponse>), Status>>,
+{
+    let mut items = Vec::new();
+    let mut key = Vec::new();
+    loop {
+        let (page_items, pagination) = query(page(key)).await?;
+        items.extend(page_items);
+
+        match pagination {
+            Some(pagination) if !pagination.next_key.is_empty() => key = pagination.next_key,
+            _ => return Ok(items),
+        }
+    }
+}
+
+/// IBC query services of a Cosmos node (the gRPC server of the app, port 9090 by default)
+#[derive(Clone)]
+pub struct CosmosQuery {
+    clients: ClientQueryClient<Channel>,
+    connections: ConnectionQueryClient<Channel>,
+    channels: ChannelQueryClient<Channel>,
+}
+
+impl CosmosQuery {
+    pub async fn connect(url: &str) -> Result<Self, Box<dyn Error>> {
+        let channel = Endpoint::from_shared(url.to_string())?
+            .connect()
+            .await
+            .map_err(|e| format!("{}: {}", url, e))?;
+
+        Ok(CosmosQuery {
+            clients: ClientQueryClient::new(channel.clone()),
+            connections: ConnectionQueryClient::new(channel.clone()),
+            channels: ChannelQueryClient::new(channel),
+        })
+    }
+
+    pub async fn client_states(&self) -> Result<Vec<CosmosClientState>, Box<dyn Error>> {
+        let states = paginate(|pagination| {
+            let mut clients = self.clients.clone();
+            async move {
+                let response = clients
+                    .client_states(QueryClientStatesRequest { pagination })
+                    .await?
+                    .into_inner();
+                Ok((response.client_states, response.pagination))
+            }
+        })
+        .await?;
+
+        Ok(states
+            .into_iter()
+            .filter_map(|state| {
+                let any = state.client_state?;
+                Some(CosmosClientState {
+                    client_id: state.client_id,
+                    type_url: any.type_url.clone(),
+                    client_state: AnyClientState::try_from(any).ok(),
+                })
+            })
+            .collect())
+    }
+
+    /// Latest client state, `None` if the client does not exist
+    pub async fn client_state(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<CosmosClientState>, Box<dyn Error>> {
+        let response = not_found(
+            self.clients
+                .clone()
+                .client_state(QueryClientStateRequest {
+                    client_id: client_id.to_string(),
+                })
+                .await,
+        )?;
+
+        Ok(response
+            .and_then(|response| response.into_inner().client_state)
+            .map(|any| CosmosClientState {
+                client_id: client_id.to_string(),
+                type_url: any.type_url.clone(),
+                client_state: AnyClientState::try_from(any).ok(),
+            }))
+    }
+
+    pub async fn connections(&self) -> Result<Vec<IdentifiedConnectionEnd>, Box<dyn Error>> {
+        let connections = paginate(|pagination| {
+            let mut connections = self.connections.clone();
+            async move {
+                let response = connections
+                    .connections(QueryConnectionsRequest { pagination })
+                    .await?
+                    .into_inner();
+                Ok((response.connections, response.pagination))
+            }
+        })
+        .await?;
+
+        connections
+            .into_iter()
+            .map(|connection| Ok(IdentifiedConnectionEnd::try_from(connection)?))
+            .collect()
+    }
+
+    /// Connection end, `None` if the connection does not exist
+    pub async fn connection(
+        &self,
+        connection_id: &str,
+    ) -> Result<Option<ConnectionEnd>, Box<dyn Error>> {
+        let response = not_found(
+            self.connections
+                .clone()
+                .connection(QueryConnectionRequest {
+                    connection_id: connection_id.to_string(),
+                })
+                .await,
+        )?;
+
+        match response.and_then(|response| response.into_inner().connection) {
+            Some(connection) => Ok(Some(ConnectionEnd::try_from(connection)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn channels(&self) -> Result<Vec<IdentifiedChannelEnd>, Box<dyn Error>> {
+        let channels = paginate(|pagination| {
+            let mut channels = self.channels.clone();
+            async move {
+                let response = channels
+                    .channels(QueryChannelsRequest { pagination })
+                    .await?
+                    .into_inner();
+                Ok((response.channels, response.pagination))
+            }
+        })
+        .await?;
+
+        channels
+            .into_iter()
+            .map(|channel| Ok(IdentifiedChannelEnd::try_from(channel)?))
+            .collect()
+    }
+
+    /// Channel end, `None` if the channel does not exist
+    pub async fn channel(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+    ) -> Result<Option<ChannelEnd>, Box<dyn Error>> {
+        let response = not_found(
+            self.channels
+                .clone()
+                .channel(QueryChannelRequest {
+                    port_id: port_id.to_string(),
+                    channel_id: channel_id.to_string(),
+                })
+                .await,
+        )?;
+
+        match response.and_then(|response| response.into_inner().channel) {
+            Some(channel) => Ok(Some(ChannelEnd::try_from(channel)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Sequences of the packets sent on the channel whose commitment is still stored, i.e.
+    /// not acknowledged nor timed out yet
+    pub async fn packet_commitments(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        let commitments = paginate(|pagination| {
+            let mut channels = self.channels.clone();
+            async move {
+                let response = channels
+                    .packet_commitments(QueryPacketCommitmentsRequest {
+                        port_id: port_id.to_string(),
+                        channel_id: channel_id.to_string(),
+                        pagination,
+                    })
+                    .await?
+                    .into_inner();
+                Ok((response.commitments, response.pagination))
+            }
+        })
+        .await?;
+
+        Ok(commitments
+            .into_iter()
+            .map(|commitment| commitment.sequence)
+            .collect())
+    }
+
+    /// Those of the sequences (of packets sent by the counterparty to the channel) the chain
+    /// has not received
+    pub async fn unreceived_packets(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+        sequences: &[u64],
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        Ok(self
+            .channels
+            .clone()
+            .unreceived_packets(QueryUnreceivedPacketsRequest {
+                port_id: port_id.to_string(),
+                channel_id: channel_id.to_string(),
+                packet_commitment_sequences: sequences.to_vec(),
+            })
+            .await?
+            .into_inner()
+            .sequences)
+    }
+
+    /// Those of the sequences (of packets the chain sent on the channel and the counterparty
+    /// acknowledged) whose acknowledgement the chain has not received
+    pub async fn unreceived_acks(
+        &self,
+        port_id: &str,
+        channel_id: &str,
+        sequences: &[u64],
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        Ok(self
+            .channels
+            .clone()
+            .unreceived_acks(QueryUnreceivedAcksRequest {
+                port_id: port_id.to_string(),
+                channel_id: channel_id.to_string(),
+                packet_ack_sequences: sequences.to_vec(),
+            })
+            .await?
+            .into_inner()
+            .sequences)
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tendermint_rpc::{Client, HttpClient};
+use tokio::time::{sleep, Duration};
+
+use crate::{
+    proto::tendermint::light::{SignedHeader, TmHeader, ValidatorSet},
+    types,
+};
+
+/// Fetches tendermint headers over RPC, converted to the types of the light client
+#[derive(Clone)]
+pub struct HeaderSource {
+    client: HttpClient,
+    /// If set, fetched headers and validator sets are saved there as JSON
+    save_dir: Option<PathBuf>,
+}
+
+impl HeaderSource {
+    pub fn new(client: HttpClient) -> Self {
+        HeaderSource {
+            client,
+            save_dir: None,
+        }
+    }
+
+    /// Saves every fetched header to `dir` (`header.<height>.signed_header.json` and
+    /// `header.<height>.validator_set.json`)
+    pub fn with_save_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.save_dir = Some(dir.into());
+        self
+    }
+
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    /// Chain id the node reports
+    pub async fn chain_id(&self) -> Result<String, Box<dyn Error>> {
+        Ok(self.client.status().await?.node_info.network.to_string())
+    }
+
+    /// Height of the latest block
+    pub async fn latest_height(&self) -> Result<u64, Box<dyn Error>> {
+        Ok(self.client.latest_block().await?.block.header.height.into())
+    }
+
+    /// Waits until the block at given height is available (or gives up after ~20 s)
+    pub async fn wait_for_block(&self, height: u64) -> bool {
+        // NOTE: we could use websocket client subscription, but public node providers doesn't seem
+        // to expose the websocket endpoint, so this is more universal approach
+        for _ in 1..10 {
+            match self
+                .client
+                .block(tendermint::block::Height::from(height as u32))
+                .await
+            {
+                Ok(_) => return true,
+                Err(_) => sleep(Duration::from_secs(2)).await,
+            }
+        }
+
+        false
+    }
+
+    /// Signed header and validator set at given height
+    pub async fn light_block(&self, height: i64) -> Result<TmHeader, Box<dyn Error>> {
+        let vs = self.validator_set(height).await?;
+        let sh = self.signed_header(height).await?;
+
+        Ok(types::to_light_block(&sh, &vs))
+    }
+
+    pub async fn validator_set(&self, height: i64) -> Result<ValidatorSet, Box<dyn Error>> {
+        let validator_set_future = self
+            .client
+            .validators(
+                tendermint::block::Height::from(height as u32),
+                tendermint_rpc::Paging::All,
+            )
+            .await?;
+
+        let vs = types::to_validator_set(&validator_set_future.validators);
+        self.save(&format!("header.{}.validator_set.json", height), &vs)?;
+
+        Ok(vs)
+    }
+
+    pub async fn signed_header(&self, height: i64) -> Result<SignedHeader, Box<dyn Error>> {
+        let commit_future = self
+            .client
+            .commit(tendermint::block::Height::from(height as u32))
+            .await?;
+
+        let sh = types::to_signed_header(&commit_future.signed_header);
+        self.save(&format!("header.{}.signed_header.json", height), &sh)?;
+
+        Ok(sh)
+    }
+
+    fn save<V: serde::Serialize>(&self, name: &str, value: &V) -> Result<(), Box<dyn Error>> {
+        match &self.save_dir {
+            Some(dir) => write(dir, name, value),
+            None => Ok(()),
+        }
+    }
+}
+
+fn write<V: serde::Serialize>(dir: &Path, name: &str, value: &V) -> Result<(), Box<dyn Error>> {
+    let mut output = File::create(dir.join(name))?;
+    let data = serde_json::to_vec_pretty(value)?;
+    output.write_all(&data)?;
+
+    Ok(())
+}
+
+/// Saves the signed header and validator set of `tm_header` in the format of
+/// `HeaderSource::with_save_dir`
+pub fn write_light_block(dir: &Path, tm_header: &TmHeader) -> Result<(), Box<dyn Error>> {
+    let height = crate::encoder::header_height(tm_header);
+    let signed_header = tm_header
+        .signed_header
+        .as_ref()
+        .ok_or("header is missing")?;
+    let validator_set = tm_header
+        .validator_set
+        .as_ref()
+        .ok_or("validator set is missing")?;
+
+    write(
+        dir,
+        &format!("header.{}.signed_header.json", height),
+        signed_header,
+    )?;
+    write(
+        dir,
+        &format!("header.{}.validator_set.json", height),
+        validator_set,
+    )
+}
+
+fn read<V: serde::de::DeserializeOwned>(
+    dir: &Path,
+    name: &str,
+    fixup: impl FnOnce(&mut serde_json::Value),
+) -> Result<V, Box<dyn Error>> {
+    let path = dir.join(name);
+    let data = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut value =
+        serde_json::from_str(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+    fixup(&mut value);
+
+    Ok(serde_json::from_value(value).map_err(|e| format!("{}: {}", path.display(), e))?)
+}
+
+/// Validator set saved by `HeaderSource::with_save_dir` (e.g. the fixtures in `test/data`).
+///
+/// Public keys rewritten for the truffle tests by `scripts/fix_test_data.sh`
+/// (`{"ed25519": ...}` instead of `{"sum": {"Ed25519": ...}}`) are accepted as well.
+pub fn read_validator_set(dir: &Path, height: i64) -> Result<ValidatorSet, Box<dyn Error>> {
+    read(
+        dir,
+        &format!("header.{}.validator_set.json", height),
+        |validator_set| {
+            let validators = validator_set["validators"].as_array_mut().into_iter();
+            for validator in validators.flatten() {
+                let pub_key = &mut validator["pub_key"];
+                if pub_key.get("sum").is_some() {
+                    continue;
+                }
+                for (key, variant) in [("ed25519", "Ed25519"), ("secp256k1", "Secp256k1")] {
+                    if let Some(bytes) = pub_key.get(key).cloned() {
+                        *pub_key = serde_json::json!({ "sum": { variant: bytes } });
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Signed header saved by `HeaderSource::with_save_dir`
+pub fn read_signed_header(dir: &Path, height: i64) -> Result<SignedHeader, Box<dyn Error>> {
+    read(
+        dir,
+        &format!("header.{}.signed_header.json", height),
+        |_| (),
+    )
+}
+
+/// Saved signed header and validator set at given height
+pub fn read_light_block(dir: &Path, height: i64) -> Result<TmHeader, Box<dyn Error>> {
+    let vs = read_validator_set(dir, height)?;
+    let sh = read_signed_header(dir, height)?;
+
+    Ok(types::to_light_block(&sh, &vs))
+}
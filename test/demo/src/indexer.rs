@@ -0,0 +1,351 @@
+//! Indexer of the IBC events emitted on Celo: identifiers generated by IBCHost for new
+//! clients, connections and channels, and the packet lifecycle events of IBCHandler.
+//!
+//! Logs are fetched in bounded block ranges (`eth_getLogs` of public nodes rejects large ones)
+//! and the next block to scan is stored with the events, so a restarted relayer continues where
+//! it stopped instead of scanning from block 0 again. Celo blocks are final once mined (IBFT),
+//! scanned ranges are never revisited.
+//!
+//! Without `--state-db` the events are kept in a temporary store, for the life of the process.
+use serde::{Deserialize, Serialize};
+use std::{error::Error, ops::Deref};
+use tracing::{debug, info};
+use web3::{
+    types::{BlockNumber, Bytes, FilterBuilder, Log, H256},
+    Transport, Web3,
+};
+
+use crate::{
+    bindings::{
+        ibc_handler::{
+            self,
+            events::{AcknowledgePacket, RecvPacket, SendPacket, WriteAcknowledgement},
+        },
+        ibc_host::events::{
+            GeneratedChannelIdentifier, GeneratedClientIdentifier, GeneratedConnectionIdentifier,
+        },
+    },
+    contracts::IbcContracts,
+    proto::ibc::channel,
+    store::{IndexScope, Store},
+};
+
+/// Blocks per `eth_getLogs` request
+pub const DEFAULT_BLOCK_RANGE: u64 = 5_000;
+
+/// Packet carried by the IBCHandler events
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Packet {
+    pub sequence: u64,
+    pub source_port: String,
+    pub source_channel: String,
+    pub destination_port: String,
+    pub destination_channel: String,
+    pub data: Bytes,
+    pub timeout_revision_number: u64,
+    pub timeout_revision_height: u64,
+    pub timeout_timestamp: u64,
+}
+
+impl From<ibc_handler::Packet> for Packet {
+    fn from(packet: ibc_handler::Packet) -> Self {
+        Packet {
+            sequence: packet.sequence,
+            source_port: packet.source_port,
+            source_channel: packet.source_channel,
+            destination_port: packet.destination_port,
+            destination_channel: packet.destination_channel,
+            data: Bytes(packet.data),
+            timeout_revision_number: packet.timeout_height.revision_number,
+            timeout_revision_height: packet.timeout_height.revision_height,
+            timeout_timestamp: packet.timeout_timestamp,
+        }
+    }
+}
+
+impl From<&Packet> for channel::Packet {
+    fn from(packet: &Packet) -> Self {
+        channel::Packet {
+            sequence: packet.sequence,
+            source_port: packet.source_port.clone(),
+            source_channel: packet.source_channel.clone(),
+            destination_port: packet.destination_port.clone(),
+            destination_channel: packet.destination_channel.clone(),
+            data: packet.data.0.clone(),
+            timeout_height: Some(channel::Height {
+                revision_number: packet.timeout_revision_number,
+                revision_height: packet.timeout_revision_height,
+            }),
+            timeout_timestamp: packet.timeout_timestamp,
+        }
+    }
+}
+
+/// Decoded event, named after the contract event
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IbcEvent {
+    GeneratedClientIdentifier {
+        client_id: String,
+    },
+    GeneratedConnectionIdentifier {
+        connection_id: String,
+    },
+    GeneratedChannelIdentifier {
+        channel_id: String,
+    },
+    SendPacket {
+        packet: Packet,
+    },
+    RecvPacket {
+        packet: Packet,
+    },
+    WriteAcknowledgement {
+        port_id: String,
+        channel_id: String,
+        sequence: u64,
+        acknowledgement: Bytes,
+    },
+    AcknowledgePacket {
+        packet: Packet,
+        acknowledgement: Bytes,
+    },
+}
+
+impl IbcEvent {
+    /// Topics 0 of the indexed events
+    fn topics() -> Vec<H256> {
+        vec![
+            GeneratedClientIdentifier::topic(),
+            GeneratedConnectionIdentifier::topic(),
+            GeneratedChannelIdentifier::topic(),
+            SendPacket::topic(),
+            RecvPacket::topic(),
+            WriteAcknowledgement::topic(),
+            AcknowledgePacket::topic(),
+        ]
+    }
+
+    /// Decodes the log, `None` if it is not one of the indexed events
+    pub fn from_log(log: &Log) -> Result<Option<Self>, Box<dyn Error>> {
+        let topic = match log.topics.first() {
+            Some(topic) => *topic,
+            None => return Ok(None),
+        };
+
+        let event = if topic == GeneratedClientIdentifier::topic() {
+            IbcEvent::GeneratedClientIdentifier {
+                client_id: GeneratedClientIdentifier::from_log(log)?.arg0,
+            }
+        } else if topic == GeneratedConnectionIdentifier::topic() {
+            IbcEvent::GeneratedConnectionIdentifier {
+                connection_id: GeneratedConnectionIdentifier::from_log(log)?.arg0,
+            }
+        } else if topic == GeneratedChannelIdentifier::topic() {
+            IbcEvent::GeneratedChannelIdentifier {
+                channel_id: GeneratedChannelIdentifier::from_log(log)?.arg0,
+            }
+        } else if topic == SendPacket::topic() {
+            IbcEvent::SendPacket {
+                packet: SendPacket::from_log(log)?.packet.into(),
+            }
+        } else if topic == RecvPacket::topic() {
+            IbcEvent::RecvPacket {
+                packet: RecvPacket::from_log(log)?.packet.into(),
+            }
+        } else if topic == WriteAcknowledgement::topic() {
+            let event = WriteAcknowledgement::from_log(log)?;
+            IbcEvent::WriteAcknowledgement {
+                port_id: event.destination_port_id,
+                channel_id: event.destination_channel,
+                sequence: event.sequence,
+                acknowledgement: Bytes(event.acknowledgement),
+            }
+        } else if topic == AcknowledgePacket::topic() {
+            let event = AcknowledgePacket::from_log(log)?;
+            IbcEvent::AcknowledgePacket {
+                packet: event.packet.into(),
+                acknowledgement: Bytes(event.acknowledgement),
+            }
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(event))
+    }
+}
+
+/// Event with its position in the chain
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IndexedEvent {
+    pub block_number: u64,
+    pub log_index: u64,
+    pub tx_hash: H256,
+    #[serde(flatten)]
+    pub event: IbcEvent,
+}
+
+enum Backing<'a> {
+    Shared(&'a Store),
+    Owned(Store),
+}
+
+impl Deref for Backing<'_> {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        match self {
+            Backing::Shared(store) => store,
+            Backing::Owned(store) => store,
+        }
+    }
+}
+
+pub struct Indexer<'a, T: Transport> {
+    transport: &'a T,
+    contracts: IbcContracts<'a, T>,
+    store: Backing<'a>,
+    start_block: u64,
+    block_range: u64,
+}
+
+impl<'a, T: Transport> Indexer<'a, T> {
+    /// Indexer keeping its events in `store`, or in a temporary store if none is given
+    pub fn new(transport: &'a T, store: Option<&'a Store>) -> Result<Self, Box<dyn Error>> {
+        Ok(Indexer {
+            transport,
+            contracts: IbcContracts::new(transport),
+            store: match store {
+                Some(store) => Backing::Shared(store),
+                None => Backing::Owned(Store::temporary()?),
+            },
+            start_block: 0,
+            block_range: DEFAULT_BLOCK_RANGE,
+        })
+    }
+
+    /// Block the first scan starts at (e.g. the deployment block of the contracts), ignored
+    /// once the store has a cursor
+    pub fn with_start_block(mut self, start_block: u64) -> Self {
+        self.start_block = start_block;
+        self
+    }
+
+    pub fn with_block_range(mut self, block_range: u64) -> Self {
+        self.block_range = block_range.max(1);
+        self
+    }
+
+    pub fn contracts(&self) -> &IbcContracts<'a, T> {
+        &self.contracts
+    }
+
+    /// Next block to scan
+    pub fn cursor(&self) -> Result<u64, Box<dyn Error>> {
+        Ok(self
+            .store
+            .index_cursor(&self.scope())?
+            .unwrap_or(self.start_block))
+    }
+
+    /// The contracts whose events are indexed
+    fn scope(&self) -> IndexScope {
+        IndexScope {
+            host: self.contracts.host.address(),
+            handler: self.contracts.handler_address(),
+        }
+    }
+
+    /// Scans the blocks up to the latest one, returns the number of new events
+    pub async fn sync(&self) -> Result<usize, Box<dyn Error>> {
+        let eth = Web3::new(self.transport).eth();
+        let latest = eth.block_number().await?.as_u64();
+        let scope = self.scope();
+        let addresses = vec![scope.host, scope.handler];
+
+        let mut from = self.cursor()?;
+        let mut count = 0;
+        while from <= latest {
+            let to = (from + self.block_range - 1).min(latest);
+            let logs = eth
+                .logs(
+                    FilterBuilder::default()
+                        .address(addresses.clone())
+                        .from_block(BlockNumber::Number(from.into()))
+                        .to_block(BlockNumber::Number(to.into()))
+                        .topics(Some(IbcEvent::topics()), None, None, None)
+                        .build(),
+                )
+                .await?;
+
+            let mut events = Vec::new();
+            for log in &logs {
+                let event = match IbcEvent::from_log(log)? {
+                    Some(event) => event,
+                    None => continue,
+                };
+                if let IbcEvent::GeneratedClientIdentifier { client_id } = &event {
+                    self.map_client(client_id).await?;
+                }
+                events.push(IndexedEvent {
+                    block_number: log.block_number.ok_or("log of a pending block")?.as_u64(),
+                    log_index: log.log_index.ok_or("log without index")?.as_u64(),
+                    tx_hash: log.transaction_hash.ok_or("log without tx hash")?,
+                    event,
+                });
+            }
+            self.store.add_events(&scope, &events, to + 1)?;
+            debug!(from, to, events = events.len(), "indexed blocks");
+
+            count += events.len();
+            from = to + 1;
+        }
+
+        if count > 0 {
+            info!(count, next_block = from, "indexed IBC events");
+        }
+        Ok(count)
+    }
+
+    /// Records the chain id of a new client (the chain id of a client never changes)
+    async fn map_client(&self, client_id: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(client_state) = self.contracts.client_state(client_id).await? {
+            self.store
+                .set_client_chain(&self.scope(), client_id, &client_state.chain_id)?;
+        }
+        Ok(())
+    }
+
+    /// Indexed events from `from_block` on, in chain order
+    pub fn events(&self, from_block: u64) -> Result<Vec<IndexedEvent>, Box<dyn Error>> {
+        self.store.events(&self.scope(), from_block)
+    }
+
+    /// Indexed clients, oldest first
+    pub fn client_ids(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self
+            .events(0)?
+            .into_iter()
+            .filter_map(|indexed| match indexed.event {
+                IbcEvent::GeneratedClientIdentifier { client_id } => Some(client_id),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Chain id of the client, `None` if it is not a tendermint client seen by the indexer
+    pub fn client_chain(&self, client_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        self.store.client_chain(&self.scope(), client_id)
+    }
+
+    /// Indexed clients of the chain, oldest first
+    pub fn clients_of_chain(&self, chain_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut clients = Vec::new();
+        for client_id in self.client_ids()? {
+            if self.client_chain(&client_id)?.as_deref() == Some(chain_id) {
+                clients.push(client_id);
+            }
+        }
+        Ok(clients)
+    }
+}
@@ -0,0 +1,246 @@
+use ethabi::Token;
+use prost::Message;
+use serde_json::{json, Value};
+use std::error::Error;
+use web3::signing::keccak256;
+
+use crate::{
+    proto::{
+        self,
+        tendermint::light::{
+            BlockIdFlag, ClientState, ConsensusState, SignedHeader, TmHeader, ValidatorSet,
+        },
+    },
+    query,
+};
+
+fn validator_set_json(validator_set: &Option<ValidatorSet>) -> Value {
+    match validator_set {
+        Some(validator_set) => json!({
+            "validators": validator_set.validators.len(),
+            "total_voting_power": validator_set.total_voting_power,
+            "bytes": validator_set.encoded_len(),
+        }),
+        None => Value::Null,
+    }
+}
+
+fn signed_header_json(signed_header: &SignedHeader) -> Value {
+    let header = signed_header.header.as_ref();
+    let commit = signed_header.commit.as_ref();
+
+    let signatures = commit
+        .map(|commit| commit.signatures.as_slice())
+        .unwrap_or_default();
+    let count = |flag: BlockIdFlag| {
+        signatures
+            .iter()
+            .filter(|sig| sig.block_id_flag == flag as i32)
+            .count()
+    };
+
+    json!({
+        "chain_id": header.map(|header| header.chain_id.clone()),
+        "height": header.map(|header| header.height),
+        "time": header.and_then(|header| header.time.as_ref()).map(|time| time.seconds),
+        "signatures": {
+            "total": signatures.len(),
+            "commit": count(BlockIdFlag::Commit),
+            "nil": count(BlockIdFlag::Nil),
+            "absent": count(BlockIdFlag::Absent),
+        },
+        "bytes": {
+            "header": header.map(|header| header.encoded_len()),
+            "commit": commit.map(|commit| commit.encoded_len()),
+        },
+    })
+}
+
+fn tm_header_json(tm_header: &TmHeader) -> Value {
+    let height = tm_header
+        .signed_header
+        .as_ref()
+        .and_then(|signed_header| signed_header.header.as_ref())
+        .map(|header| header.height);
+
+    json!({
+        "height": height,
+        "trusted_height": tm_header.trusted_height,
+        "adjacent": height == Some(tm_header.trusted_height + 1),
+        "signed_header": tm_header.signed_header.as_ref().map(signed_header_json),
+        "validator_set": validator_set_json(&tm_header.validator_set),
+        "trusted_validators": validator_set_json(&tm_header.trusted_validators),
+        "bytes": tm_header.encoded_len(),
+    })
+}
+
+/// 4 byte function selector (the function signature without the outputs)
+fn selector(function: &ethabi::Function) -> [u8; 4] {
+    let inputs: Vec<String> = function
+        .inputs
+        .iter()
+        .map(|param| param.kind.to_string())
+        .collect();
+    let hash = keccak256(format!("{}({})", function.name, inputs.join(",")).as_bytes());
+
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn bytes_arg(token: &Token) -> Result<&[u8], Box<dyn Error>> {
+    match token {
+        Token::Bytes(bytes) => Ok(bytes),
+        token => Err(format!("expected bytes, got {:?}", token).into()),
+    }
+}
+
+/// `uint64` argument (the `Display` of `Token::Uint` is hex)
+fn u64_arg(token: &Token) -> Result<u64, Box<dyn Error>> {
+    match token {
+        Token::Uint(n) if n.bits() <= 64 => Ok(n.low_u64()),
+        token => Err(format!("expected uint64, got {:?}", token).into()),
+    }
+}
+
+/// Decodes `createClient`/`updateClient` calldata of IBCHandler down to the protobuf messages
+pub fn inspect_input(abi: &ethabi::Contract, input: &[u8]) -> Result<Value, Box<dyn Error>> {
+    if input.len() < 4 {
+        return Err("calldata is too short to contain a function selector".into());
+    }
+
+    let function = abi
+        .functions()
+        .find(|function| selector(function) == input[..4])
+        .ok_or_else(|| format!("unknown function selector 0x{}", hex::encode(&input[..4])))?;
+
+    let msg = match function.decode_input(&input[4..])?.pop() {
+        Some(Token::Tuple(msg)) => msg,
+        _ => return Err(format!("{} is not an IBC client message", function.name).into()),
+    };
+
+    let report = match (function.name.as_str(), msg.as_slice()) {
+        // MsgCreateClient
+        ("createClient", [client_type, height, client_state_bytes, consensus_state_bytes]) => {
+            let client_state_bytes = bytes_arg(client_state_bytes)?;
+            let consensus_state_bytes = bytes_arg(consensus_state_bytes)?;
+            let client_state: ClientState =
+                proto::prost_deserialize_any(client_state_bytes, "/tendermint.types.ClientState")?;
+            let consensus_state: ConsensusState = proto::prost_deserialize_any(
+                consensus_state_bytes,
+                "/tendermint.types.ConsensusState",
+            )?;
+
+            json!({
+                "client_type": client_type.to_string(),
+                "height": u64_arg(height)?,
+                "client_state": query::client_state_json(&client_state),
+                "consensus_state": query::consensus_state_json(&consensus_state),
+                "bytes": {
+                    "client_state": client_state_bytes.len(),
+                    "consensus_state": consensus_state_bytes.len(),
+                },
+            })
+        }
+        // MsgUpdateClient
+        ("updateClient", [client_id, header]) => {
+            let header_bytes = bytes_arg(header)?;
+            let tm_header: TmHeader =
+                proto::prost_deserialize_any(header_bytes, "/tendermint.types.TmHeader")?;
+
+            json!({
+                "client_id": client_id.to_string(),
+                "header": tm_header_json(&tm_header),
+                "bytes": {
+                    "header": header_bytes.len(),
+                },
+            })
+        }
+        (name, _) => return Err(format!("{} is not supported", name).into()),
+    };
+
+    Ok(json!({
+        "function": function.name,
+        "calldata_bytes": input.len(),
+        "msg": report,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bindings::ibc_handler,
+        encoder::{self, ClientParams},
+        header,
+    };
+    use std::path::{Path, PathBuf};
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../data")
+    }
+
+    #[test]
+    fn decodes_create_client() {
+        let tm_header = header::read_light_block(&fixtures(), 28).unwrap();
+        let light_header = tm_header
+            .signed_header
+            .as_ref()
+            .and_then(|signed_header| signed_header.header.as_ref())
+            .unwrap();
+        let msg = encoder::create_client_msg(
+            &encoder::client_state(light_header, &ClientParams::default()),
+            &encoder::consensus_state(light_header),
+        )
+        .unwrap();
+        let input = ibc_handler::create_client_call(msg);
+
+        let report = inspect_input(ibc_handler::abi(), &input).unwrap();
+        assert_eq!(report["function"], "createClient");
+        assert_eq!(report["calldata_bytes"], input.len());
+        assert_eq!(report["msg"]["client_type"], encoder::CLIENT_TYPE);
+        assert_eq!(report["msg"]["height"], 28);
+        assert_eq!(report["msg"]["client_state"]["latest_height"], 28);
+        assert_eq!(report["msg"]["client_state"]["frozen"], false);
+        assert_eq!(
+            report["msg"]["client_state"]["chain_id"],
+            light_header.chain_id
+        );
+    }
+
+    #[test]
+    fn decodes_update_client() {
+        let tm_header = header::read_light_block(&fixtures(), 30).unwrap();
+        let trusted_validators = header::read_validator_set(&fixtures(), 29).unwrap();
+        let update = encoder::update_header(&tm_header, 28, Some(trusted_validators.clone()));
+        let input = ibc_handler::update_client_call(
+            encoder::update_client_msg("07-tendermint-0", &update).unwrap(),
+        );
+
+        let report = inspect_input(ibc_handler::abi(), &input).unwrap();
+        assert_eq!(report["function"], "updateClient");
+        let msg = &report["msg"];
+        assert_eq!(msg["client_id"], "07-tendermint-0");
+        assert_eq!(msg["header"]["height"], 30);
+        assert_eq!(msg["header"]["trusted_height"], 28);
+        assert_eq!(msg["header"]["adjacent"], false);
+        assert_eq!(
+            msg["header"]["trusted_validators"]["validators"],
+            trusted_validators.validators.len()
+        );
+
+        let signatures = &msg["header"]["signed_header"]["signatures"];
+        let commit = tm_header.signed_header.unwrap().commit.unwrap();
+        assert_eq!(signatures["total"], commit.signatures.len());
+        assert_eq!(
+            signatures["commit"].as_u64().unwrap()
+                + signatures["nil"].as_u64().unwrap()
+                + signatures["absent"].as_u64().unwrap(),
+            commit.signatures.len() as u64
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_selectors() {
+        assert!(inspect_input(ibc_handler::abi(), &[0xde, 0xad, 0xbe, 0xef]).is_err());
+        assert!(inspect_input(ibc_handler::abi(), &[0x01]).is_err());
+    }
+}
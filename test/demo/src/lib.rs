@@ -1 +1,39 @@
+//! Relayer of tendermint headers to the IBC light client deployed on Celo.
+//!
+//! - [`header::HeaderSource`] fetches headers from a tendermint RPC node
+//! - [`encoder`] builds the client/consensus states and the IBC client messages
+//! - [`contracts::IbcContracts`] talks to IBCHandler/IBCHost on Celo
+//! - [`evm::EmbeddedChain`] runs the contracts in process to measure gas without a node
+//! - [`relay::Relayer`] drives client creation, updates and refreshes through a
+//!   [`txmgr::TxManager`]
+
+pub mod bindings;
+pub mod celo;
+pub mod clear;
+pub mod commitment;
 pub mod consts;
+pub mod contracts;
+pub mod delay;
+pub mod encoder;
+pub mod evm;
+pub mod gas;
+pub mod grpc;
+pub mod header;
+pub mod indexer;
+pub mod inspect;
+pub mod metrics;
+pub mod mock_rpc;
+pub mod planner;
+pub mod proof;
+pub mod proto;
+pub mod query;
+pub mod refresh;
+pub mod relay;
+pub mod scenario;
+pub mod signer;
+pub mod status;
+pub mod store;
+pub mod synth;
+pub mod txmgr;
+pub mod types;
+pub mod util;
@@ -0,0 +1,26 @@
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// Installs the global subscriber.
+///
+/// `filter` is either a plain level (`info`, `debug`, ..) or a full filter directive such as
+/// `info,client::txmgr=debug`. With `json` set, every event is printed as a single JSON object
+/// carrying its fields and the fields of the enclosing spans (client_id, height, ..).
+pub fn init(filter: &str, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let builder = fmt()
+        .with_env_filter(EnvFilter::try_new(filter)?)
+        .with_target(false);
+
+    if json {
+        builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .try_init()
+            .map_err(|e| e as Box<dyn std::error::Error>)
+    } else {
+        builder
+            .try_init()
+            .map_err(|e| e as Box<dyn std::error::Error>)
+    }
+}
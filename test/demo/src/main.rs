@@ -1,339 +1,38 @@
-mod consts;
-mod eth;
-mod proto;
-mod types;
-mod util;
+mod cli;
+mod logging;
 
 extern crate clap;
-use clap::{App, Arg};
-
-use tokio::time::{sleep, Duration};
-use web3::{contract::Options, signing::Key, types::U256};
-
-use ethabi::Token;
-use ibc;
-use std::{error::Error, fs::File, io::Write};
-
-use proto::tendermint::light::{
-    ClientState, ConsensusState, Fraction, SignedHeader, TmHeader, ValidatorSet,
+use clap::{App, AppSettings, Arg, SubCommand};
+
+use std::{error::Error, sync::Arc};
+use tokio::time::Duration;
+use tracing::{error, info};
+use web3::types::U256;
+
+use client::{
+    celo,
+    contracts::IbcContracts,
+    encoder::ClientParams,
+    gas::GasConfig,
+    header::HeaderSource,
+    indexer::Indexer,
+    metrics::{self, MeteredTransport, Metrics},
+    refresh::RefreshPolicy,
+    relay::{self, RelayOptions, Relayer, UpdateStrategy},
+    signer::{LocalSigner, RemoteSigner, Signer},
+    store::Store,
+    txmgr::{TxManager, TxManagerConfig},
+    types, util,
 };
-use tendermint_rpc::{Client, HttpClient};
-
-use consts::{IBC_HANDLER_ADDRESS, IBC_HOST_ADDRESS, TENDERMINT_LIGHT_CLIENT_ADDRESS};
-
-async fn recv_data_httpclient(
-    height: i64,
-    client: &mut HttpClient,
-    save_header: bool,
-) -> Result<TmHeader, Box<dyn Error>> {
-    let vs = fetch_validator_set(client, height, save_header).await?;
-    let sh = fetch_signed_header(client, height, save_header).await?;
-
-    Ok(types::to_light_block(&sh, &vs))
-}
-
-async fn fetch_validator_set(
-    client: &mut tendermint_rpc::HttpClient,
-    height: i64,
-    save_header: bool,
-) -> Result<ValidatorSet, Box<dyn Error>> {
-    let validator_set_future = client
-        .validators(
-            tendermint::block::Height::from(height as u32),
-            tendermint_rpc::Paging::All,
-        )
-        .await?;
-
-    let vs = types::to_validator_set(&validator_set_future.validators);
-
-    if save_header {
-        let path = format!("../data/header.{}.validator_set.json", height);
-        let mut output = File::create(path)?;
-        let data = serde_json::to_vec_pretty(&vs)?;
-        output.write_all(&data)?;
-    }
-
-    Ok(vs)
-}
-
-async fn fetch_signed_header(
-    client: &mut tendermint_rpc::HttpClient,
-    height: i64,
-    save_header: bool,
-) -> Result<SignedHeader, Box<dyn Error>> {
-    let commit_future = client
-        .commit(tendermint::block::Height::from(height as u32))
-        .await?;
-
-    let sh = types::to_signed_header(&commit_future.signed_header);
-
-    if save_header {
-        let path = format!("../data/header.{}.signed_header.json", height);
-        let mut output = File::create(path)?;
-        let data = serde_json::to_vec_pretty(&sh)?;
-        output.write_all(&data)?;
-    }
-
-    Ok(sh)
-}
 
-async fn handle_header<'a, T: web3::Transport>(
-    client: &'a mut tendermint_rpc::HttpClient,
-    transport: &'a T,
-    trusted_tm_header: Option<TmHeader>,
-    tm_header: TmHeader,
-    cnt: u64,
-    non_adjecent_test: bool,
-    gas: u64,
-    celo_usd_price: f64,
-    celo_gas_price: f64,
-    celo_private_key_path: &str,
-    client_id: Option<&str>,
-) -> Result<TmHeader, Box<dyn Error>> {
-    let trusted_height = match trusted_tm_header.as_ref() {
-        Some(trusted_header) => {
-            trusted_header
-                .signed_header
-                .as_ref()
-                .unwrap()
-                .header
-                .as_ref()
-                .unwrap()
-                .height
+/// Logs the error and terminates the process
+fn exit_on_error<T>(result: Result<T, Box<dyn Error>>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            error!(error = %e, "failed");
+            std::process::exit(1)
         }
-        None => 0,
-    };
-
-    let signed_header = tm_header.signed_header.as_ref().unwrap();
-    let header = signed_header.header.as_ref().unwrap();
-
-    println!(
-        "\n[0][header] height = {:?} cnt = {:?} trusted_height = {:?}",
-        header.height, cnt, trusted_height
-    );
-
-    let mut options = Options::default();
-    options.gas = Some(U256::from(gas as i64));
-
-    let key = util::get_celo_private_key(&celo_private_key_path)?;
-    println!(
-        "[0] Celo account address: {:?}",
-        web3::signing::SecretKeyRef::new(&key).address()
-    );
-
-    // test
-    let handler_contract = eth::load_contract(
-        &transport,
-        "../../build/contracts/IBCHandler.json",
-        IBC_HANDLER_ADDRESS,
-    )?;
-    let host_contract = eth::load_contract(
-        &transport,
-        "../../build/contracts/IBCHost.json",
-        IBC_HOST_ADDRESS,
-    )?;
-    let _tendermint_contract = eth::load_contract(
-        &transport,
-        "../../build/contracts/TendermintLightClient.json",
-        TENDERMINT_LIGHT_CLIENT_ADDRESS,
-    );
-
-    // create client
-    if cnt == 0 {
-        let register_client_response = handler_contract.signed_call_with_confirmations(
-            "registerClient",
-            (
-                "07-tendermint".to_string(),
-                types::to_addr(TENDERMINT_LIGHT_CLIENT_ADDRESS.to_string()),
-            ),
-            options.clone(),
-            1,
-            web3::signing::SecretKeyRef::new(&key),
-        );
-
-        let register_client_reciept: web3::types::TransactionReceipt =
-            register_client_response.await?;
-
-        match register_client_reciept.status {
-            Some(status) => {
-                if status == web3::types::U64([1 as u64]) {
-                    println!(
-                        "[1][register-client][] New client: {} registered",
-                        "07-tendermint"
-                    );
-                } else {
-                    println!(
-                        "[1][register-client][] Warning client: {} already registered",
-                        "07-tendermint"
-                    );
-                }
-                util::calculate_and_display_fee(
-                    "[1][register-client][]",
-                    "".to_string(),
-                    &transport,
-                    &register_client_reciept,
-                    celo_usd_price,
-                    celo_gas_price,
-                )
-                .await;
-            }
-            None => panic!("unkown outcome - cannot determine if client is registered already?"),
-        };
-
-        let client_state = ClientState {
-            chain_id: header.chain_id.to_owned(),
-            trust_level: Some(Fraction {
-                numerator: 1,
-                denominator: 3,
-            }),
-            trusting_period: Some(types::to_duration(100000000000, 0)),
-            unbonding_period: Some(types::to_duration(100000000000, 0)),
-            max_clock_drift: Some(types::to_duration(100000000000, 0)),
-            frozen_height: 0,
-            latest_height: header.height,
-            allow_update_after_expiry: true,
-            allow_update_after_misbehaviour: true,
-        };
-
-        let consensus_state = ConsensusState {
-            root: Some(proto::tendermint::light::MerkleRoot {
-                hash: header.app_hash.to_owned(),
-            }),
-            timestamp: header.time.to_owned(),
-            next_validators_hash: header.next_validators_hash.to_owned(),
-        };
-
-        let consensus_state_bytes =
-            proto::prost_serialize_any(&consensus_state, "/tendermint.types.ConsensusState")?;
-
-        let client_state_bytes =
-            proto::prost_serialize_any(&client_state, "/tendermint.types.ClientState")?;
-
-        // MsgCreateClient
-        let tok = ethabi::Token::Tuple(vec![
-            Token::String("07-tendermint".to_string()),
-            Token::Uint(U256::from(header.height)),
-            Token::Bytes(client_state_bytes),
-            Token::Bytes(consensus_state_bytes),
-        ]);
-
-        let create_client_result = handler_contract.signed_call_with_confirmations(
-            "createClient",
-            tok,
-            options.clone(),
-            1,
-            web3::signing::SecretKeyRef::new(&key),
-        );
-        let create_client_reciept: web3::types::TransactionReceipt = create_client_result.await?;
-        match create_client_reciept.status {
-            Some(status) => {
-                if status == web3::types::U64([1 as u64]) {
-                    println!(
-                        "[2][create-client][] new client instance: {} registered",
-                        "07-tendermint"
-                    );
-                } else {
-                    println!(
-                        "[2][create-client][] failed to create new client instance: {}",
-                        "07-tendermint"
-                    );
-                }
-                util::calculate_and_display_fee(
-                    "[2][create-client]",
-                    "".to_string(),
-                    &transport,
-                    &create_client_reciept,
-                    celo_usd_price,
-                    celo_gas_price,
-                )
-                .await;
-            }
-            None => panic!("unkown outcome - dunno "),
-        };
-
-        Ok(tm_header)
-    } else if cnt == 1 && non_adjecent_test {
-        Ok(trusted_tm_header.unwrap())
-    } else {
-        let client_id = match client_id {
-            Some(id) => id.to_string(),
-            None => eth::get_client_ids(&transport, &host_contract)
-                .await?
-                .last()
-                .unwrap()
-                .to_string(),
-        };
-
-        // TODO: proofs
-        //const IBC_QUERY_PATH: &str = "store/ibc/key";
-        //let path = tendermint::abci::Path::from_str(IBC_QUERY_PATH).unwrap();
-        //let _client_id = ibc::ics24_host::identifier::ClientId::new(ibc::ics02_client::client_type::ClientType::Tendermint, next_client_seq - 1).unwrap();
-        //let client_state_path = ibc::ics24_host::Path::ClientState(_client_id.clone());
-        //println!("CLIENT: {:?}", _client_id);
-        //println!("CLIENT STATE PATH: {:?}", client_state_path.clone().to_string());
-
-        //let abci_response = client
-        //.abci_query(Some(path), client_state_path.into_bytes(), Some(tendermint::block::Height::from(trusted_height as u32)), true)
-        //.await.unwrap();
-
-        // sending trusted validators is required only for non-adjecent test,
-        // because tm_header.validator_set.hash() == consensusState.next_validators_hash (adjecent case)
-        let trusted_validator_set = match non_adjecent_test {
-            true => fetch_validator_set(client, trusted_height + 1, false).await?,
-            false => ValidatorSet::default(),
-        };
-
-        let tm_header = TmHeader {
-            signed_header: Some(signed_header.to_owned()),
-            validator_set: tm_header.validator_set.to_owned(),
-            trusted_height: trusted_height,
-            trusted_validators: Some(trusted_validator_set),
-        };
-
-        let serialized_header =
-            proto::prost_serialize_any(&tm_header, "/tendermint.types.TmHeader")?;
-
-        // MsgUpdateClient
-        let tok = ethabi::Token::Tuple(vec![
-            Token::String(client_id.clone()),
-            Token::Bytes(serialized_header),
-        ]);
-        let update_client_result = handler_contract.signed_call_with_confirmations(
-            "updateClient",
-            tok,
-            options.clone(),
-            1,
-            web3::signing::SecretKeyRef::new(&key),
-        );
-        let update_client_reciept: web3::types::TransactionReceipt = update_client_result.await?;
-
-        match update_client_reciept.status {
-            Some(status) => {
-                if status == web3::types::U64([1 as u64]) {
-                    println!(
-                        "[3][update-client][{}] updated client tx: {:?}",
-                        client_id, update_client_reciept.transaction_hash
-                    );
-                } else {
-                    println!(
-                        "[3][update-client][{}] failed to update client tx: {:?}",
-                        client_id, update_client_reciept.transaction_hash
-                    );
-                }
-                util::calculate_and_display_fee(
-                    "[3][update-client]",
-                    client_id,
-                    &transport,
-                    &update_client_reciept,
-                    celo_usd_price,
-                    celo_gas_price,
-                )
-                .await;
-            }
-            None => panic!("unkown outcome - dunno "),
-        };
-
-        Ok(tm_header)
     }
 }
 
@@ -353,7 +52,7 @@ async fn main() -> web3::Result<()> {
 			.value_name("NUM")
 			.default_value("0")
 			.required(true)
-			.help("Celo gas price in wei (see: https://stats.celo.org/)")
+			.help("Celo gas price in wei (see: https://stats.celo.org/), 0 means the price is taken from the node")
 			.takes_value(true))
 		.arg(Arg::with_name("celo-usd-price")
 			.long("celo-usd-price")
@@ -372,9 +71,14 @@ async fn main() -> web3::Result<()> {
 		.arg(Arg::with_name("gas")
 			.long("gas")
 			.value_name("GAS")
-			.default_value("20000000")
-			.required(true)
-			.help("Maximum tx gas")
+			.required(false)
+			.help("Fixed tx gas limit, disables eth_estimateGas")
+			.takes_value(true))
+		.arg(Arg::with_name("gas-margin-percent")
+			.long("gas-margin-percent")
+			.value_name("PERCENT")
+			.default_value("20")
+			.help("Safety margin added to the eth_estimateGas result")
 			.takes_value(true))
 		.arg(Arg::with_name("tendermint-url")
 			.long("tendermint-url")
@@ -383,6 +87,12 @@ async fn main() -> web3::Result<()> {
 			.required(true)
 			.help("Tendermint RPC endpoint")
 			.takes_value(true))
+		.arg(Arg::with_name("cosmos-grpc-url")
+			.long("cosmos-grpc-url")
+			.value_name("URL")
+			.default_value("http://localhost:9090")
+			.help("gRPC endpoint of the Cosmos app, for the IBC queries")
+			.takes_value(true))
 		.arg(Arg::with_name("celo-private-key")
 			.long("celo-private-key")
 			.value_name("URL")
@@ -390,11 +100,129 @@ async fn main() -> web3::Result<()> {
 			.required(true)
 			.help("Celo secp256k1 private key")
 			.takes_value(true))
+		.arg(Arg::with_name("celo-signer-url")
+			.long("celo-signer-url")
+			.value_name("URL")
+			.required(false)
+			.requires("celo-signer-address")
+			.help("Remote signer JSON-RPC endpoint (eth_signTransaction), used instead of the local private key")
+			.takes_value(true))
+		.arg(Arg::with_name("celo-signer-address")
+			.long("celo-signer-address")
+			.value_name("ADDRESS")
+			.required(false)
+			.help("Celo account address the remote signer signs for")
+			.takes_value(true))
+		.arg(Arg::with_name("fee-currency")
+			.long("fee-currency")
+			.value_name("TOKEN")
+			.required(false)
+			.help("Pay tx fees in a stable token instead of CELO: cusd, ceur, creal or token address")
+			.takes_value(true))
+		.arg(Arg::with_name("fee-currency-usd-price")
+			.long("fee-currency-usd-price")
+			.value_name("NUM")
+			.default_value("1")
+			.help("USD price of the fee currency token (used instead of --celo-usd-price)")
+			.takes_value(true))
+		.arg(Arg::with_name("gateway-fee-recipient")
+			.long("gateway-fee-recipient")
+			.value_name("ADDRESS")
+			.required(false)
+			.help("Full node operator receiving the gateway fee")
+			.takes_value(true))
+		.arg(Arg::with_name("gateway-fee")
+			.long("gateway-fee")
+			.value_name("NUM")
+			.default_value("0")
+			.help("Gateway fee (in the fee currency, wei)")
+			.takes_value(true))
+		.arg(Arg::with_name("confirmations")
+			.long("confirmations")
+			.value_name("NUM")
+			.default_value("1")
+			.help("Number of blocks a tx must be buried under to be considered final")
+			.takes_value(true))
+		.arg(Arg::with_name("tx-replace-timeout")
+			.long("tx-replace-timeout")
+			.value_name("SECONDS")
+			.default_value("60")
+			.help("Time after which a pending tx is replaced with a higher gas price")
+			.takes_value(true))
+		.arg(Arg::with_name("tx-confirm-timeout")
+			.long("tx-confirm-timeout")
+			.value_name("SECONDS")
+			.default_value("1800")
+			.help("Time after which the relayer gives up waiting for a tx (e.g. dropped from the pool)")
+			.takes_value(true))
+		.arg(Arg::with_name("gas-bump-percent")
+			.long("gas-bump-percent")
+			.value_name("PERCENT")
+			.default_value("20")
+			.help("Gas price increase applied when replacing a stuck tx")
+			.takes_value(true))
+		.arg(Arg::with_name("max-gas-price")
+			.long("max-gas-price")
+			.value_name("WEI")
+			.required(false)
+			.help("Upper bound for the bumped gas price")
+			.takes_value(true))
+		.arg(Arg::with_name("max-in-flight")
+			.long("max-in-flight")
+			.value_name("NUM")
+			.default_value("1")
+			.help("Maximum number of unconfirmed updateClient txs")
+			.takes_value(true))
 		.arg(Arg::with_name("non-adjecent-mode")
 			.long("non-adjecent-mode")
 			.short("n")
 			.help("If present, the program skips 2nd header to verify that non-adjecent mode works")
+			.conflicts_with("update-strategy")
 			.takes_value(false))
+		.arg(Arg::with_name("update-strategy")
+			.long("update-strategy")
+			.value_name("STRATEGY")
+			.possible_values(&["adjacent", "auto"])
+			.default_value("adjacent")
+			.help("adjacent: update header by header, auto: pick the cheapest mix of adjacent and non-adjacent updates reaching the last header")
+			.takes_value(true))
+		.arg(Arg::with_name("metrics-addr")
+			.long("metrics-addr")
+			.value_name("ADDR")
+			.required(false)
+			.help("If present, Prometheus metrics are served at http://ADDR/metrics (e.g. 0.0.0.0:9100)")
+			.takes_value(true))
+		.arg(Arg::with_name("log-level")
+			.long("log-level")
+			.value_name("FILTER")
+			.default_value("info")
+			.help("Log level (error, warn, info, debug, trace) or filter directive, e.g. info,client::txmgr=debug")
+			.takes_value(true))
+		.arg(Arg::with_name("log-format")
+			.long("log-format")
+			.value_name("FORMAT")
+			.possible_values(&["text", "json"])
+			.default_value("text")
+			.help("Log output format")
+			.takes_value(true))
+		.arg(Arg::with_name("trusting-period")
+			.long("trusting-period")
+			.value_name("SECONDS")
+			.default_value("100000000000")
+			.help("Trusting period of the created client")
+			.takes_value(true))
+		.arg(Arg::with_name("unbonding-period")
+			.long("unbonding-period")
+			.value_name("SECONDS")
+			.default_value("100000000000")
+			.help("Unbonding period of the created client")
+			.takes_value(true))
+		.arg(Arg::with_name("max-clock-drift")
+			.long("max-clock-drift")
+			.value_name("SECONDS")
+			.default_value("100000000000")
+			.help("Max clock drift of the created client")
+			.takes_value(true))
 		.arg(Arg::with_name("save")
 			.long("save")
 			.short("s")
@@ -412,21 +240,380 @@ async fn main() -> web3::Result<()> {
 			.required(false)
 			.help("Start form given block height")
 			.takes_value(true))
+		.arg(Arg::with_name("state-db")
+			.long("state-db")
+			.value_name("DIR")
+			.help("Database recording the submitted txs, their cost and the trusted heights; relaying resumes with its latest client instead of creating a new one")
+			.takes_value(true))
+		.arg(Arg::with_name("index-start-block")
+			.long("index-start-block")
+			.value_name("BLOCK")
+			.default_value("0")
+			.help("Celo block the IBC event indexer starts at (e.g. the deployment block of the contracts), until --state-db holds its cursor")
+			.takes_value(true))
+		.arg(Arg::with_name("index-block-range")
+			.long("index-block-range")
+			.value_name("BLOCKS")
+			.default_value("5000")
+			.help("Celo blocks per eth_getLogs request of the IBC event indexer")
+			.takes_value(true))
+		.subcommand(SubCommand::with_name("relay")
+			.about("Creates a client with the first header (unless --client-id or --state-db name one to resume) and updates it with the following ones; the default without a subcommand"))
+		.subcommand(SubCommand::with_name("register-client")
+			.about("Registers the tendermint light client for the 07-tendermint client type, unless a light client is registered already"))
+		.subcommand(SubCommand::with_name("create-client")
+			.about("Creates a client trusting the header, unless a client of the chain already trusts it")
+			.arg(Arg::with_name("height")
+				.long("height")
+				.value_name("HEIGHT")
+				.help("Height of the header, the latest one if not given")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("update-client")
+			.about("Updates the client to the header, unless the client already has a consensus state at its height")
+			.arg(Arg::with_name("client-id")
+				.long("client-id")
+				.value_name("CLIENT_ID")
+				.required(true)
+				.help("Client to update")
+				.takes_value(true))
+			.arg(Arg::with_name("height")
+				.long("height")
+				.value_name("HEIGHT")
+				.required(true)
+				.help("Height of the header")
+				.takes_value(true))
+			.arg(Arg::with_name("trusted-height")
+				.long("trusted-height")
+				.value_name("HEIGHT")
+				.help("Height of the consensus state the update is verified against, the latest height of the client if not given")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("clear-packets")
+			.about("Compares the channel on Celo with its counterparty on the Cosmos chain and relays the packets and acknowledgements Celo has not received, in sequence order . Unsupported for Cosmos SDK chains: their proofs are keyed by the ICS-24 path, not by the IBCHost storage slot the light client verifies, which the printed plan reports (use --dry-run)")
+			.arg(Arg::with_name("port-id")
+				.long("port")
+				.value_name("PORT_ID")
+				.required(true)
+				.help("Port of the channel on Celo")
+				.takes_value(true))
+			.arg(Arg::with_name("channel-id")
+				.long("channel")
+				.value_name("CHANNEL_ID")
+				.required(true)
+				.help("Channel on Celo")
+				.takes_value(true))
+			.arg(Arg::with_name("dry-run")
+				.long("dry-run")
+				.help("Only prints what is outstanding on the channel")))
+		.subcommand(SubCommand::with_name("refresh")
+			.about("Updates the client whenever its latest consensus state gets close to expiry, even without packets")
+			.arg(Arg::with_name("threshold")
+				.long("threshold")
+				.value_name("FRACTION")
+				.default_value("2/3")
+				.help("Part of the trusting period (e.g. 2/3 or 0.66) after which the client is refreshed")
+				.takes_value(true))
+			.arg(Arg::with_name("check-interval")
+				.long("check-interval")
+				.value_name("SECONDS")
+				.default_value("60")
+				.help("Maximum time between two checks of the client state")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("evm-gas")
+			.about("Runs createClient/updateClient on saved headers in an in-process EVM and reports the gas used")
+			.arg(Arg::with_name("artifacts")
+				.long("artifacts")
+				.value_name("DIR")
+				.default_value("../../build/contracts")
+				.help("Truffle build directory with the compiled contracts")
+				.takes_value(true))
+			.arg(Arg::with_name("fixtures")
+				.long("fixtures")
+				.value_name("DIR")
+				.default_value("../data")
+				.help("Directory with the headers saved by --save")
+				.takes_value(true))
+			.arg(Arg::with_name("heights")
+				.long("heights")
+				.value_name("HEIGHTS")
+				.default_value("8619996,8619997,8619998")
+				.use_delimiter(true)
+				.help("Headers to ingest in order, the client is created with the first one")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("scenario")
+			.about("Runs light client scenarios (header sequences with expected outcomes) and reports every step")
+			.arg(Arg::with_name("files")
+				.value_name("FILE")
+				.required(true)
+				.multiple(true)
+				.help("YAML or JSON scenario files"))
+			.arg(Arg::with_name("evm")
+				.long("evm")
+				.help("Runs the scenarios in an in-process EVM instead of the Celo node, with time advancing only on sleep steps"))
+			.arg(Arg::with_name("artifacts")
+				.long("artifacts")
+				.value_name("DIR")
+				.default_value("../../build/contracts")
+				.help("Truffle build directory with the compiled contracts, for --evm")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("synth")
+			.about("Generates a tendermint chain with signed commits and saves its headers like --save does")
+			.arg(Arg::with_name("out")
+				.long("out")
+				.value_name("DIR")
+				.required(true)
+				.help("Directory the headers are written to")
+				.takes_value(true))
+			.arg(Arg::with_name("blocks")
+				.long("blocks")
+				.value_name("COUNT")
+				.default_value("3")
+				.help("Number of consecutive blocks to generate")
+				.takes_value(true))
+			.arg(Arg::with_name("validators")
+				.long("validators")
+				.value_name("COUNT")
+				.default_value("4")
+				.help("Size of the validator set")
+				.takes_value(true))
+			.arg(Arg::with_name("power")
+				.long("power")
+				.value_name("DISTRIBUTION")
+				.default_value("equal")
+				.possible_values(&["equal", "linear", "zipf"])
+				.help("Voting power distribution over the validators")
+				.takes_value(true))
+			.arg(Arg::with_name("absent")
+				.long("absent")
+				.value_name("FRACTION")
+				.default_value("0")
+				.help("Part of the validators absent from every commit")
+				.takes_value(true))
+			.arg(Arg::with_name("nil")
+				.long("nil")
+				.value_name("FRACTION")
+				.default_value("0")
+				.help("Part of the validators voting nil in every commit")
+				.takes_value(true))
+			.arg(Arg::with_name("churn")
+				.long("churn")
+				.value_name("COUNT")
+				.default_value("0")
+				.help("Validators replaced by new ones at every height")
+				.takes_value(true))
+			.arg(Arg::with_name("chain-id")
+				.long("chain-id")
+				.value_name("CHAIN_ID")
+				.default_value("synthetic")
+				.takes_value(true))
+			.arg(Arg::with_name("start-height")
+				.long("start-height")
+				.value_name("HEIGHT")
+				.default_value("1")
+				.takes_value(true))
+			.arg(Arg::with_name("seed")
+				.long("seed")
+				.value_name("SEED")
+				.default_value("0")
+				.help("Seed of the validator keys and vote selection")
+				.takes_value(true))
+			.arg(Arg::with_name("fork")
+				.long("fork")
+				.value_name("N")
+				.default_value("0")
+				.help("Application state variant, chains generated with another fork (and the same seed) conflict at every height")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("mock-rpc")
+			.about("Serves recorded headers over the tendermint JSON-RPC, in place of a node")
+			.arg(Arg::with_name("fixtures")
+				.long("fixtures")
+				.value_name("DIR")
+				.default_value("../data")
+				.help("Directory with the headers saved by --save")
+				.takes_value(true))
+			.arg(Arg::with_name("listen")
+				.long("listen")
+				.value_name("ADDR")
+				.default_value("127.0.0.1:26657")
+				.help("Address the RPC listens on")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("index")
+			.about("Indexes the IBC events emitted on Celo into --state-db and prints the clients with their chain ids and the events")
+			.arg(Arg::with_name("since")
+				.long("since")
+				.value_name("BLOCK")
+				.default_value("0")
+				.help("Only prints the events from this block on")
+				.takes_value(true))
+			.arg(Arg::with_name("check-packets")
+				.long("check-packets")
+				.help("Checks every sent packet against the commitment stored on Celo: pending, cleared (acknowledged or timed out) or mismatch")))
+		.subcommand(SubCommand::with_name("history")
+			.about("Prints the clients, submitted txs (with their cost) and trusted heights recorded in --state-db")
+			.arg(Arg::with_name("client-id")
+				.value_name("CLIENT_ID")
+				.help("Only the txs and trusted heights of this client")
+				.index(1)))
+		.subcommand(SubCommand::with_name("inspect-tx")
+			.about("Decodes a createClient/updateClient tx down to the tendermint header")
+			.arg(Arg::with_name("tx-hash")
+				.value_name("TX_HASH")
+				.required(true)
+				.index(1)))
+		.subcommand(SubCommand::with_name("status")
+			.about("Reports whether the client is Active, Expired or Frozen and the time left until expiry")
+			.arg(Arg::with_name("client-id")
+				.value_name("CLIENT_ID")
+				.required(true)
+				.index(1)))
+		.subcommand(SubCommand::with_name("cosmos-query")
+			.about("Queries the IBC state of the Cosmos chain over gRPC")
+			.setting(AppSettings::SubcommandRequiredElseHelp)
+			.subcommand(SubCommand::with_name("client-states")
+				.about("Prints the states of every client"))
+			.subcommand(SubCommand::with_name("client-state")
+				.about("Prints the latest client state")
+				.arg(Arg::with_name("client-id")
+					.value_name("CLIENT_ID")
+					.required(true)
+					.index(1)))
+			.subcommand(SubCommand::with_name("connections")
+				.about("Prints every connection end"))
+			.subcommand(SubCommand::with_name("connection")
+				.about("Prints the connection end")
+				.arg(Arg::with_name("connection-id")
+					.value_name("CONNECTION_ID")
+					.required(true)
+					.index(1)))
+			.subcommand(SubCommand::with_name("channels")
+				.about("Prints every channel end"))
+			.subcommand(SubCommand::with_name("channel")
+				.about("Prints the channel end")
+				.arg(Arg::with_name("port-id")
+					.value_name("PORT_ID")
+					.required(true)
+					.index(1))
+				.arg(Arg::with_name("channel-id")
+					.value_name("CHANNEL_ID")
+					.required(true)
+					.index(2)))
+			.subcommand(SubCommand::with_name("packet-commitments")
+				.about("Prints the sequences of the packets sent on the channel and not acknowledged nor timed out yet")
+				.arg(Arg::with_name("port-id")
+					.value_name("PORT_ID")
+					.required(true)
+					.index(1))
+				.arg(Arg::with_name("channel-id")
+					.value_name("CHANNEL_ID")
+					.required(true)
+					.index(2)))
+			.subcommand(SubCommand::with_name("unreceived-packets")
+				.about("Prints those of the sequences of packets sent to the channel the chain has not received")
+				.arg(Arg::with_name("port-id")
+					.value_name("PORT_ID")
+					.required(true)
+					.index(1))
+				.arg(Arg::with_name("channel-id")
+					.value_name("CHANNEL_ID")
+					.required(true)
+					.index(2))
+				.arg(Arg::with_name("sequences")
+					.value_name("SEQUENCE")
+					.required(true)
+					.multiple(true)
+					.index(3)))
+			.subcommand(SubCommand::with_name("unreceived-acks")
+				.about("Prints those of the sequences of packets sent on the channel whose acknowledgement the chain has not received")
+				.arg(Arg::with_name("port-id")
+					.value_name("PORT_ID")
+					.required(true)
+					.index(1))
+				.arg(Arg::with_name("channel-id")
+					.value_name("CHANNEL_ID")
+					.required(true)
+					.index(2))
+				.arg(Arg::with_name("sequences")
+					.value_name("SEQUENCE")
+					.required(true)
+					.multiple(true)
+					.index(3))))
+		.subcommand(SubCommand::with_name("celo-proof")
+			.about("Proves IBCHost commitments with eth_getProof, for the counterparty to verify")
+			.arg(Arg::with_name("paths")
+				.value_name("PATH")
+				.required(true)
+				.multiple(true)
+				.help("ICS-24 paths, e.g. connections/connection-0 or commitments/ports/transfer/channels/channel-0/sequences/1"))
+			.arg(Arg::with_name("block")
+				.long("block")
+				.value_name("NUMBER")
+				.help("Celo block the proofs are for, the latest if not given")
+				.takes_value(true)))
+		.subcommand(SubCommand::with_name("query")
+			.about("Queries the IBC state stored on Celo")
+			.setting(AppSettings::SubcommandRequiredElseHelp)
+			.subcommand(SubCommand::with_name("client-state")
+				.about("Prints the latest client state")
+				.arg(Arg::with_name("client-id")
+					.value_name("CLIENT_ID")
+					.required(true)
+					.index(1)))
+			.subcommand(SubCommand::with_name("consensus-state")
+				.about("Prints the consensus state stored for the height")
+				.arg(Arg::with_name("client-id")
+					.value_name("CLIENT_ID")
+					.required(true)
+					.index(1))
+				.arg(Arg::with_name("height")
+					.value_name("HEIGHT")
+					.required(true)
+					.index(2))))
 		.get_matches();
 
+    logging::init(
+        matches.value_of("log-level").unwrap(),
+        matches.value_of("log-format").unwrap() == "json",
+    )
+    .unwrap();
+
     let max_headers = matches
         .value_of("max-headers")
         .unwrap()
         .parse::<u64>()
         .unwrap();
-    let non_adjecent_test = matches.occurrences_of("non-adjecent-mode") > 0;
+    let skip_second_header = matches.occurrences_of("non-adjecent-mode") > 0;
     let save_header = matches.occurrences_of("save") > 0;
+    let update_strategy = matches
+        .value_of("update-strategy")
+        .unwrap()
+        .parse::<UpdateStrategy>()
+        .unwrap();
+    let metrics_addr = matches
+        .value_of("metrics-addr")
+        .map(|addr| addr.parse::<std::net::SocketAddr>().unwrap());
     let tendermint_url = matches.value_of("tendermint-url").unwrap();
+    let cosmos_grpc_url = matches.value_of("cosmos-grpc-url").unwrap();
     let celo_private_key_path = matches.value_of("celo-private-key").unwrap();
     let celo_url = matches.value_of("celo-url").unwrap();
+    let celo_signer_url = matches.value_of("celo-signer-url");
+    let celo_signer_address = matches.value_of("celo-signer-address");
     let client_id = matches.value_of("client-id");
-    let from_height = matches.value_of("from-height");
-    let gas = matches.value_of("gas").unwrap().parse::<u64>().unwrap();
+    let from_height = matches
+        .value_of("from-height")
+        .map(|height| height.parse::<u64>().unwrap());
+    let state_db = matches.value_of("state-db");
+    let index_start_block = matches
+        .value_of("index-start-block")
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
+    let index_block_range = matches
+        .value_of("index-block-range")
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
+    let gas = matches
+        .value_of("gas")
+        .map(|gas| U256::from_dec_str(gas).unwrap());
     let celo_gas_price = matches
         .value_of("celo-gas-price")
         .unwrap()
@@ -437,74 +624,258 @@ async fn main() -> web3::Result<()> {
         .unwrap()
         .parse::<f64>()
         .unwrap();
+    let seconds =
+        |name: &str| Duration::from_secs(matches.value_of(name).unwrap().parse::<u64>().unwrap());
+    let trusting_period = seconds("trusting-period");
+    let unbonding_period = seconds("unbonding-period");
+    let max_clock_drift = seconds("max-clock-drift");
+    let client_params = ClientParams {
+        trusting_period,
+        unbonding_period,
+        max_clock_drift,
+    };
+
+    if let Some(matches) = matches.subcommand_matches("evm-gas") {
+        exit_on_error(cli::evm_gas(&client_params, matches));
+        return Ok(());
+    }
+    if let Some(matches) = matches
+        .subcommand_matches("scenario")
+        .filter(|matches| matches.is_present("evm"))
+    {
+        exit_on_error(cli::scenario_evm(&client_params, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("synth") {
+        exit_on_error(cli::synth(matches));
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("history") {
+        exit_on_error(cli::history(state_db, matches));
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("cosmos-query") {
+        exit_on_error(cli::cosmos_query(cosmos_grpc_url, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("mock-rpc") {
+        exit_on_error(cli::mock_rpc(matches).await);
+        return Ok(());
+    }
 
     // Setup eth client
-    let transport = web3::transports::Http::new(celo_url).unwrap();
-    let mut client = tendermint_rpc::HttpClient::new(tendermint_url).unwrap();
+    let metrics = Arc::new(Metrics::new().unwrap());
+    let transport = MeteredTransport::new(
+        web3::transports::Http::new(celo_url).unwrap(),
+        "celo",
+        metrics.clone(),
+    );
 
-    let mut cnt: u64 = 0;
-    let last_height: u64 = match from_height {
-        Some(height) => client.block(tendermint::block::Height::from(
-            height.parse::<u64>().unwrap() as u32,
-        )),
-        None => client.latest_block(),
+    if let Some(matches) = matches.subcommand_matches("query") {
+        exit_on_error(cli::query(&transport, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("status") {
+        exit_on_error(cli::status(&transport, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("index") {
+        let store = state_db.map(|path| exit_on_error(Store::open(path)));
+        let indexer = exit_on_error(Indexer::new(&transport, store.as_ref()))
+            .with_start_block(index_start_block)
+            .with_block_range(index_block_range);
+        exit_on_error(cli::index(&indexer, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("celo-proof") {
+        exit_on_error(cli::celo_proof(&transport, matches).await);
+        return Ok(());
     }
-    .await
-    .unwrap()
-    .block
-    .header
-    .height
-    .into();
+    if let Some(matches) = matches.subcommand_matches("inspect-tx") {
+        exit_on_error(cli::inspect_tx(&transport, matches).await);
+        return Ok(());
+    }
+
+    let fee_currency = match matches.value_of("fee-currency") {
+        Some(currency) => Some(celo::resolve_fee_currency(&transport, currency).await?),
+        None => None,
+    };
+    let fee_usd_price = match fee_currency {
+        Some(_) => matches
+            .value_of("fee-currency-usd-price")
+            .unwrap()
+            .parse::<f64>()
+            .unwrap(),
+        None => celo_usd_price,
+    };
+    let tx_config = TxManagerConfig {
+        confirmations: matches
+            .value_of("confirmations")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        replace_after: Duration::from_secs(
+            matches
+                .value_of("tx-replace-timeout")
+                .unwrap()
+                .parse::<u64>()
+                .unwrap(),
+        ),
+        confirm_timeout: Duration::from_secs(
+            matches
+                .value_of("tx-confirm-timeout")
+                .unwrap()
+                .parse::<u64>()
+                .unwrap(),
+        ),
+        gas_bump_percent: matches
+            .value_of("gas-bump-percent")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap(),
+        max_gas_price: matches
+            .value_of("max-gas-price")
+            .map(|price| U256::from_dec_str(price).unwrap()),
+        fee_currency,
+        gateway_fee_recipient: matches
+            .value_of("gateway-fee-recipient")
+            .map(|address| types::to_addr(address.to_string())),
+        gateway_fee: U256::from_dec_str(matches.value_of("gateway-fee").unwrap()).unwrap(),
+        gas: GasConfig {
+            margin_percent: matches
+                .value_of("gas-margin-percent")
+                .unwrap()
+                .parse::<u64>()
+                .unwrap(),
+            gas_limit: gas,
+            gas_price: match celo_gas_price as u128 {
+                0 => None,
+                price => Some(U256::from(price)),
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let max_in_flight = matches
+        .value_of("max-in-flight")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
 
-    let mut header: Option<TmHeader> = None;
-    for h in last_height..last_height + max_headers {
-        let mut block: Option<tendermint::block::Block> = None;
+    let mut source = HeaderSource::new(tendermint_rpc::HttpClient::new(tendermint_url).unwrap());
+    if save_header {
+        source = source.with_save_dir("../data");
+    }
 
-        // try 10 times to get next header
-        // NOTE: we could use websocket client subscription, but public node providers doesn't seem
-        // to expose the websocket endpoint, so this is more universal approach
-        for _ in 1..10 {
-            let r = client
-                .block(tendermint::block::Height::from(h as u32))
-                .await;
+    let signer: Box<dyn Signer> = match celo_signer_url {
+        Some(url) => Box::new(RemoteSigner::new(
+            web3::transports::Http::new(url)?,
+            types::to_addr(celo_signer_address.unwrap().to_string()),
+        )),
+        None => Box::new(LocalSigner::new(
+            util::get_celo_private_key(celo_private_key_path).unwrap(),
+        )),
+    };
 
-            if r.is_err() {
-                sleep(Duration::from_secs(2)).await;
-            } else {
-                block = Some(r.unwrap().block);
+    let store = state_db.map(|path| exit_on_error(Store::open(path)));
+    let resumed = match matches.subcommand_name() {
+        None | Some("relay") => exit_on_error(
+            relay::resume_point(&IbcContracts::new(&transport), store.as_ref(), client_id).await,
+        ),
+        _ => None,
+    };
+    if let Some((client_id, height)) = &resumed {
+        info!(client_id = %client_id, height, "resuming");
+    }
+
+    let opts = RelayOptions {
+        fee_usd_price,
+        celo_gas_price,
+        client_id: resumed
+            .as_ref()
+            .map(|(client_id, _)| client_id.as_str())
+            .or(client_id),
+        client_params,
+        metrics: &metrics,
+        store: store.as_ref(),
+        update_strategy,
+        max_in_flight,
+        skip_second_header,
+    };
+
+    if let Some(addr) = metrics_addr {
+        info!(%addr, "serving metrics");
+        tokio::spawn(metrics::poll_head(source.clone(), metrics.clone()));
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(metrics, addr).await {
+                error!(error = %e, "metrics server failed");
             }
-        }
+        });
+    }
 
-        if block.is_none() {
-            break;
+    let txmgr = TxManager::new(transport.clone(), signer.as_ref(), tx_config).await?;
+    info!(address = ?txmgr.address(), "celo account");
+
+    // txs left behind by a previous run must land before new ones can be sent
+    let leftovers = txmgr.pending_nonces();
+    if !leftovers.is_empty() {
+        info!(
+            count = leftovers.len(),
+            "waiting for pending txs from a previous run"
+        );
+        for nonce in leftovers {
+            txmgr.wait(nonce).await?;
         }
+    }
 
-        let response = recv_data_httpclient(
-            block.unwrap().header.height.into(),
-            &mut client,
-            save_header,
-        )
-        .await;
+    if let Some(matches) = matches.subcommand_matches("scenario") {
+        exit_on_error(cli::scenario_devnet(&transport, &txmgr, &opts.client_params, matches).await);
+        return Ok(());
+    }
 
-        header = Some(
-            handle_header(
-                &mut client,
-                &transport,
-                header,
-                response.unwrap(),
-                cnt,
-                non_adjecent_test,
-                gas,
-                celo_usd_price,
-                celo_gas_price,
-                celo_private_key_path,
-                client_id,
-            )
-            .await
-            .unwrap(),
+    let indexer = exit_on_error(Indexer::new(&transport, store.as_ref()))
+        .with_start_block(index_start_block)
+        .with_block_range(index_block_range);
+    let relayer = Relayer::new(source, &transport, &txmgr, &indexer, opts);
+
+    if matches.subcommand_matches("register-client").is_some() {
+        exit_on_error(cli::register_client(&relayer).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("create-client") {
+        exit_on_error(cli::create_client(&relayer, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("update-client") {
+        exit_on_error(cli::update_client(&relayer, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("clear-packets") {
+        exit_on_error(cli::clear_packets(&relayer, cosmos_grpc_url, matches).await);
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("refresh") {
+        let policy = exit_on_error(
+            RefreshPolicy::parse(matches.value_of("threshold").unwrap()).map_err(Into::into),
         );
-        cnt += 1;
+        let check_interval = Duration::from_secs(
+            matches
+                .value_of("check-interval")
+                .unwrap()
+                .parse::<u64>()
+                .unwrap(),
+        );
+        exit_on_error(relayer.refresh(policy, check_interval).await);
+        return Ok(());
     }
 
+    let resumed_height = resumed.as_ref().map(|(_, height)| *height);
+    exit_on_error(
+        relayer
+            .relay(from_height, max_headers, resumed_height)
+            .await,
+    );
+
     Ok(())
 }
@@ -0,0 +1,432 @@
+use futures::{future::BoxFuture, FutureExt};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use prometheus::{
+    CounterVec, Encoder, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tendermint_rpc::Client;
+use tokio::time::sleep;
+use tracing::warn;
+use web3::{types::U256, RequestId, Transport};
+
+use crate::header::HeaderSource;
+
+/// Latest consensus state the relayer knows of for a client
+#[derive(Clone, Copy, Debug)]
+struct ClientInfo {
+    height: u64,
+    /// Unix time (seconds) of the header the client was updated to
+    time: f64,
+    trusting_period: Duration,
+}
+
+/// Relayer metrics, exposed in the Prometheus text format by `serve`
+pub struct Metrics {
+    registry: Registry,
+    head_height: IntGauge,
+    head_time: Gauge,
+    client_height: IntGaugeVec,
+    lag_blocks: IntGaugeVec,
+    lag_seconds: GaugeVec,
+    expiry_seconds: GaugeVec,
+    gas_used: IntCounterVec,
+    fees: CounterVec,
+    txs: IntCounterVec,
+    rpc_errors: IntCounterVec,
+    confirmation_seconds: HistogramVec,
+    head: Mutex<Option<(u64, f64)>>,
+    clients: Mutex<HashMap<String, ClientInfo>>,
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+impl Metrics {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("tendermint_sol".to_string()), None)?;
+
+        let head_height =
+            IntGauge::new("head_height", "Latest block height of the tendermint chain")?;
+        let head_time = Gauge::new(
+            "head_timestamp_seconds",
+            "Unix time of the latest tendermint block",
+        )?;
+        let client_height = IntGaugeVec::new(
+            Opts::new(
+                "client_latest_height",
+                "Latest height the light client on Celo was updated to",
+            ),
+            &["client_id"],
+        )?;
+        let lag_blocks = IntGaugeVec::new(
+            Opts::new(
+                "client_lag_blocks",
+                "Number of tendermint blocks the light client is behind",
+            ),
+            &["client_id"],
+        )?;
+        let lag_seconds = GaugeVec::new(
+            Opts::new(
+                "client_lag_seconds",
+                "Time between the latest tendermint block and the client's latest header",
+            ),
+            &["client_id"],
+        )?;
+        let expiry_seconds = GaugeVec::new(
+            Opts::new(
+                "client_trusting_period_remaining_seconds",
+                "Time until the client's latest consensus state leaves the trusting period",
+            ),
+            &["client_id"],
+        )?;
+        let gas_used = IntCounterVec::new(
+            Opts::new("tx_gas_used_total", "Gas used by the relayer txs"),
+            &["message"],
+        )?;
+        let fees = CounterVec::new(
+            Opts::new(
+                "tx_fees_total",
+                "Fees (incl. gateway fee) paid by the relayer, in units of the fee currency",
+            ),
+            &["message", "currency"],
+        )?;
+        let txs = IntCounterVec::new(
+            Opts::new("txs_total", "Confirmed relayer txs"),
+            &["message", "status"],
+        )?;
+        let rpc_errors = IntCounterVec::new(
+            Opts::new("rpc_errors_total", "Failed RPC requests"),
+            &["endpoint", "method"],
+        )?;
+        let confirmation_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "tx_confirmation_seconds",
+                "Time from the submission of a tx until its confirmation",
+            )
+            .buckets(vec![
+                1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+            ]),
+            &["message"],
+        )?;
+
+        registry.register(Box::new(head_height.clone()))?;
+        registry.register(Box::new(head_time.clone()))?;
+        registry.register(Box::new(client_height.clone()))?;
+        registry.register(Box::new(lag_blocks.clone()))?;
+        registry.register(Box::new(lag_seconds.clone()))?;
+        registry.register(Box::new(expiry_seconds.clone()))?;
+        registry.register(Box::new(gas_used.clone()))?;
+        registry.register(Box::new(fees.clone()))?;
+        registry.register(Box::new(txs.clone()))?;
+        registry.register(Box::new(rpc_errors.clone()))?;
+        registry.register(Box::new(confirmation_seconds.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            head_height,
+            head_time,
+            client_height,
+            lag_blocks,
+            lag_seconds,
+            expiry_seconds,
+            gas_used,
+            fees,
+            txs,
+            rpc_errors,
+            confirmation_seconds,
+            head: Mutex::new(None),
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Records the latest tendermint block (`time` is a unix timestamp in seconds)
+    pub fn set_head(&self, height: u64, time: f64) {
+        self.head_height.set(height as i64);
+        self.head_time.set(time);
+        *self.head.lock().unwrap() = Some((height, time));
+        self.refresh();
+    }
+
+    /// Records a confirmed client update to the header at `height` created at `time`
+    pub fn set_client(&self, client_id: &str, height: u64, time: f64, trusting_period: Duration) {
+        self.client_height
+            .with_label_values(&[client_id])
+            .set(height as i64);
+        self.clients.lock().unwrap().insert(
+            client_id.to_string(),
+            ClientInfo {
+                height,
+                time,
+                trusting_period,
+            },
+        );
+        self.refresh();
+    }
+
+    /// Records a confirmed tx of the given message type (`registerClient`, `updateClient`, ..)
+    pub fn observe_tx(
+        &self,
+        message: &str,
+        success: bool,
+        gas_used: U256,
+        fee: f64,
+        currency: &str,
+        confirmation: Duration,
+    ) {
+        let status = if success { "success" } else { "failure" };
+        self.txs.with_label_values(&[message, status]).inc();
+        self.gas_used
+            .with_label_values(&[message])
+            .inc_by(gas_used.low_u64());
+        self.fees
+            .with_label_values(&[message, currency])
+            .inc_by(fee);
+        self.confirmation_seconds
+            .with_label_values(&[message])
+            .observe(confirmation.as_secs_f64());
+    }
+
+    pub fn rpc_error(&self, endpoint: &str, method: &str) {
+        self.rpc_errors.with_label_values(&[endpoint, method]).inc();
+    }
+
+    /// Recomputes the lag and expiry gauges, which depend on the head and the wall clock
+    fn refresh(&self) {
+        let head = *self.head.lock().unwrap();
+        let now = unix_time();
+
+        for (client_id, client) in self.clients.lock().unwrap().iter() {
+            if let Some((head_height, head_time)) = head {
+                self.lag_blocks
+                    .with_label_values(&[client_id])
+                    .set(head_height.saturating_sub(client.height) as i64);
+                self.lag_seconds
+                    .with_label_values(&[client_id])
+                    .set((head_time - client.time).max(0.0));
+            }
+            self.expiry_seconds
+                .with_label_values(&[client_id])
+                .set(client.time + client.trusting_period.as_secs_f64() - now);
+        }
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        self.refresh();
+
+        let mut buffer = Vec::new();
+        let encoder = TextEncoder::new();
+        encoder
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics are valid");
+
+        String::from_utf8(buffer).expect("metrics are valid utf-8")
+    }
+}
+
+/// Serves `GET /metrics` until the process exits
+pub async fn serve(metrics: Arc<Metrics>, addr: SocketAddr) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let metrics = metrics.clone();
+                async move {
+                    let response = match (request.method(), request.uri().path()) {
+                        (&Method::GET, "/metrics") => Response::builder()
+                            .header("Content-Type", prometheus::TEXT_FORMAT)
+                            .body(Body::from(metrics.render())),
+                        _ => Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Body::empty()),
+                    };
+                    Ok::<_, Infallible>(response.unwrap())
+                }
+            }))
+        }
+    });
+
+    Server::bind(&addr).serve(make_service).await
+}
+
+/// Keeps the tendermint head metrics up to date
+pub async fn poll_head(source: HeaderSource, metrics: Arc<Metrics>) {
+    loop {
+        match source.client().latest_block().await {
+            Ok(response) => {
+                let header = response.block.header;
+                let time = header.time.0;
+                metrics.set_head(
+                    header.height.into(),
+                    time.timestamp() as f64 + time.timestamp_subsec_nanos() as f64 / 1e9,
+                );
+            }
+            Err(e) => {
+                warn!(error = %e, "failed to fetch the latest tendermint block");
+                metrics.rpc_error("tendermint", "block");
+            }
+        }
+
+        sleep(Duration::from_secs(5)).await;
+    }
+}
+
+/// Web3 transport counting failed requests per RPC method
+#[derive(Clone)]
+pub struct MeteredTransport<T> {
+    inner: T,
+    endpoint: &'static str,
+    metrics: Arc<Metrics>,
+}
+
+impl<T> MeteredTransport<T> {
+    pub fn new(inner: T, endpoint: &'static str, metrics: Arc<Metrics>) -> Self {
+        MeteredTransport {
+            inner,
+            endpoint,
+            metrics,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MeteredTransport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MeteredTransport")
+            .field("inner", &self.inner)
+            .field("endpoint", &self.endpoint)
+            .finish()
+    }
+}
+
+impl<T> Transport for MeteredTransport<T>
+where
+    T: Transport,
+    T::Out: Send + 'static,
+{
+    type Out = BoxFuture<'static, web3::Result<serde_json::Value>>;
+
+    fn prepare(
+        &self,
+        method: &str,
+        params: Vec<serde_json::Value>,
+    ) -> (RequestId, jsonrpc_core::Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: jsonrpc_core::Call) -> Self::Out {
+        let method = match &request {
+            jsonrpc_core::Call::MethodCall(call) => call.method.clone(),
+            jsonrpc_core::Call::Notification(notification) => notification.method.clone(),
+            jsonrpc_core::Call::Invalid { .. } => "invalid".to_string(),
+        };
+        let endpoint = self.endpoint;
+        let metrics = self.metrics.clone();
+
+        self.inner
+            .send(id, request)
+            .map(move |result: web3::Result<serde_json::Value>| {
+                if result.is_err() {
+                    metrics.rpc_error(endpoint, &method);
+                }
+                result
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value of the sample of `name` with the given `client_id` label
+    fn sample(rendered: &str, name: &str, client_id: &str) -> f64 {
+        let prefix = format!("{}{{client_id=\"{}\"}} ", name, client_id);
+        rendered
+            .lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .unwrap_or_else(|| panic!("no sample {} in\n{}", prefix, rendered))
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn renders_lag_and_expiry() {
+        let metrics = Metrics::new().unwrap();
+        // whole seconds, so that the differences are exact
+        let now = unix_time().floor();
+        let trusting_period = Duration::from_secs(3600);
+
+        metrics.set_client("07-tendermint-0", 28, now - 100.0, trusting_period);
+        metrics.set_head(30, now - 40.0);
+        let rendered = metrics.render();
+
+        assert!(rendered.contains("\ntendermint_sol_head_height 30\n"));
+        assert!(!rendered.contains("tendermint_sol_tendermint_"));
+        assert_eq!(
+            sample(
+                &rendered,
+                "tendermint_sol_client_latest_height",
+                "07-tendermint-0"
+            ),
+            28.0
+        );
+        assert_eq!(
+            sample(
+                &rendered,
+                "tendermint_sol_client_lag_blocks",
+                "07-tendermint-0"
+            ),
+            2.0
+        );
+        assert_eq!(
+            sample(
+                &rendered,
+                "tendermint_sol_client_lag_seconds",
+                "07-tendermint-0"
+            ),
+            60.0
+        );
+        // evaluated against the clock at rendering
+        let remaining = sample(
+            &rendered,
+            "tendermint_sol_client_trusting_period_remaining_seconds",
+            "07-tendermint-0",
+        );
+        assert!(remaining <= 3500.0 && remaining > 3490.0, "{}", remaining);
+
+        // a newer head before the client catches up
+        metrics.set_head(35, now - 10.0);
+        let rendered = metrics.render();
+        assert_eq!(
+            sample(
+                &rendered,
+                "tendermint_sol_client_lag_blocks",
+                "07-tendermint-0"
+            ),
+            7.0
+        );
+        assert_eq!(
+            sample(
+                &rendered,
+                "tendermint_sol_client_lag_seconds",
+                "07-tendermint-0"
+            ),
+            90.0
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Tendermint JSON-RPC server answering from recorded data instead of a node, so the
+//! `HttpClient` code paths can run without network access.
+//!
+//! Headers are read from the files `HeaderSource::with_save_dir` writes
+//! (`header.<height>.signed_header.json` and `header.<height>.validator_set.json`, e.g. the
+//! fixtures in `test/data`). `abci_query` answers from `abci_query.<path>.<hex data>.json`
+//! (the `response` object of the RPC, `/` of the path replaced by `.`) and with an empty value
+//! when there is no such file, like a node does for a missing key.
+//!
+//! Both the JSON-RPC (`POST /`) and the URI (`GET /commit?height=28`) forms are accepted.
+use hyper::{
+    body,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    convert::{Infallible, TryInto},
+    error::Error,
+    fs,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+};
+use tendermint::{
+    block::{self, signed_header::SignedHeader, Block, Commit},
+    evidence, validator,
+};
+use tendermint_rpc::endpoint::{abci_query, commit, status, validators};
+use tracing::{debug, info};
+
+use crate::header;
+
+/// Validators per page of `/validators` if the request does not say
+const DEFAULT_PER_PAGE: usize = 30;
+/// Largest page of `/validators`, as on tendermint nodes
+const MAX_PER_PAGE: usize = 100;
+
+/// Recorded headers, validator sets and ABCI query responses
+pub struct Fixtures {
+    dir: PathBuf,
+    /// Heights with both the signed header and the validator set recorded, ascending
+    heights: Vec<u64>,
+}
+
+impl Fixtures {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.into();
+
+        let mut heights = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let height = name
+                .strip_prefix("header.")
+                .and_then(|name| name.strip_suffix(".signed_header.json"))
+                .and_then(|height| height.parse::<u64>().ok());
+            if let Some(height) = height {
+                if dir
+                    .join(format!("header.{}.validator_set.json", height))
+                    .is_file()
+                {
+                    heights.push(height);
+                }
+            }
+        }
+        heights.sort_unstable();
+
+        if heights.is_empty() {
+            return Err(format!("no recorded headers in {}", dir.display()).into());
+        }
+
+        Ok(Fixtures { dir, heights })
+    }
+
+    /// Recorded heights, ascending
+    pub fn heights(&self) -> &[u64] {
+        &self.heights
+    }
+
+    /// Height of the request, the latest recorded one if none is given
+    fn height(&self, params: &Value) -> Result<u64, String> {
+        let latest = *self.heights.last().unwrap();
+
+        match params.get("height").and_then(Value::as_str) {
+            None | Some("") | Some("0") => Ok(latest),
+            Some(height) => {
+                let height = height
+                    .parse::<u64>()
+                    .map_err(|e| format!("invalid height {}: {}", height, e))?;
+                if self.heights.binary_search(&height).is_ok() {
+                    Ok(height)
+                } else if height > latest {
+                    Err(format!(
+                        "height {} must be less than or equal to the current blockchain height {}",
+                        height, latest
+                    ))
+                } else {
+                    Err(format!("height {} is not recorded", height))
+                }
+            }
+        }
+    }
+
+    fn signed_header(&self, height: u64) -> Result<SignedHeader, Box<dyn Error>> {
+        let signed_header = header::read_signed_header(&self.dir, height as i64)?;
+
+        Ok(crate::types::from_signed_header(&signed_header)?)
+    }
+
+    fn validators(&self, height: u64) -> Result<Vec<validator::Info>, Box<dyn Error>> {
+        let validator_set = header::read_validator_set(&self.dir, height as i64)?;
+
+        crate::types::from_validator_set(&validator_set)
+    }
+
+    fn commit(&self, params: &Value) -> Result<Value, Box<dyn Error>> {
+        let signed_header = self.signed_header(self.height(params)?)?;
+
+        Ok(serde_json::to_value(commit::Response {
+            signed_header,
+            canonical: true,
+        })?)
+    }
+
+    /// Block without transactions nor evidence, the last commit is the recorded commit of the
+    /// previous height or, if that height is not recorded, a commit without signatures
+    fn block(&self, params: &Value) -> Result<Value, Box<dyn Error>> {
+        let height = self.height(params)?;
+        let signed_header = self.signed_header(height)?;
+        let (header, commit) = (signed_header.header, signed_header.commit);
+
+        let last_commit = if height <= 1 {
+            None
+        } else if self.heights.binary_search(&(height - 1)).is_ok() {
+            Some(self.signed_header(height - 1)?.commit)
+        } else {
+            Some(Commit {
+                height: (height - 1).try_into()?,
+                round: block::Round::default(),
+                block_id: header.last_block_id.unwrap_or_default(),
+                signatures: Vec::new(),
+            })
+        };
+
+        Ok(json!({
+            "block_id": commit.block_id,
+            "block": Block::new(
+                header,
+                tendermint_proto::types::Data { txs: Vec::new() }.into(),
+                evidence::Data::new(Vec::new()),
+                last_commit,
+            )?,
+        }))
+    }
+
+    fn validators_page(&self, params: &Value) -> Result<Value, Box<dyn Error>> {
+        let height = self.height(params)?;
+        let validators = self.validators(height)?;
+
+        let number = |name: &str, default: usize| -> Result<usize, Box<dyn Error>> {
+            match params.get(name).and_then(Value::as_str) {
+                None | Some("") => Ok(default),
+                Some(value) => Ok(value.parse::<usize>()?),
+            }
+        };
+        let per_page = number("per_page", DEFAULT_PER_PAGE)?.clamp(1, MAX_PER_PAGE);
+        let page = number("page", 1)?.max(1);
+
+        let start = (page - 1) * per_page;
+        if start > 0 && start >= validators.len() {
+            return Err(format!("page {} is out of range", page).into());
+        }
+        let end = (start + per_page).min(validators.len());
+
+        Ok(serde_json::to_value(validators::Response::new(
+            height.try_into()?,
+            validators[start..end].to_vec(),
+            validators.len() as i32,
+        ))?)
+    }
+
+    /// Status of a node synced to the latest recorded height, the first validator of the set
+    /// stands for the node's own validator key
+    fn status(&self, listen_addr: SocketAddr) -> Result<Value, Box<dyn Error>> {
+        let height = *self.heights.last().unwrap();
+        let signed_header = self.signed_header(height)?;
+        let (header, commit) = (signed_header.header, signed_header.commit);
+        let validator_info = self
+            .validators(height)?
+            .into_iter()
+            .next()
+            .ok_or("empty validator set")?;
+
+        let node_info = serde_json::from_value(json!({
+            "protocol_version": {
+                "p2p": "8",
+                "block": header.version.block.to_string(),
+                "app": header.version.app.to_string(),
+            },
+            "id": "0000000000000000000000000000000000000000",
+            "listen_addr": format!("tcp://{}", listen_addr),
+            "network": header.chain_id,
+            "version": "0.34.0",
+            "channels": "40202122233038606100",
+            "moniker": "mock",
+            "other": {
+                "tx_index": "off",
+                "rpc_address": format!("tcp://{}", listen_addr),
+            },
+        }))?;
+
+        Ok(serde_json::to_value(status::Response {
+            node_info,
+            sync_info: status::SyncInfo {
+                latest_block_hash: commit.block_id.hash,
+                latest_app_hash: header.app_hash,
+                latest_block_height: header.height,
+                latest_block_time: header.time,
+                catching_up: false,
+            },
+            validator_info,
+        })?)
+    }
+
+    fn abci_query(&self, params: &Value) -> Result<Value, Box<dyn Error>> {
+        let data = params
+            .get("data")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .trim_start_matches("0x")
+            .to_lowercase();
+        let path = params
+            .get("path")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .replace('/', ".");
+        let recorded = self.dir.join(format!("abci_query{}.{}.json", path, data));
+
+        let response = if recorded.is_file() {
+            serde_json::from_str(&fs::read_to_string(&recorded)?)?
+        } else {
+            abci_query::AbciQuery {
+                key: hex::decode(&data)?,
+                height: self.height(params)?.try_into()?,
+                ..Default::default()
+            }
+        };
+
+        Ok(serde_json::to_value(abci_query::Response { response })?)
+    }
+
+    /// Result of the RPC method, error message if it fails
+    pub fn handle(
+        &self,
+        method: &str,
+        params: &Value,
+        listen_addr: SocketAddr,
+    ) -> Result<Value, String> {
+        let result = match method {
+            "block" => self.block(params),
+            "commit" => self.commit(params),
+            "validators" => self.validators_page(params),
+            "status" => self.status(listen_addr),
+            "abci_query" => self.abci_query(params),
+            _ => return Err(format!("method {} is not supported by the mock", method)),
+        };
+
+        result.map_err(|e| e.to_string())
+    }
+}
+
+/// Parameters of a URI request (`?height=28&path="/store"`), all kept as strings like
+/// tendermint-rpc sends them in JSON
+fn uri_params(query: Option<&str>) -> Value {
+    let mut params = Map::new();
+
+    for pair in query.unwrap_or_default().split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            params.insert(key.to_string(), json!(value.trim_matches('"')));
+        }
+    }
+
+    Value::Object(params)
+}
+
+fn rpc_response(id: Value, result: Result<Value, String>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32603, "message": "Internal error", "data": error },
+        }),
+    }
+}
+
+async fn handle_request(
+    fixtures: &Fixtures,
+    listen_addr: SocketAddr,
+    request: Request<Body>,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let (method, id, params) = match *request.method() {
+        Method::POST => {
+            let call: Value = serde_json::from_slice(&body::to_bytes(request.into_body()).await?)?;
+            (
+                call["method"].as_str().unwrap_or_default().to_string(),
+                call["id"].clone(),
+                call["params"].clone(),
+            )
+        }
+        _ => (
+            request.uri().path().trim_start_matches('/').to_string(),
+            json!(-1),
+            uri_params(request.uri().query()),
+        ),
+    };
+
+    debug!(%method, %params, "mock RPC request");
+    Ok(rpc_response(
+        id,
+        fixtures.handle(&method, &params, listen_addr),
+    ))
+}
+
+/// Serves the recorded data until the process exits
+pub async fn serve(fixtures: Arc<Fixtures>, addr: SocketAddr) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let fixtures = fixtures.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let fixtures = fixtures.clone();
+                async move {
+                    let body = match handle_request(&fixtures, addr, request).await {
+                        Ok(response) => response,
+                        Err(e) => json!({
+                            "jsonrpc": "2.0",
+                            "id": -1,
+                            "error": { "code": -32700, "message": "Parse error", "data": e.to_string() },
+                        }),
+                    };
+                    Ok::<_, Infallible>(
+                        Response::builder()
+                            .header("Content-Type", "application/json")
+                            .body(Body::from(body.to_string()))
+                            .unwrap(),
+                    )
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr).serve(make_service);
+    info!(addr = %server.local_addr(), "mock tendermint RPC listening");
+
+    server.await
+}
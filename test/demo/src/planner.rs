@@ -0,0 +1,312 @@
+use async_trait::async_trait;
+use std::{error::Error, fmt};
+use web3::types::U256;
+
+/// Gas estimates of candidate `updateClient` calls
+#[async_trait(?Send)]
+pub trait UpdateEstimator {
+    /// Gas needed to update the client to `height` using the consensus state at
+    /// `trusted_height`, `None` if such an update is not possible (the call reverts, e.g.
+    /// insufficient trust overlap, or does not fit into a block).
+    async fn estimate_update(
+        &mut self,
+        trusted_height: u64,
+        height: u64,
+    ) -> Result<Option<U256>, Box<dyn Error>>;
+}
+
+/// Single `updateClient` transaction of a plan
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub trusted_height: u64,
+    pub height: u64,
+    pub gas: U256,
+}
+
+impl Step {
+    /// Adjacent (sequential) updates don't need to carry the trusted validator set
+    pub fn is_adjacent(&self) -> bool {
+        self.height == self.trusted_height + 1
+    }
+}
+
+#[derive(Debug)]
+pub enum PlanError {
+    /// Nothing between the trusted and the target height can be verified within a block
+    NoFeasibleRoute {
+        trusted_height: u64,
+        target: u64,
+    },
+    Estimator(Box<dyn Error>),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::NoFeasibleRoute {
+                trusted_height,
+                target,
+            } => write!(
+                f,
+                "no update from trusted height {} towards {} fits under the block gas limit",
+                trusted_height, target
+            ),
+            PlanError::Estimator(e) => write!(f, "update estimation failed: {}", e),
+        }
+    }
+}
+
+impl Error for PlanError {}
+
+/// Chooses between adjacent (many cheaper txs) and non-adjacent (fewer txs carrying the
+/// trusted validator set) updates.
+///
+/// Only the next step is planned: every candidate is estimated with `eth_estimateGas`
+/// against the current on-chain state, which is only possible for updates trusting the
+/// latest consensus state. The remainder of the route is extrapolated from the same
+/// estimates, and the plan is refined once the step has been executed.
+pub struct Planner {
+    /// Upper bound of candidate heights evaluated per step (bisection depth)
+    pub max_candidates: usize,
+}
+
+impl Default for Planner {
+    fn default() -> Self {
+        Planner { max_candidates: 8 }
+    }
+}
+
+impl Planner {
+    /// Candidate heights: the target first, then bisecting towards the adjacent height
+    fn candidates(&self, trusted_height: u64, target: u64) -> Vec<u64> {
+        let mut candidates = Vec::new();
+        let mut height = target;
+
+        while height > trusted_height + 1 && candidates.len() + 1 < self.max_candidates {
+            candidates.push(height);
+            height = trusted_height + (height - trusted_height) / 2;
+        }
+        candidates.push(trusted_height + 1);
+
+        candidates
+    }
+
+    /// Returns the cheapest next step reaching (or approaching) `target`
+    pub async fn next_step<E: UpdateEstimator>(
+        &self,
+        estimator: &mut E,
+        trusted_height: u64,
+        target: u64,
+    ) -> Result<Step, PlanError> {
+        let mut estimates: Vec<Step> = Vec::new();
+        for height in self.candidates(trusted_height, target) {
+            let gas = estimator
+                .estimate_update(trusted_height, height)
+                .await
+                .map_err(PlanError::Estimator)?;
+
+            if let Some(gas) = gas {
+                estimates.push(Step {
+                    trusted_height,
+                    height,
+                    gas,
+                });
+            }
+        }
+
+        let adjacent_gas = estimates.iter().find(|s| s.is_adjacent()).map(|s| s.gas);
+        let skip_gas = estimates
+            .iter()
+            .filter(|s| !s.is_adjacent())
+            .map(|s| s.gas)
+            .min();
+
+        // cost of the remainder of the route after the step: either sequential updates or
+        // a skip which is assumed to cost about the same as the skips estimated above
+        let remainder = |height: u64| -> U256 {
+            let remaining = target - height;
+            if remaining == 0 {
+                return U256::zero();
+            }
+
+            let adjacent = adjacent_gas.map(|gas| gas * remaining);
+            let skip = skip_gas.map(|gas| match adjacent_gas {
+                // the last leg of a skipping route is no cheaper than an adjacent one
+                Some(adjacent) if remaining == 1 => std::cmp::min(gas, adjacent),
+                _ => gas,
+            });
+
+            match (adjacent, skip) {
+                (Some(a), Some(s)) => std::cmp::min(a, s),
+                (Some(a), None) => a,
+                (None, Some(s)) => s,
+                (None, None) => U256::MAX,
+            }
+        };
+
+        estimates
+            .into_iter()
+            .min_by(|a, b| {
+                let cost_a = a.gas.saturating_add(remainder(a.height));
+                let cost_b = b.gas.saturating_add(remainder(b.height));
+                // on a tie, the step getting further wins
+                cost_a.cmp(&cost_b).then(b.height.cmp(&a.height))
+            })
+            .ok_or(PlanError::NoFeasibleRoute {
+                trusted_height,
+                target,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Estimates from a gas table of `(trusted_height, height)`, recording the estimated
+    /// candidates
+    struct MockEstimator<F: Fn(u64, u64) -> Option<u64>> {
+        gas: F,
+        estimated: Vec<(u64, u64)>,
+    }
+
+    impl<F: Fn(u64, u64) -> Option<u64>> MockEstimator<F> {
+        fn new(gas: F) -> Self {
+            MockEstimator {
+                gas,
+                estimated: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl<F: Fn(u64, u64) -> Option<u64>> UpdateEstimator for MockEstimator<F> {
+        async fn estimate_update(
+            &mut self,
+            trusted_height: u64,
+            height: u64,
+        ) -> Result<Option<U256>, Box<dyn Error>> {
+            self.estimated.push((trusted_height, height));
+            Ok((self.gas)(trusted_height, height).map(U256::from))
+        }
+    }
+
+    struct FailingEstimator;
+
+    #[async_trait(?Send)]
+    impl UpdateEstimator for FailingEstimator {
+        async fn estimate_update(
+            &mut self,
+            _: u64,
+            _: u64,
+        ) -> Result<Option<U256>, Box<dyn Error>> {
+            Err("node unreachable".into())
+        }
+    }
+
+    fn step(trusted_height: u64, height: u64, gas: u64) -> Step {
+        Step {
+            trusted_height,
+            height,
+            gas: gas.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn only_adjacent_feasible() {
+        let mut estimator =
+            MockEstimator::new(
+                |trusted_height, height| match height == trusted_height + 1 {
+                    true => Some(300_000),
+                    false => None,
+                },
+            );
+
+        let step = Planner::default()
+            .next_step(&mut estimator, 10, 26)
+            .await
+            .unwrap();
+        assert_eq!(step, self::step(10, 11, 300_000));
+        assert!(step.is_adjacent());
+        assert_eq!(
+            estimator.estimated,
+            vec![(10, 26), (10, 18), (10, 14), (10, 12), (10, 11)]
+        );
+    }
+
+    #[tokio::test]
+    async fn skip_cheaper_than_adjacent_route() {
+        let mut estimator =
+            MockEstimator::new(
+                |trusted_height, height| match height == trusted_height + 1 {
+                    true => Some(300_000),
+                    false => Some(900_000),
+                },
+            );
+
+        // 900k for the skip against 300k times 16 headers
+        let step = Planner::default()
+            .next_step(&mut estimator, 10, 26)
+            .await
+            .unwrap();
+        assert_eq!(step, self::step(10, 26, 900_000));
+    }
+
+    #[tokio::test]
+    async fn adjacent_route_cheaper_than_skip() {
+        let mut estimator =
+            MockEstimator::new(
+                |trusted_height, height| match height == trusted_height + 1 {
+                    true => Some(300_000),
+                    false => Some(1_000_000),
+                },
+            );
+
+        // three adjacent updates cost 900k, less than a skip to the target
+        let step = Planner::default()
+            .next_step(&mut estimator, 10, 13)
+            .await
+            .unwrap();
+        assert!(step.is_adjacent());
+    }
+
+    #[tokio::test]
+    async fn ties_go_to_the_further_step() {
+        // a skip to 12 costs as much as the adjacent route through 11
+        let mut estimator =
+            MockEstimator::new(|trusted_height, height| Some(300_000 * (height - trusted_height)));
+
+        let step = Planner::default()
+            .next_step(&mut estimator, 10, 12)
+            .await
+            .unwrap();
+        assert_eq!(step, self::step(10, 12, 600_000));
+    }
+
+    #[tokio::test]
+    async fn no_feasible_candidate() {
+        let mut estimator = MockEstimator::new(|_, _| None);
+        let planner = Planner { max_candidates: 3 };
+
+        match planner.next_step(&mut estimator, 10, 1_000).await {
+            Err(PlanError::NoFeasibleRoute {
+                trusted_height: 10,
+                target: 1_000,
+            }) => {}
+            result => panic!("expected no feasible route, got {:?}", result),
+        }
+        // bounded by `max_candidates`, the adjacent height included
+        assert_eq!(estimator.estimated, vec![(10, 1_000), (10, 505), (10, 11)]);
+    }
+
+    #[tokio::test]
+    async fn estimator_errors() {
+        match Planner::default()
+            .next_step(&mut FailingEstimator, 10, 12)
+            .await
+        {
+            Err(PlanError::Estimator(e)) => assert_eq!(e.to_string(), "node unreachable"),
+            result => panic!("expected an estimator error, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,651 @@
+//! Proofs of the IBC state stored by IBCHost on Celo, for the counterparty to verify the
+//! handshake and packet messages relayed from Celo.
+//!
+//! IBCHost keeps a hash of every provable value in its `commitments` mapping (storage slot 0)
+//! under a key derived from the ICS-24 path (`IBCIdentifier.sol`), so proving a value amounts
+//! to proving one storage slot of IBCHost. [`StateProof`] carries, for a Celo block:
+//!
+//! - `state_root` of the block header, which the counterparty's Celo light client must trust
+//! - `account_proof`: the state trie nodes from `state_root` down to IBCHost's account, at the
+//!   path `keccak256(address)`; the leaf is `rlp([nonce, balance, storage_hash, code_hash])`
+//! - for every path, `proof`: the storage trie nodes from `storage_hash` down to the slot, at
+//!   the path `keccak256(slot)`; the leaf is `rlp(value)` with the leading zero bytes of the
+//!   value stripped, and a proof ending without a leaf shows the slot is empty (value zero)
+//!
+//! `slot` is `keccak256(key ++ uint256(0))` and `key` is `keccak256` of the packed prefix and
+//! path fields (see [`CommitmentPath::key`]). Trie nodes are RLP-encoded, hex in JSON, in the
+//! order of `eth_getProof`.
+use rlp::Rlp;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{error::Error, fmt, str::FromStr};
+use web3::{
+    signing::keccak256,
+    types::{Address, BlockId, BlockNumber, Bytes, H256, U256},
+    Transport, Web3,
+};
+
+/// Storage slot of the `commitments` mapping of IBCHost
+const COMMITMENT_SLOT: u64 = 0;
+
+/// Path of a value committed by IBCHost, written like ICS-24 paths
+/// (`commitments/ports/transfer/channels/channel-0/sequences/1`)
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommitmentPath {
+    ClientState {
+        client_id: String,
+    },
+    ConsensusState {
+        client_id: String,
+        height: u64,
+    },
+    Connection {
+        connection_id: String,
+    },
+    Channel {
+        port_id: String,
+        channel_id: String,
+    },
+    Packet {
+        port_id: String,
+        channel_id: String,
+        sequence: u64,
+    },
+    PacketAcknowledgement {
+        port_id: String,
+        channel_id: String,
+        sequence: u64,
+    },
+}
+
+impl CommitmentPath {
+    /// Key in the `commitments` mapping, `IBCIdentifier.*CommitmentKey`
+    pub fn key(&self) -> H256 {
+        // abi.encodePacked: uint8 prefix, raw string bytes, uint64 as 8 big-endian bytes
+        let mut packed = Vec::new();
+        let mut push = |parts: &[&[u8]]| {
+            for part in parts {
+                packed.extend_from_slice(part);
+            }
+        };
+        match self {
+            CommitmentPath::ClientState { client_id } => push(&[&[0], client_id.as_bytes()]),
+            CommitmentPath::ConsensusState { client_id, height } => {
+                push(&[&[1], client_id.as_bytes(), b"/", &height.to_be_bytes()])
+            }
+            CommitmentPath::Connection { connection_id } => push(&[&[2], connection_id.as_bytes()]),
+            CommitmentPath::Channel {
+                port_id,
+                channel_id,
+            } => push(&[&[3], port_id.as_bytes(), b"/", channel_id.as_bytes()]),
+            CommitmentPath::Packet {
+                port_id,
+                channel_id,
+                sequence,
+            } => push(&[
+                &[4],
+                port_id.as_bytes(),
+                b"/",
+                channel_id.as_bytes(),
+                b"/",
+                &sequence.to_be_bytes(),
+            ]),
+            CommitmentPath::PacketAcknowledgement {
+                port_id,
+                channel_id,
+                sequence,
+            } => push(&[
+                &[5],
+                port_id.as_bytes(),
+                b"/",
+                channel_id.as_bytes(),
+                b"/",
+                &sequence.to_be_bytes(),
+            ]),
+        }
+
+        H256(keccak256(&packed))
+    }
+
+    /// Storage slot of IBCHost holding the commitment, `IBCIdentifier.*CommitmentSlot`
+    pub fn slot(&self) -> H256 {
+        let mut packed = self.key().as_bytes().to_vec();
+        packed.extend_from_slice(&H256::from_low_u64_be(COMMITMENT_SLOT).0);
+
+        H256(keccak256(&packed))
+    }
+}
+
+impl fmt::Display for CommitmentPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommitmentPath::ClientState { client_id } => {
+                write!(f, "clients/{}/clientState", client_id)
+            }
+            CommitmentPath::ConsensusState { client_id, height } => {
+                write!(f, "clients/{}/consensusStates/{}", client_id, height)
+            }
+            CommitmentPath::Connection { connection_id } => {
+                write!(f, "connections/{}", connection_id)
+            }
+            CommitmentPath::Channel {
+                port_id,
+                channel_id,
+            } => write!(f, "channelEnds/ports/{}/channels/{}", port_id, channel_id),
+            CommitmentPath::Packet {
+                port_id,
+                channel_id,
+                sequence,
+            } => write!(
+                f,
+                "commitments/ports/{}/channels/{}/sequences/{}",
+                port_id, channel_id, sequence
+            ),
+            CommitmentPath::PacketAcknowledgement {
+                port_id,
+                channel_id,
+                sequence,
+            } => write!(
+                f,
+                "acks/ports/{}/channels/{}/sequences/{}",
+                port_id, channel_id, sequence
+            ),
+        }
+    }
+}
+
+impl FromStr for CommitmentPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |n: &str| {
+            n.parse::<u64>()
+                .map_err(|e| format!("invalid number {} in path {}: {}", n, s, e))
+        };
+        let parts: Vec<&str> = s.split('/').collect();
+
+        match parts.as_slice() {
+            ["clients", client_id, "clientState"] => Ok(CommitmentPath::ClientState {
+                client_id: client_id.to_string(),
+            }),
+            ["clients", client_id, "consensusStates", height] => {
+                Ok(CommitmentPath::ConsensusState {
+                    client_id: client_id.to_string(),
+                    height: number(height)?,
+                })
+            }
+            ["connections", connection_id] => Ok(CommitmentPath::Connection {
+                connection_id: connection_id.to_string(),
+            }),
+            ["channelEnds", "ports", port_id, "channels", channel_id] => {
+                Ok(CommitmentPath::Channel {
+                    port_id: port_id.to_string(),
+                    channel_id: channel_id.to_string(),
+                })
+            }
+            ["commitments", "ports", port_id, "channels", channel_id, "sequences", sequence] => {
+                Ok(CommitmentPath::Packet {
+                    port_id: port_id.to_string(),
+                    channel_id: channel_id.to_string(),
+                    sequence: number(sequence)?,
+                })
+            }
+            ["acks", "ports", port_id, "channels", channel_id, "sequences", sequence] => {
+                Ok(CommitmentPath::PacketAcknowledgement {
+                    port_id: port_id.to_string(),
+                    channel_id: channel_id.to_string(),
+                    sequence: number(sequence)?,
+                })
+            }
+            _ => Err(format!(
+                "unknown path {}, expected clients/<id>/clientState, \
+                 clients/<id>/consensusStates/<height>, connections/<id>, \
+                 channelEnds/ports/<port>/channels/<channel>, \
+                 commitments/ports/<port>/channels/<channel>/sequences/<sequence> or \
+                 acks/ports/<port>/channels/<channel>/sequences/<sequence>",
+                s
+            )),
+        }
+    }
+}
+
+/// Account of IBCHost in the state trie
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Account {
+    pub nonce: U256,
+    pub balance: U256,
+    pub storage_hash: H256,
+    pub code_hash: H256,
+}
+
+/// Commitment with the storage trie nodes proving it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StorageProof {
+    pub path: String,
+    /// Key in the `commitments` mapping
+    pub key: H256,
+    pub slot: H256,
+    /// Commitment stored in the slot, zero if there is none
+    pub value: H256,
+    pub proof: Vec<Bytes>,
+}
+
+/// Commitments of IBCHost proven against the state root of a Celo block (see the module doc)
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StateProof {
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub state_root: H256,
+    pub address: Address,
+    pub account: Account,
+    pub account_proof: Vec<Bytes>,
+    pub storage: Vec<StorageProof>,
+}
+
+/// `eth_getProof` response (EIP-1186)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcProof {
+    account_proof: Vec<Bytes>,
+    balance: U256,
+    code_hash: H256,
+    nonce: U256,
+    storage_hash: H256,
+    storage_proof: Vec<RpcStorageProof>,
+}
+
+#[derive(Deserialize)]
+struct RpcStorageProof {
+    value: U256,
+    proof: Vec<Bytes>,
+}
+
+/// Proves the commitments of `paths` stored by the contract at `address` at the given block
+/// (the latest if none), checking the proofs before returning them
+pub async fn prove<T: Transport>(
+    transport: &T,
+    address: Address,
+    paths: &[CommitmentPath],
+    block: Option<u64>,
+) -> Result<StateProof, Box<dyn Error>> {
+    let block_number = match block {
+        Some(number) => BlockNumber::Number(number.into()),
+        None => BlockNumber::Latest,
+    };
+    let block = Web3::new(transport)
+        .eth()
+        .block(BlockId::Number(block_number))
+        .await?
+        .ok_or("block not found")?;
+    let number = block.number.ok_or("block is pending")?;
+
+    let slots: Vec<H256> = paths.iter().map(CommitmentPath::slot).collect();
+    let response = transport
+        .execute(
+            "eth_getProof",
+            vec![json!(address), json!(slots), json!(number)],
+        )
+        .await?;
+    let state_proof = StateProof::from_rpc_proof(
+        number.as_u64(),
+        block.hash.ok_or("block is pending")?,
+        block.state_root,
+        address,
+        paths,
+        serde_json::from_value(response)?,
+    )?;
+    state_proof.verify()?;
+
+    Ok(state_proof)
+}
+
+impl StateProof {
+    /// Proof of the commitments of `paths` from the `eth_getProof` response for their slots, not
+    /// verified yet
+    fn from_rpc_proof(
+        block_number: u64,
+        block_hash: H256,
+        state_root: H256,
+        address: Address,
+        paths: &[CommitmentPath],
+        proof: RpcProof,
+    ) -> Result<Self, Box<dyn Error>> {
+        if proof.storage_proof.len() != paths.len() {
+            return Err(format!(
+                "eth_getProof returned {} storage proofs for {} slots",
+                proof.storage_proof.len(),
+                paths.len()
+            )
+            .into());
+        }
+
+        Ok(StateProof {
+            block_number,
+            block_hash,
+            state_root,
+            address,
+            account: Account {
+                nonce: proof.nonce,
+                balance: proof.balance,
+                storage_hash: proof.storage_hash,
+                code_hash: proof.code_hash,
+            },
+            account_proof: proof.account_proof,
+            storage: paths
+                .iter()
+                .zip(proof.storage_proof)
+                .map(|(path, storage)| {
+                    let mut value = H256::zero();
+                    storage.value.to_big_endian(value.as_bytes_mut());
+                    StorageProof {
+                        path: path.to_string(),
+                        key: path.key(),
+                        slot: path.slot(),
+                        value,
+                        proof: storage.proof,
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// Checks the proofs like the counterparty would: the account against the state root, and
+    /// every commitment against the storage hash of the account
+    pub fn verify(&self) -> Result<(), Box<dyn Error>> {
+        let leaf = verify_trie_proof(
+            self.state_root,
+            &keccak256(self.address.as_bytes()),
+            &self.account_proof,
+        )?
+        .ok_or("the account proof shows no account at the address")?;
+        let account = Rlp::new(&leaf);
+        let proven = Account {
+            nonce: account.val_at(0)?,
+            balance: account.val_at(1)?,
+            storage_hash: account.val_at(2)?,
+            code_hash: account.val_at(3)?,
+        };
+        if proven != self.account {
+            return Err(format!(
+                "proven account {:?} differs from the reported one {:?}",
+                proven, self.account
+            )
+            .into());
+        }
+
+        for storage in &self.storage {
+            let value = match verify_trie_proof(
+                self.account.storage_hash,
+                &keccak256(storage.slot.as_bytes()),
+                &storage.proof,
+            )? {
+                Some(leaf) => Rlp::new(&leaf).as_val::<U256>()?,
+                None => U256::zero(),
+            };
+            if value != U256::from_big_endian(storage.value.as_bytes()) {
+                return Err(format!(
+                    "proven value {:x} of {} differs from the reported one {:x}",
+                    value, storage.path, storage.value
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reference to a child node: its hash, the node itself if it is shorter than 32 bytes, or
+/// nothing
+enum Child<'a> {
+    Hash(H256),
+    Inline(Rlp<'a>),
+    Empty,
+}
+
+fn child(rlp: Rlp<'_>) -> Result<Child<'_>, Box<dyn Error>> {
+    if rlp.is_empty() {
+        Ok(Child::Empty)
+    } else if rlp.is_list() {
+        Ok(Child::Inline(rlp))
+    } else {
+        let hash = rlp.data()?;
+        if hash.len() != 32 {
+            return Err(format!("child reference of {} bytes", hash.len()).into());
+        }
+        Ok(Child::Hash(H256::from_slice(hash)))
+    }
+}
+
+/// Nibbles of a hex-prefix encoded path, and whether it ends in a leaf
+fn hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), Box<dyn Error>> {
+    let first = *encoded.first().ok_or("empty node path")?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(format!("invalid node path flag {}", flag).into());
+    }
+
+    let mut nibbles = Vec::new();
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0f);
+    }
+    for byte in &encoded[1..] {
+        nibbles.extend_from_slice(&[byte >> 4, byte & 0x0f]);
+    }
+
+    Ok((nibbles, flag >= 2))
+}
+
+/// Value stored under `key` in the Merkle Patricia trie of the given root, `None` if the proof
+/// shows there is none
+pub fn verify_trie_proof(
+    root: H256,
+    key: &[u8],
+    proof: &[Bytes],
+) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let nibbles: Vec<u8> = key
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect();
+    let mut path = nibbles.as_slice();
+    let mut nodes = proof.iter();
+    let mut expected = root;
+
+    loop {
+        let node = nodes.next().ok_or("proof ends before reaching the key")?;
+        if H256(keccak256(&node.0)) != expected {
+            return Err("proof node does not match the hash referencing it".into());
+        }
+
+        // walks the node and the nodes inlined in it, until a hash reference
+        let mut rlp = Rlp::new(&node.0);
+        let next = loop {
+            let reference = match rlp.item_count()? {
+                17 => match path.split_first() {
+                    None => {
+                        let value = rlp.at(16)?.data()?;
+                        return Ok(Some(value.to_vec()).filter(|value| !value.is_empty()));
+                    }
+                    Some((nibble, rest)) => {
+                        path = rest;
+                        rlp.at(*nibble as usize)?
+                    }
+                },
+                2 => {
+                    let (prefix, leaf) = hex_prefix(rlp.at(0)?.data()?)?;
+                    if leaf {
+                        return Ok(Some(rlp.at(1)?.data()?.to_vec()).filter(|_| path == prefix));
+                    }
+                    if !path.starts_with(&prefix) {
+                        return Ok(None);
+                    }
+                    path = &path[prefix.len()..];
+                    rlp.at(1)?
+                }
+                count => return Err(format!("trie node of {} items", count).into()),
+            };
+
+            match child(reference)? {
+                Child::Hash(hash) => break hash,
+                Child::Inline(inline) => rlp = inline,
+                Child::Empty => return Ok(None),
+            }
+        };
+        expected = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `eth_getProof` of a block whose state holds IBCHost with a few commitments and other
+    /// accounts, one of them sharing the first nibbles of IBCHost's path (an extension node)
+    fn fixture() -> StateProof {
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/state_proof.json")).unwrap();
+        let paths: Vec<CommitmentPath> = fixture["paths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|path| path.as_str().unwrap().parse().unwrap())
+            .collect();
+        let block = &fixture["block"];
+        let proof: RpcProof = serde_json::from_value(fixture["proof"].clone()).unwrap();
+
+        StateProof::from_rpc_proof(
+            serde_json::from_value::<U256>(block["number"].clone())
+                .unwrap()
+                .as_u64(),
+            serde_json::from_value(block["hash"].clone()).unwrap(),
+            serde_json::from_value(block["stateRoot"].clone()).unwrap(),
+            serde_json::from_value(fixture["proof"]["address"].clone()).unwrap(),
+            &paths,
+            proof,
+        )
+        .unwrap()
+    }
+
+    fn h256(hex: &str) -> H256 {
+        H256::from_slice(&hex::decode(hex).unwrap())
+    }
+
+    #[test]
+    fn verifies_get_proof_fixture() {
+        let proof = fixture();
+        proof.verify().unwrap();
+
+        let packet = &proof.storage[4];
+        assert_eq!(
+            packet.path,
+            "commitments/ports/transfer/channels/channel-0/sequences/1"
+        );
+        assert_eq!(
+            packet.value,
+            h256("956da0bab332d5fc70925995c0577da9c9666e3a4eff11d938063c9035acde39")
+        );
+        // no acknowledgement yet, proven by the nodes leading away from the slot
+        let ack = &proof.storage[5];
+        assert_eq!(
+            ack.path,
+            "acks/ports/transfer/channels/channel-0/sequences/1"
+        );
+        assert_eq!(ack.value, H256::zero());
+    }
+
+    #[test]
+    fn rejects_tampered_nodes() {
+        let mut proof = fixture();
+        let leaf = proof.storage[4].proof.last_mut().unwrap();
+        *leaf.0.last_mut().unwrap() ^= 1;
+        assert!(proof.verify().is_err());
+
+        let mut proof = fixture();
+        proof.account_proof[1].0[4] ^= 1;
+        assert!(proof.verify().is_err());
+
+        let mut proof = fixture();
+        proof.storage[0].value = proof.storage[1].value;
+        assert!(proof.verify().is_err());
+
+        let mut proof = fixture();
+        proof.account.storage_hash = H256::zero();
+        assert!(proof.verify().is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_keys() {
+        // the nodes proving one commitment do not prove another slot
+        let mut proof = fixture();
+        proof.storage[0].slot = proof.storage[1].slot;
+        assert!(proof.verify().is_err());
+
+        let proof = fixture();
+        let storage = &proof.storage[4];
+        let wrong_slot = proof.storage[3].slot;
+        assert_ne!(
+            verify_trie_proof(
+                proof.account.storage_hash,
+                &keccak256(wrong_slot.as_bytes()),
+                &storage.proof,
+            )
+            .ok()
+            .flatten(),
+            verify_trie_proof(
+                proof.account.storage_hash,
+                &keccak256(storage.slot.as_bytes()),
+                &storage.proof,
+            )
+            .unwrap()
+        );
+
+        let mut proof = fixture();
+        proof.address = Address::repeat_byte(0x01);
+        assert!(proof.verify().is_err());
+    }
+
+    /// Keys and slots of `IBCIdentifier.*CommitmentKey` and `*CommitmentSlot`, computed from
+    /// `keccak256(abi.encodePacked(...))` outside of this crate
+    #[test]
+    fn slots_follow_ibc_identifier() {
+        let vectors = [
+            (
+                "clients/07-tendermint-0/clientState",
+                "659388819c371aff328ba616399a320bb5933c53139a9ca12343f6097cc17dab",
+                "f997a34f734f1dde945f9d12651801e240aa2159934a9a323ad9814abc77e4b2",
+            ),
+            (
+                "clients/07-tendermint-0/consensusStates/28",
+                "efacf6e8c80900292a8d88a39b91a103131d39e2b886fa7063bff0e19094dfa5",
+                "d0d325500bb895b3557eae59e88c1e9e7c9d47c5b725c165a0028c0148e8c5bc",
+            ),
+            (
+                "connections/connection-0",
+                "064a97632504ecf5937e3e42ef10ea871b4067c4a62d07e05c427b54b87c4da0",
+                "e02a19def9a3294eb09c80091bc61b6278a795726b9aa018262bdd4216fec54a",
+            ),
+            (
+                "channelEnds/ports/transfer/channels/channel-0",
+                "ebc2fe483a9723ad437a53691b6da043052329beefe85bfc41a9d2816e5a879a",
+                "8f22928b99f1a7fd672f10173e4de769654ac732a01905a3b120ce8efa65799d",
+            ),
+            (
+                "commitments/ports/transfer/channels/channel-0/sequences/1",
+                "1c2cce2f4eea66bc7e3b370e96608a15d212f16adff25e22bb354a37a56b201a",
+                "ac2f64c19e956a615130a714e4e91c7d1798ac123bdb3dbf6f8db4d72f13f2b2",
+            ),
+            (
+                "acks/ports/transfer/channels/channel-0/sequences/1",
+                "ca973b1398f619919a6031d0735bee5fb7586067cc15a4fd2d34da0d44a56cfa",
+                "3af66fe269cad6c94a6c4211079f07a9f85c98f3af0dbb4125231cca099281ae",
+            ),
+        ];
+
+        for (path, key, slot) in vectors {
+            let commitment_path: CommitmentPath = path.parse().unwrap();
+            assert_eq!(commitment_path.to_string(), path);
+            assert_eq!(commitment_path.key(), h256(key), "key of {}", path);
+            assert_eq!(commitment_path.slot(), h256(slot), "slot of {}", path);
+        }
+    }
+}
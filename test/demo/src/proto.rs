@@ -1,12 +1,22 @@
 use prost::Message;
 use prost_types::Any;
+use std::error::Error;
 
 pub mod tendermint {
     pub mod light {
+        #![allow(dead_code)]
         tonic::include_proto!("tendermint.light");
     }
 }
 
+/// Messages of `proto/ibc/Channel.proto` (compiled as package `ibc.channel`, see `build.rs`)
+pub mod ibc {
+    pub mod channel {
+        #![allow(dead_code, clippy::module_inception)]
+        tonic::include_proto!("ibc.channel");
+    }
+}
+
 pub fn prost_serialize<T: Message>(msg: &T) -> Result<Vec<u8>, prost::EncodeError> {
     let mut buf = Vec::new();
     msg.encode(&mut buf)?;
@@ -27,3 +37,16 @@ pub fn prost_serialize_any<T: Message>(
 
     Ok(serialized)
 }
+
+/// Decodes a `google.protobuf.Any` wrapped message of the expected type
+pub fn prost_deserialize_any<T: Message + Default>(
+    bytes: &[u8],
+    type_url: &str,
+) -> Result<T, Box<dyn Error>> {
+    let any = Any::decode(bytes)?;
+    if any.type_url != type_url {
+        return Err(format!("expected {}, got {}", type_url, any.type_url).into());
+    }
+
+    Ok(T::decode(any.value.as_slice())?)
+}
@@ -0,0 +1,106 @@
+use serde_json::{json, Value};
+use std::error::Error;
+use web3::Transport;
+
+use crate::{
+    bindings::ibc_host::IBCHost,
+    proto::{
+        self,
+        tendermint::light::{ClientState, ConsensusState, Duration, Timestamp},
+    },
+};
+
+/// Latest client state stored in IBCHost, `None` if the client does not exist
+pub async fn get_client_state<T: Transport>(
+    host_contract: &IBCHost<T>,
+    client_id: &str,
+) -> Result<Option<ClientState>, Box<dyn Error>> {
+    let (bytes, found) = host_contract
+        .get_client_state(client_id.to_string())
+        .await?;
+
+    if !found {
+        return Ok(None);
+    }
+
+    Ok(Some(proto::prost_deserialize_any(
+        &bytes,
+        "/tendermint.types.ClientState",
+    )?))
+}
+
+/// Consensus state stored in IBCHost for the height, `None` if there is none
+pub async fn get_consensus_state<T: Transport>(
+    host_contract: &IBCHost<T>,
+    client_id: &str,
+    height: u64,
+) -> Result<Option<ConsensusState>, Box<dyn Error>> {
+    let (bytes, found) = host_contract
+        .get_consensus_state(client_id.to_string(), height)
+        .await?;
+
+    if !found {
+        return Ok(None);
+    }
+
+    Ok(Some(proto::prost_deserialize_any(
+        &bytes,
+        "/tendermint.types.ConsensusState",
+    )?))
+}
+
+fn duration_json(duration: &Option<Duration>) -> Value {
+    match duration {
+        Some(duration) => json!(format!(
+            "{}s",
+            duration.seconds as f64 + duration.nanos as f64 / 1e9
+        )),
+        None => Value::Null,
+    }
+}
+
+fn timestamp_json(timestamp: &Option<Timestamp>) -> Value {
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => return Value::Null,
+    };
+
+    match tendermint::Time::from_unix_timestamp(timestamp.seconds, timestamp.nanos as u32) {
+        Ok(time) => json!(time.as_rfc3339()),
+        Err(_) => json!({
+            "seconds": timestamp.seconds,
+            "nanos": timestamp.nanos,
+        }),
+    }
+}
+
+/// Human readable form of the client state (durations in seconds, 0 height = not frozen)
+pub fn client_state_json(client_state: &ClientState) -> Value {
+    json!({
+        "chain_id": client_state.chain_id,
+        "trust_level": client_state
+            .trust_level
+            .as_ref()
+            .map(|level| format!("{}/{}", level.numerator, level.denominator)),
+        "trusting_period": duration_json(&client_state.trusting_period),
+        "unbonding_period": duration_json(&client_state.unbonding_period),
+        "max_clock_drift": duration_json(&client_state.max_clock_drift),
+        "frozen_height": client_state.frozen_height,
+        "frozen": client_state.frozen_height != 0,
+        "latest_height": client_state.latest_height,
+        "allow_update_after_expiry": client_state.allow_update_after_expiry,
+        "allow_update_after_misbehaviour": client_state.allow_update_after_misbehaviour,
+    })
+}
+
+/// Human readable form of the consensus state (RFC 3339 timestamp, hex encoded hashes)
+pub fn consensus_state_json(consensus_state: &ConsensusState) -> Value {
+    json!({
+        "timestamp": timestamp_json(&consensus_state.timestamp),
+        "app_hash": consensus_state
+            .root
+            .as_ref()
+            .map(|root| hex::encode_upper(&root.hash)),
+        "next_validators_hash": hex::encode_upper(&consensus_state.next_validators_hash),
+    })
+}
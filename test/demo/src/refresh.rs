@@ -0,0 +1,88 @@
+/// When to refresh a client whose latest consensus state is older than `threshold` of the
+/// trusting period, so that it never expires even without packet traffic.
+#[derive(Clone, Copy, Debug)]
+pub struct RefreshPolicy {
+    /// Fraction of the trusting period, in (0, 1]
+    pub threshold: f64,
+}
+
+impl RefreshPolicy {
+    /// Parses the threshold given either as a fraction (`2/3`) or a decimal (`0.66`)
+    pub fn parse(threshold: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid refresh threshold: {}", threshold);
+
+        let value = match threshold.split_once('/') {
+            Some((numerator, denominator)) => {
+                let numerator = numerator.trim().parse::<f64>().map_err(|_| invalid())?;
+                let denominator = denominator.trim().parse::<f64>().map_err(|_| invalid())?;
+                numerator / denominator
+            }
+            None => threshold.trim().parse::<f64>().map_err(|_| invalid())?,
+        };
+
+        if !(value > 0.0 && value <= 1.0) {
+            return Err(format!(
+                "refresh threshold must be within (0, 1], got {}",
+                threshold
+            ));
+        }
+
+        Ok(RefreshPolicy { threshold: value })
+    }
+
+    /// Unix time (seconds) at which a consensus state created at `timestamp` is due
+    pub fn refresh_at(&self, timestamp: i64, trusting_period: i64) -> i64 {
+        timestamp.saturating_add((trusting_period as f64 * self.threshold) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fractions_and_decimals() {
+        assert_eq!(RefreshPolicy::parse("2/3").unwrap().threshold, 2.0 / 3.0);
+        assert_eq!(
+            RefreshPolicy::parse(" 2 / 3 ").unwrap().threshold,
+            2.0 / 3.0
+        );
+        assert_eq!(RefreshPolicy::parse("0.66").unwrap().threshold, 0.66);
+        assert_eq!(RefreshPolicy::parse("1").unwrap().threshold, 1.0);
+    }
+
+    #[test]
+    fn rejects_thresholds_outside_the_trusting_period() {
+        for threshold in &[
+            "0",
+            "1.5",
+            "1/0",
+            "0/0",
+            "-0.5",
+            "3/2",
+            "",
+            "two thirds",
+            "1/x",
+        ] {
+            assert!(
+                RefreshPolicy::parse(threshold).is_err(),
+                "accepted {:?}",
+                threshold
+            );
+        }
+    }
+
+    #[test]
+    fn refresh_time() {
+        let policy = RefreshPolicy::parse("2/3").unwrap();
+        // 14 days trusting period, due after 9 days 8 hours
+        assert_eq!(
+            policy.refresh_at(1_650_000_000, 14 * 24 * 3600),
+            1_650_000_000 + 806_400
+        );
+
+        let policy = RefreshPolicy::parse("1").unwrap();
+        assert_eq!(policy.refresh_at(1_650_000_000, 3600), 1_650_003_600);
+        assert_eq!(policy.refresh_at(i64::MAX - 10, 3600), i64::MAX);
+    }
+}
@@ -0,0 +1,1054 @@
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+};
+use tokio::time::{sleep, Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use web3::{
+    contract::Options,
+    types::{TransactionReceipt, H256, U256},
+    Transport,
+};
+
+use crate::{
+    bindings::{
+        ibc_handler::{MsgPacketAcknowledgement, MsgPacketRecv},
+        ibc_host::events::GeneratedClientIdentifier,
+    },
+    contracts::IbcContracts,
+    delay::{self, DelayPeriod},
+    encoder::{self, ClientParams},
+    gas::GasError,
+    header::HeaderSource,
+    indexer::Indexer,
+    metrics::Metrics,
+    planner::{Planner, Step, UpdateEstimator},
+    proto::tendermint::light::{LightHeader, TmHeader},
+    refresh::RefreshPolicy,
+    status::Status,
+    store::{self, ClientRecord, Outcome, Store, Submission, TrustedState},
+    txmgr::TxManager,
+    util,
+};
+
+/// Settings shared by every relayed header
+pub struct RelayOptions<'a> {
+    /// USD price of the fee currency, used for fee reporting only
+    pub fee_usd_price: f64,
+    pub celo_gas_price: f64,
+    /// Client to update, the most recently created one if not set
+    pub client_id: Option<&'a str>,
+    pub client_params: ClientParams,
+    pub metrics: &'a Metrics,
+    /// Record of the submitted txs and trusted states (`--state-db`)
+    pub store: Option<&'a Store>,
+    /// How `relay` updates the client
+    pub update_strategy: UpdateStrategy,
+    /// Maximum number of unconfirmed `updateClient` txs
+    pub max_in_flight: usize,
+    /// Skip the second header, so that the first update is non-adjacent
+    pub skip_second_header: bool,
+}
+
+/// How `Relayer::relay` updates the client
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpdateStrategy {
+    /// Header by header
+    Adjacent,
+    /// The cheapest mix of adjacent and non-adjacent updates reaching the last header
+    Auto,
+}
+
+impl std::str::FromStr for UpdateStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "adjacent" => Ok(UpdateStrategy::Adjacent),
+            "auto" => Ok(UpdateStrategy::Auto),
+            s => Err(format!("unknown update strategy {}", s)),
+        }
+    }
+}
+
+/// `updateClient` transaction submitted to the tx manager, but not confirmed yet
+pub struct PendingUpdate {
+    pub client_id: String,
+    pub nonce: U256,
+    pub height: u64,
+    /// Unix time (seconds) of the header
+    pub time: f64,
+    pub submitted_at: Instant,
+    /// Id of the submission in the state db
+    pub record: Option<u64>,
+}
+
+/// Whether the tx succeeded (Celo receipts always carry the status)
+pub fn tx_succeeded(receipt: &TransactionReceipt) -> Result<bool, Box<dyn Error>> {
+    match receipt.status {
+        Some(status) => Ok(status == web3::types::U64([1_u64])),
+        None => Err(format!(
+            "cannot determine the outcome of tx {:?}, the receipt carries no status",
+            receipt.transaction_hash
+        )
+        .into()),
+    }
+}
+
+/// Client to resume relaying to (`client_id` if given, the most recently created client of
+/// the state db otherwise) and its latest height on chain, `None` if there is nothing to resume.
+///
+/// The chain has the last word: updates submitted right before a crash may have landed
+/// without their confirmation being recorded.
+pub async fn resume_point<T: Transport>(
+    contracts: &IbcContracts<'_, T>,
+    store: Option<&Store>,
+    client_id: Option<&str>,
+) -> Result<Option<(String, u64)>, Box<dyn Error>> {
+    let client_id = match (client_id, store) {
+        (Some(client_id), _) => client_id.to_string(),
+        (None, Some(store)) => match store.clients()?.pop() {
+            Some(client) => client.client_id,
+            None => return Ok(None),
+        },
+        (None, None) => return Ok(None),
+    };
+
+    let client_state = match contracts.client_state(&client_id).await? {
+        Some(client_state) => client_state,
+        None => {
+            warn!(client_id = %client_id, "client not found on chain");
+            return Ok(None);
+        }
+    };
+    let height = client_state.latest_height as u64;
+    let recorded = match store {
+        Some(store) => store.trusted(&client_id)?.last().map(|state| state.height),
+        None => Some(height),
+    };
+    if recorded != Some(height) {
+        warn!(
+            client_id = %client_id,
+            height,
+            recorded = ?recorded,
+            "state db does not match the client on chain, resuming from the chain"
+        );
+    }
+
+    Ok(Some((client_id, height)))
+}
+
+/// Relays tendermint headers to the light client on Celo
+pub struct Relayer<'a, T: Transport> {
+    source: HeaderSource,
+    transport: &'a T,
+    contracts: IbcContracts<'a, T>,
+    txmgr: &'a TxManager<'a, T>,
+    indexer: &'a Indexer<'a, T>,
+    opts: RelayOptions<'a>,
+}
+
+impl<'a, T: Transport> Relayer<'a, T> {
+    pub fn new(
+        source: HeaderSource,
+        transport: &'a T,
+        txmgr: &'a TxManager<'a, T>,
+        indexer: &'a Indexer<'a, T>,
+        opts: RelayOptions<'a>,
+    ) -> Self {
+        Relayer {
+            source,
+            transport,
+            contracts: IbcContracts::new(transport),
+            txmgr,
+            indexer,
+            opts,
+        }
+    }
+
+    pub fn source(&self) -> &HeaderSource {
+        &self.source
+    }
+
+    pub fn contracts(&self) -> &IbcContracts<'a, T> {
+        &self.contracts
+    }
+
+    pub fn indexer(&self) -> &Indexer<'a, T> {
+        self.indexer
+    }
+
+    /// The client being relayed to: the one of the options, the most recently created client of
+    /// the source chain otherwise
+    pub async fn client_id(&self) -> Result<String, Box<dyn Error>> {
+        if let Some(client_id) = self.opts.client_id {
+            return Ok(client_id.to_string());
+        }
+
+        let chain_id = self.source.chain_id().await?;
+        self.indexer.sync().await?;
+        Ok(self
+            .indexer
+            .clients_of_chain(&chain_id)?
+            .pop()
+            .ok_or_else(|| format!("no client of {} has been created yet", chain_id))?)
+    }
+
+    /// Records the submitted tx in the state db, its id there
+    fn record_submission(
+        &self,
+        method: &str,
+        client_id: Option<&str>,
+        height: Option<u64>,
+        trusted_height: Option<u64>,
+        nonce: U256,
+    ) -> Result<Option<u64>, Box<dyn Error>> {
+        let store = match self.opts.store {
+            Some(store) => store,
+            None => return Ok(None),
+        };
+
+        Ok(Some(store.add_submission(&Submission {
+            id: 0,
+            method: method.to_string(),
+            client_id: client_id.map(str::to_string),
+            height,
+            trusted_height,
+            nonce,
+            submitted_at: store::now(),
+            outcome: None,
+        })?))
+    }
+
+    /// Records a consensus state the client holds now
+    fn record_trusted(
+        &self,
+        client_id: &str,
+        height: u64,
+        time: f64,
+        receipt: &TransactionReceipt,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(store) = self.opts.store {
+            store.add_trusted(
+                client_id,
+                &TrustedState {
+                    height,
+                    time,
+                    tx_hash: receipt.transaction_hash,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Reports the fee and metrics of a confirmed tx (and records them with the submission
+    /// `record` of the state db) and tells whether it succeeded
+    async fn observe_tx(
+        &self,
+        message: &str,
+        receipt: &TransactionReceipt,
+        submitted_at: Instant,
+        record: Option<u64>,
+        client_id: Option<&str>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let success = tx_succeeded(receipt)?;
+        let fee = util::calculate_and_display_fee(
+            message,
+            self.transport,
+            receipt,
+            self.opts.fee_usd_price,
+            self.opts.celo_gas_price,
+        )
+        .await?;
+        self.opts.metrics.observe_tx(
+            message,
+            success,
+            fee.gas_used,
+            fee.fee,
+            &fee.currency,
+            submitted_at.elapsed(),
+        );
+        if let (Some(store), Some(id)) = (self.opts.store, record) {
+            store.set_outcome(
+                id,
+                client_id,
+                Outcome {
+                    tx_hash: receipt.transaction_hash,
+                    success,
+                    gas_used: fee.gas_used,
+                    fee: fee.fee,
+                    currency: fee.currency,
+                    confirmed_at: store::now(),
+                },
+            )?;
+        }
+
+        Ok(success)
+    }
+
+    /// Registers the tendermint light client for its client type, unless a light client is
+    /// registered already. Tells whether the registration was sent.
+    pub async fn register_client(&self) -> Result<bool, Box<dyn Error>> {
+        match self.contracts.client_impl().await? {
+            Some(address) if address == self.contracts.light_client_address() => {
+                info!(?address, "client type 07-tendermint already registered");
+                return Ok(false);
+            }
+            Some(address) => {
+                warn!(
+                    ?address,
+                    "client type 07-tendermint is registered to another light client, leaving it"
+                );
+                return Ok(false);
+            }
+            None => {}
+        }
+
+        let submitted_at = Instant::now();
+        let nonce = self
+            .txmgr
+            .submit(
+                "registerClient",
+                self.contracts.handler_address(),
+                self.contracts.register_client_call(),
+                Options::default(),
+            )
+            .await?;
+        let record = self.record_submission("registerClient", None, None, None, nonce)?;
+        let register_client_reciept = self.txmgr.wait(nonce).await?;
+        if !self
+            .observe_tx(
+                "registerClient",
+                &register_client_reciept,
+                submitted_at,
+                record,
+                None,
+            )
+            .await?
+        {
+            return Err(format!(
+                "registerClient tx {:?} failed",
+                register_client_reciept.transaction_hash
+            )
+            .into());
+        }
+        info!(
+            tx_hash = ?register_client_reciept.transaction_hash,
+            "client type 07-tendermint registered"
+        );
+
+        Ok(true)
+    }
+
+    /// Most recently created client of the header's chain already holding the consensus state
+    /// of the header
+    async fn client_trusting(
+        &self,
+        header: &LightHeader,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let consensus_state = encoder::consensus_state(header);
+
+        self.indexer.sync().await?;
+        for client_id in self
+            .indexer
+            .clients_of_chain(&header.chain_id)?
+            .into_iter()
+            .rev()
+        {
+            let stored = self
+                .contracts
+                .consensus_state(&client_id, header.height as u64)
+                .await?;
+            if stored.as_ref() == Some(&consensus_state) {
+                return Ok(Some(client_id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Creates a client trusting `tm_header`, registering the client type first if needed.
+    ///
+    /// If a client of the chain already holds the consensus state of the header, nothing is
+    /// sent and that client is returned.
+    pub async fn create_client(&self, tm_header: &TmHeader) -> Result<String, Box<dyn Error>> {
+        let header = tm_header
+            .signed_header
+            .as_ref()
+            .and_then(|signed_header| signed_header.header.as_ref())
+            .ok_or("light block carries no header")?;
+
+        if let Some(client_id) = self.client_trusting(header).await? {
+            info!(client_id = %client_id, "a client trusting the header exists already");
+            return Ok(client_id);
+        }
+
+        self.register_client().await?;
+
+        let client_state = encoder::client_state(header, &self.opts.client_params);
+        let consensus_state = encoder::consensus_state(header);
+        let height = header.height as u64;
+
+        let submitted_at = Instant::now();
+        let nonce = self
+            .txmgr
+            .submit(
+                "createClient",
+                self.contracts.handler_address(),
+                self.contracts
+                    .create_client_call(&client_state, &consensus_state)?,
+                Options::default(),
+            )
+            .await?;
+        let record = self.record_submission("createClient", None, Some(height), None, nonce)?;
+        let create_client_reciept = self.txmgr.wait(nonce).await?;
+        let client_id = create_client_reciept
+            .logs
+            .iter()
+            .filter(|log| log.address == self.contracts.host.address())
+            .find_map(|log| GeneratedClientIdentifier::from_log(log).ok())
+            .map(|event| event.arg0);
+        let success = self
+            .observe_tx(
+                "createClient",
+                &create_client_reciept,
+                submitted_at,
+                record,
+                client_id.as_deref(),
+            )
+            .await?;
+        let client_id = match (success, client_id) {
+            (true, Some(client_id)) => client_id,
+            _ => {
+                return Err(format!(
+                    "failed to create a new 07-tendermint client instance (tx {:?})",
+                    create_client_reciept.transaction_hash
+                )
+                .into())
+            }
+        };
+        info!(
+            tx_hash = ?create_client_reciept.transaction_hash,
+            client_id = %client_id,
+            "new 07-tendermint client instance created"
+        );
+
+        if let Some(store) = self.opts.store {
+            store.add_client(&ClientRecord {
+                id: 0,
+                client_id: client_id.clone(),
+                chain_id: header.chain_id.clone(),
+                height,
+                created_at: store::now(),
+            })?;
+        }
+        self.record_trusted(
+            &client_id,
+            height,
+            encoder::header_time(tm_header),
+            &create_client_reciept,
+        )?;
+
+        Ok(client_id)
+    }
+
+    /// Updates the client to `height`, trusting `trusted_height` (the latest height of the
+    /// client if not given). Nothing is sent if the client already has a consensus state at
+    /// `height`; tells whether the update was sent.
+    pub async fn update_client(
+        &self,
+        client_id: &str,
+        height: u64,
+        trusted_height: Option<u64>,
+    ) -> Result<bool, Box<dyn Error>> {
+        if self
+            .contracts
+            .consensus_state(client_id, height)
+            .await?
+            .is_some()
+        {
+            info!(client_id, height, "consensus state already stored");
+            return Ok(false);
+        }
+
+        let trusted_height = match trusted_height {
+            Some(trusted_height) => trusted_height,
+            None => {
+                self.contracts
+                    .client_state(client_id)
+                    .await?
+                    .ok_or_else(|| format!("client {} not found", client_id))?
+                    .latest_height as u64
+            }
+        };
+        if self
+            .contracts
+            .consensus_state(client_id, trusted_height)
+            .await?
+            .is_none()
+        {
+            return Err(format!(
+                "client {} has no consensus state at the trusted height {}",
+                client_id, trusted_height
+            )
+            .into());
+        }
+
+        let tm_header = self.source.light_block(height as i64).await?;
+        let (_, update) = self
+            .submit_update(client_id.to_string(), trusted_height as i64, &tm_header)
+            .await?;
+        if !self.confirm_update(update).await? {
+            return Err(format!("update to height {} failed", height).into());
+        }
+
+        Ok(true)
+    }
+
+    /// Builds the `updateClient` call moving the client from `trusted_height` to `tm_header`
+    pub async fn update_client_call(
+        &self,
+        client_id: &str,
+        tm_header: &TmHeader,
+        trusted_height: i64,
+    ) -> Result<(TmHeader, Vec<u8>), Box<dyn Error>> {
+        let trusted_validators = match encoder::header_height(tm_header) == trusted_height + 1 {
+            true => None,
+            false => Some(self.source.validator_set(trusted_height + 1).await?),
+        };
+
+        let tm_header = encoder::update_header(tm_header, trusted_height, trusted_validators);
+        let data = self.contracts.update_client_call(client_id, &tm_header)?;
+
+        Ok((tm_header, data))
+    }
+
+    /// Submits an update from `trusted_height` to `tm_header` without waiting for it.
+    ///
+    /// The update is confirmed later on (see `confirm_update`), so that several updates can
+    /// be in flight at the same time.
+    pub async fn submit_update(
+        &self,
+        client_id: String,
+        trusted_height: i64,
+        tm_header: &TmHeader,
+    ) -> Result<(TmHeader, PendingUpdate), Box<dyn Error>> {
+        let (tm_header, data) = self
+            .update_client_call(&client_id, tm_header, trusted_height)
+            .await?;
+
+        let submitted_at = Instant::now();
+        let nonce = self
+            .txmgr
+            .submit(
+                "updateClient",
+                self.contracts.handler_address(),
+                data,
+                Options::default(),
+            )
+            .await?;
+        debug!(client_id = %client_id, nonce = %nonce, "update submitted");
+
+        let height = encoder::header_height(&tm_header) as u64;
+        let record = self.record_submission(
+            "updateClient",
+            Some(&client_id),
+            Some(height),
+            Some(trusted_height as u64),
+            nonce,
+        )?;
+        let update = PendingUpdate {
+            client_id,
+            nonce,
+            height,
+            time: encoder::header_time(&tm_header),
+            submitted_at,
+            record,
+        };
+
+        Ok((tm_header, update))
+    }
+
+    /// Waits for a submitted update and tells whether it succeeded
+    pub async fn confirm_update(&self, update: PendingUpdate) -> Result<bool, Box<dyn Error>> {
+        let update_client_reciept = self.txmgr.wait(update.nonce).await?;
+
+        let success = self
+            .observe_tx(
+                "updateClient",
+                &update_client_reciept,
+                update.submitted_at,
+                update.record,
+                Some(&update.client_id),
+            )
+            .await?;
+        if success {
+            info!(
+                tx_hash = ?update_client_reciept.transaction_hash,
+                "client updated"
+            );
+            self.record_trusted(
+                &update.client_id,
+                update.height,
+                update.time,
+                &update_client_reciept,
+            )?;
+            self.opts.metrics.set_client(
+                &update.client_id,
+                update.height,
+                update.time,
+                self.opts.client_params.trusting_period,
+            );
+        } else {
+            error!(
+                tx_hash = ?update_client_reciept.transaction_hash,
+                "failed to update client"
+            );
+        }
+
+        Ok(success)
+    }
+
+    /// Waits until a message proven at `proof_height` passes the delay period checks of the
+    /// connection of the channel, rather than sending it early and paying for the revert
+    async fn wait_for_delay(
+        &self,
+        client_id: &str,
+        port_id: &str,
+        channel_id: &str,
+        proof_height: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let delay = DelayPeriod::of_channel(&self.contracts, port_id, channel_id).await?;
+
+        loop {
+            let proof_delay = delay::get_proof_delay(
+                self.transport,
+                &self.contracts,
+                client_id,
+                delay,
+                proof_height,
+            )
+            .await?;
+            if proof_delay.elapsed() {
+                return Ok(());
+            }
+
+            info!(
+                client_id,
+                proof_height,
+                seconds_left = proof_delay.seconds_left,
+                blocks_left = proof_delay.blocks_left,
+                "waiting for the connection delay period"
+            );
+            // a Celo block takes a second at least
+            let wait = proof_delay.seconds_left.max(proof_delay.blocks_left);
+            sleep(Duration::from_secs(wait.clamp(1, 30))).await;
+        }
+    }
+
+    /// Sends a packet message verified against the consensus state of the client at
+    /// `proof_height` and waits for it, returns the tx hash.
+    ///
+    /// The gas estimation of the tx manager runs the call first, so a message the contracts
+    /// reject (e.g. a proof that does not verify) fails without being sent.
+    async fn send_packet_msg(
+        &self,
+        method: &str,
+        client_id: &str,
+        proof_height: u64,
+        data: Vec<u8>,
+    ) -> Result<H256, Box<dyn Error>> {
+        let submitted_at = Instant::now();
+        let nonce = self
+            .txmgr
+            .submit(
+                method,
+                self.contracts.handler_address(),
+                data,
+                Options::default(),
+            )
+            .await?;
+        let record =
+            self.record_submission(method, Some(client_id), Some(proof_height), None, nonce)?;
+        let receipt = self.txmgr.wait(nonce).await?;
+        if !self
+            .observe_tx(method, &receipt, submitted_at, record, Some(client_id))
+            .await?
+        {
+            return Err(format!("{} tx {:?} failed", method, receipt.transaction_hash).into());
+        }
+
+        Ok(receipt.transaction_hash)
+    }
+
+    /// Relays a packet sent by the chain of the client, once the delay period of the connection
+    /// has passed for the proof height
+    pub async fn recv_packet(
+        &self,
+        client_id: &str,
+        msg: MsgPacketRecv,
+    ) -> Result<H256, Box<dyn Error>> {
+        let proof_height = msg.proof_height;
+        let sequence = msg.packet.sequence;
+        self.wait_for_delay(
+            client_id,
+            &msg.packet.destination_port,
+            &msg.packet.destination_channel,
+            proof_height,
+        )
+        .await?;
+        let tx_hash = self
+            .send_packet_msg(
+                "recvPacket",
+                client_id,
+                proof_height,
+                self.contracts.recv_packet_call(msg),
+            )
+            .await?;
+        info!(?tx_hash, sequence, "packet received");
+
+        Ok(tx_hash)
+    }
+
+    /// Relays the acknowledgement the chain of the client wrote for a packet sent by Celo, once
+    /// the delay period of the connection has passed for the proof height
+    pub async fn acknowledge_packet(
+        &self,
+        client_id: &str,
+        msg: MsgPacketAcknowledgement,
+    ) -> Result<H256, Box<dyn Error>> {
+        let proof_height = msg.proof_height;
+        let sequence = msg.packet.sequence;
+        self.wait_for_delay(
+            client_id,
+            &msg.packet.source_port,
+            &msg.packet.source_channel,
+            proof_height,
+        )
+        .await?;
+        let tx_hash = self
+            .send_packet_msg(
+                "acknowledgePacket",
+                client_id,
+                proof_height,
+                self.contracts.acknowledge_packet_call(msg),
+            )
+            .await?;
+        info!(?tx_hash, sequence, "packet acknowledged");
+
+        Ok(tx_hash)
+    }
+
+    /// Relays `count` headers starting at `from` (if not set, the height after the resumed one
+    /// or the latest block).
+    ///
+    /// Without a `resumed_height` the first header creates a new client, otherwise the client
+    /// of the options is updated. Up to `max_in_flight` updates are submitted before the oldest
+    /// one is confirmed; after a failed update relaying continues from the height the client
+    /// holds on chain, since the updates trusting the failed one fail as well. With the `Auto`
+    /// strategy only the first header is processed one by one, the rest of the range is
+    /// covered by `relay_planned`.
+    pub async fn relay(
+        &self,
+        from: Option<u64>,
+        count: u64,
+        resumed_height: Option<u64>,
+    ) -> Result<(), Box<dyn Error>> {
+        let from = match (from, resumed_height) {
+            (Some(height), _) => height,
+            (None, Some(height)) => height + 1,
+            (None, None) => self.source.latest_height().await?,
+        };
+        let mut client_id = match resumed_height {
+            Some(_) => Some(self.client_id().await?),
+            None => None,
+        };
+
+        let mut trusted_height = resumed_height.unwrap_or_default() as i64;
+        let mut in_flight: VecDeque<PendingUpdate> = VecDeque::new();
+        let mut failed_updates = 0;
+        let headers = match self.opts.update_strategy {
+            UpdateStrategy::Auto => 1,
+            UpdateStrategy::Adjacent => count,
+        };
+        for (cnt, h) in (from..from + headers).enumerate() {
+            if !self.source.wait_for_block(h).await {
+                break;
+            }
+
+            let span = info_span!("header", height = h);
+            let tm_header = self
+                .source
+                .light_block(h as i64)
+                .instrument(span.clone())
+                .await?;
+
+            span.in_scope(|| info!(cnt, trusted_height, "processing header"));
+            if cnt == 0 && resumed_height.is_none() {
+                client_id = Some(self.create_client(&tm_header).instrument(span).await?);
+            } else if cnt == 1 && self.opts.skip_second_header && resumed_height.is_none() {
+                // the header is skipped, so that the next update is non-adjacent
+                continue;
+            } else {
+                let client_id = client_id
+                    .clone()
+                    .expect("client created with the first header");
+                let (_, update) = self
+                    .submit_update(client_id, trusted_height, &tm_header)
+                    .instrument(span)
+                    .await?;
+                in_flight.push_back(update);
+            }
+            trusted_height = encoder::header_height(&tm_header);
+
+            while in_flight.len() >= self.opts.max_in_flight.max(1) {
+                let update = in_flight.pop_front().unwrap();
+                let client_id = update.client_id.clone();
+                if !self.confirm_update_instrumented(update).await? {
+                    failed_updates += 1;
+                    trusted_height = self.client_height(&client_id).await?;
+                    warn!(
+                        failed_updates,
+                        trusted_height,
+                        "update failed, continuing from the height the client holds"
+                    );
+                }
+            }
+        }
+
+        for update in in_flight {
+            if !self.confirm_update_instrumented(update).await? {
+                failed_updates += 1;
+            }
+        }
+        if failed_updates > 0 {
+            return Err(format!("{} updates failed", failed_updates).into());
+        }
+
+        let target = from + count - 1;
+        if self.opts.update_strategy == UpdateStrategy::Auto
+            && target > from
+            && self.source.wait_for_block(target).await
+        {
+            let client_id = client_id.expect("client created with the first header");
+            self.relay_planned(&client_id, from, target).await?;
+        }
+
+        Ok(())
+    }
+
+    /// `confirm_update` within the span of the update
+    async fn confirm_update_instrumented(
+        &self,
+        update: PendingUpdate,
+    ) -> Result<bool, Box<dyn Error>> {
+        let span = info_span!(
+            "update",
+            client_id = %update.client_id,
+            height = update.height
+        );
+
+        self.confirm_update(update).instrument(span).await
+    }
+
+    /// Latest height of the client on chain
+    async fn client_height(&self, client_id: &str) -> Result<i64, Box<dyn Error>> {
+        Ok(self
+            .contracts
+            .client_state(client_id)
+            .await?
+            .ok_or_else(|| format!("client {} not found", client_id))?
+            .latest_height)
+    }
+
+    /// Updates the client to `target` following the cheapest route found by the planner
+    pub async fn relay_planned(
+        &self,
+        client_id: &str,
+        mut trusted_height: u64,
+        target: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let planner = Planner::default();
+        let mut candidates = UpdateCandidates {
+            relayer: self,
+            client_id,
+            calls: HashMap::new(),
+        };
+
+        while trusted_height < target {
+            let step = planner
+                .next_step(&mut candidates, trusted_height, target)
+                .instrument(info_span!("plan", trusted_height, target))
+                .await?;
+            let span = info_span!(
+                "update",
+                client_id = %client_id,
+                height = step.height,
+                trusted_height = step.trusted_height
+            );
+            span.in_scope(|| {
+                info!(
+                    target,
+                    adjacent = step.is_adjacent(),
+                    gas = %step.gas,
+                    "planned update"
+                )
+            });
+
+            let (data, time) = candidates
+                .take_call(&step)
+                .ok_or("planner chose an unestimated step")?;
+            let submitted_at = Instant::now();
+            let nonce = self
+                .txmgr
+                .submit(
+                    "updateClient",
+                    self.contracts.handler_address(),
+                    data,
+                    Options::default(),
+                )
+                .instrument(span.clone())
+                .await?;
+
+            // the next step is estimated against the state this update leaves behind
+            let update = PendingUpdate {
+                client_id: client_id.to_string(),
+                nonce,
+                height: step.height,
+                time,
+                submitted_at,
+                record: self.record_submission(
+                    "updateClient",
+                    Some(client_id),
+                    Some(step.height),
+                    Some(step.trusted_height),
+                    nonce,
+                )?,
+            };
+            if !self.confirm_update(update).instrument(span).await? {
+                return Err(format!("update to height {} failed", step.height).into());
+            }
+
+            trusted_height = step.height;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the client from expiring when there is no traffic: once the latest consensus
+    /// state is older than the policy threshold of the trusting period, the client is updated
+    /// to the latest tendermint block following the cheapest route found by the planner
+    pub async fn refresh(
+        &self,
+        policy: RefreshPolicy,
+        check_interval: Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        let client_id = self.client_id().await?;
+
+        loop {
+            let (client_state, status) = self.contracts.client_status(&client_id).await?;
+            if status.status != Status::Active {
+                return Err(format!("client {} is {}", client_id, status.status).into());
+            }
+
+            // the trusting period of the client may differ from the one given in the options
+            let trusting_period = client_state
+                .trusting_period
+                .as_ref()
+                .map(|period| period.seconds)
+                .unwrap_or_default();
+            self.opts.metrics.set_client(
+                &client_id,
+                status.latest_height as u64,
+                status.latest_timestamp as f64,
+                Duration::from_secs(trusting_period as u64),
+            );
+
+            let refresh_at = policy.refresh_at(status.latest_timestamp, trusting_period);
+            let trusted_height = status.latest_height as u64;
+            let target = self.source.latest_height().await?;
+
+            if status.now < refresh_at || target <= trusted_height {
+                let wait = Duration::from_secs((refresh_at - status.now).max(0) as u64)
+                    .min(check_interval)
+                    .max(Duration::from_secs(1));
+                debug!(
+                    client_id = %client_id,
+                    seconds_to_refresh = refresh_at - status.now,
+                    seconds_to_expiry = status.seconds_to_expiry,
+                    "client is fresh"
+                );
+                sleep(wait).await;
+                continue;
+            }
+
+            info!(
+                client_id = %client_id,
+                trusted_height,
+                target,
+                seconds_to_expiry = status.seconds_to_expiry,
+                "refreshing client"
+            );
+            self.relay_planned(&client_id, trusted_height, target)
+                .await?;
+        }
+    }
+}
+
+/// Estimates candidate updates for the planner, keeping the encoded calls around so that
+/// the chosen one doesn't have to be fetched again.
+struct UpdateCandidates<'r, 'a, T: Transport> {
+    relayer: &'r Relayer<'a, T>,
+    client_id: &'r str,
+    calls: HashMap<(u64, u64), (Vec<u8>, f64)>,
+}
+
+impl<'r, 'a, T: Transport> UpdateCandidates<'r, 'a, T> {
+    /// Encoded call and header time of the chosen step
+    fn take_call(&mut self, step: &Step) -> Option<(Vec<u8>, f64)> {
+        let call = self.calls.remove(&(step.trusted_height, step.height));
+        self.calls.clear();
+        call
+    }
+}
+
+#[async_trait(?Send)]
+impl<'r, 'a, T: Transport> UpdateEstimator for UpdateCandidates<'r, 'a, T> {
+    async fn estimate_update(
+        &mut self,
+        trusted_height: u64,
+        height: u64,
+    ) -> Result<Option<U256>, Box<dyn Error>> {
+        let tm_header = self.relayer.source.light_block(height as i64).await?;
+        let (_, data) = self
+            .relayer
+            .update_client_call(self.client_id, &tm_header, trusted_height as i64)
+            .await?;
+
+        let gas = match self
+            .relayer
+            .txmgr
+            .estimate_gas(
+                "updateClient",
+                self.relayer.contracts.handler_address(),
+                &data,
+            )
+            .await
+        {
+            Ok(gas) => gas,
+            // reverted (e.g. not enough trusted voting power signed) or too big for a block
+            Err(GasError::Rpc(web3::Error::Rpc(_)))
+            | Err(GasError::ExceedsBlockGasLimit { .. }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        self.calls.insert(
+            (trusted_height, height),
+            (data, encoder::header_time(&tm_header)),
+        );
+
+        Ok(Some(gas))
+    }
+}
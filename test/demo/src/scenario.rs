@@ -0,0 +1,551 @@
+//! Light client scenarios: header sequences described in YAML (or JSON) files, submitted to
+//! the light client with the outcome of every step checked against the expected one.
+//!
+//! ```yaml
+//! name: conflicting header freezes the client
+//! chains:
+//!   main:
+//!     synth: {}
+//!   fork:
+//!     synth: { fork: 1 }
+//! client:
+//!   trusting_period: 86400
+//! steps:
+//!   - create_client: { height: 1 }
+//!   - update_client: { height: 2 }
+//!   - update_client: { height: 2, trusted_height: 1, chain: fork }
+//!     expect: frozen
+//! ```
+//!
+//! Headers come from the `chains` of the scenario, either recorded (`fixtures: <dir>`, relative
+//! to the scenario file) or generated (`synth:` with the fields of [`ChainConfig`]); steps use
+//! the `main` chain unless they name another one. Updates trust the latest height the client
+//! reached unless `trusted_height` is given. A step expects `ok` (the default), `frozen` (the
+//! update succeeds and freezes the client) or a revert, the expected message has to be a part
+//! of the revert reason (e.g. `LC: failed to verify header`).
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{info, warn};
+use web3::{contract::Options, Transport};
+
+use crate::{
+    bindings::ibc_host::events::GeneratedClientIdentifier,
+    contracts::IbcContracts,
+    encoder::{self, ClientParams},
+    evm::EmbeddedChain,
+    gas::GasError,
+    header,
+    proto::tendermint::light::{ClientState, TmHeader},
+    relay::tx_succeeded,
+    synth::{ChainConfig, SyntheticChain},
+    txmgr::TxManager,
+};
+
+/// Chain the name of which steps use by default
+pub const MAIN_CHAIN: &str = "main";
+
+/// Where the headers of a chain come from
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// Directory with headers saved by `--save` or `synth`
+    Fixtures(PathBuf),
+    /// Headers generated on the fly
+    Synth(ChainConfig),
+}
+
+/// Client parameters overriding those of the command line, in seconds
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ClientOverrides {
+    pub trusting_period: Option<u64>,
+    pub unbonding_period: Option<u64>,
+    pub max_clock_drift: Option<u64>,
+}
+
+impl ClientOverrides {
+    fn apply(&self, params: &ClientParams) -> ClientParams {
+        let seconds = |value: Option<u64>, default: Duration| {
+            value.map(Duration::from_secs).unwrap_or(default)
+        };
+
+        ClientParams {
+            trusting_period: seconds(self.trusting_period, params.trusting_period),
+            unbonding_period: seconds(self.unbonding_period, params.unbonding_period),
+            max_clock_drift: seconds(self.max_clock_drift, params.max_clock_drift),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    CreateClient {
+        height: i64,
+        chain: Option<String>,
+    },
+    UpdateClient {
+        height: i64,
+        trusted_height: Option<i64>,
+        chain: Option<String>,
+    },
+    /// Lets time pass on the chain the light client runs on
+    Sleep {
+        seconds: u64,
+    },
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on = |chain: &Option<String>| match chain {
+            Some(chain) => format!(" on {}", chain),
+            None => String::new(),
+        };
+
+        match self {
+            Action::CreateClient { height, chain } => {
+                write!(f, "createClient at {}{}", height, on(chain))
+            }
+            Action::UpdateClient {
+                height,
+                trusted_height: Some(trusted_height),
+                chain,
+            } => write!(
+                f,
+                "updateClient to {} trusting {}{}",
+                height,
+                trusted_height,
+                on(chain)
+            ),
+            Action::UpdateClient { height, chain, .. } => {
+                write!(f, "updateClient to {}{}", height, on(chain))
+            }
+            Action::Sleep { seconds } => write!(f, "sleep {}s", seconds),
+        }
+    }
+}
+
+/// Expected outcome of a step
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Expect {
+    #[default]
+    Ok,
+    /// The update succeeds and freezes the client (valid conflicting header)
+    Frozen,
+    /// The transaction reverts with a reason containing the message
+    Revert(String),
+}
+
+impl From<String> for Expect {
+    fn from(expect: String) -> Self {
+        match expect.as_str() {
+            "ok" => Expect::Ok,
+            "frozen" => Expect::Frozen,
+            _ => Expect::Revert(expect),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Expect {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(String::deserialize(deserializer)?.into())
+    }
+}
+
+impl fmt::Display for Expect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expect::Ok => write!(f, "ok"),
+            Expect::Frozen => write!(f, "frozen"),
+            Expect::Revert(reason) => write!(f, "revert: {}", reason),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Step {
+    #[serde(flatten)]
+    pub action: Action,
+    #[serde(default)]
+    pub expect: Expect,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub chains: BTreeMap<String, Source>,
+    #[serde(default)]
+    pub client: ClientOverrides,
+    pub steps: Vec<Step>,
+    /// Directory of the scenario file, fixtures are relative to it
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+impl Scenario {
+    /// Reads a scenario from a YAML file (JSON being YAML as well)
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // serde_yaml wants tags (`!synth`) for enums, through JSON values the single-key maps
+        // of JSON scenarios work in YAML as well
+        let value: serde_json::Value =
+            serde_yaml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut scenario: Scenario =
+            serde_json::from_value(value).map_err(|e| format!("{}: {}", path.display(), e))?;
+        scenario.dir = path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+
+        Ok(scenario)
+    }
+}
+
+/// Headers of a scenario chain, synthetic ones generated up to the highest height asked for
+enum Headers {
+    Recorded(PathBuf),
+    Synthetic {
+        chain: Box<SyntheticChain>,
+        blocks: HashMap<i64, TmHeader>,
+    },
+}
+
+impl Headers {
+    fn open(source: &Source, dir: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(match source {
+            Source::Fixtures(fixtures) => Headers::Recorded(dir.join(fixtures)),
+            Source::Synth(config) => Headers::Synthetic {
+                chain: Box::new(SyntheticChain::new(config.clone())?),
+                blocks: HashMap::new(),
+            },
+        })
+    }
+
+    fn light_block(&mut self, height: i64) -> Result<TmHeader, Box<dyn Error>> {
+        match self {
+            Headers::Recorded(dir) => header::read_light_block(dir, height),
+            Headers::Synthetic { chain, blocks } => {
+                while chain.next_height() as i64 <= height {
+                    let block = chain.next_block()?;
+                    blocks.insert(encoder::header_height(&block.tm_header), block.tm_header);
+                }
+
+                Ok(blocks
+                    .get(&height)
+                    .ok_or_else(|| format!("height {} is before the first block", height))?
+                    .clone())
+            }
+        }
+    }
+}
+
+/// Chain the light client runs on
+#[async_trait(?Send)]
+pub trait Target {
+    /// Creates a client, its identifier or the revert reason if the creation fails
+    async fn create_client(
+        &mut self,
+        tm_header: &TmHeader,
+        params: &ClientParams,
+    ) -> Result<Result<String, String>, Box<dyn Error>>;
+
+    /// Updates the client, the revert reason if the update fails
+    async fn update_client(
+        &mut self,
+        client_id: &str,
+        tm_header: &TmHeader,
+    ) -> Result<Result<(), String>, Box<dyn Error>>;
+
+    async fn client_state(
+        &mut self,
+        client_id: &str,
+    ) -> Result<Option<ClientState>, Box<dyn Error>>;
+
+    async fn sleep(&mut self, seconds: u64) -> Result<(), Box<dyn Error>>;
+}
+
+#[async_trait(?Send)]
+impl Target for EmbeddedChain {
+    async fn create_client(
+        &mut self,
+        tm_header: &TmHeader,
+        params: &ClientParams,
+    ) -> Result<Result<String, String>, Box<dyn Error>> {
+        let (client_id, execution) = EmbeddedChain::create_client(self, tm_header, params)?;
+
+        Ok(match execution.error {
+            Some(reason) => Err(reason),
+            None => Ok(client_id.ok_or("createClient emitted no client identifier")?),
+        })
+    }
+
+    async fn update_client(
+        &mut self,
+        client_id: &str,
+        tm_header: &TmHeader,
+    ) -> Result<Result<(), String>, Box<dyn Error>> {
+        let execution = EmbeddedChain::update_client(self, client_id, tm_header)?;
+
+        Ok(match execution.error {
+            Some(reason) => Err(reason),
+            None => Ok(()),
+        })
+    }
+
+    async fn client_state(
+        &mut self,
+        client_id: &str,
+    ) -> Result<Option<ClientState>, Box<dyn Error>> {
+        EmbeddedChain::client_state(self, client_id)
+    }
+
+    async fn sleep(&mut self, seconds: u64) -> Result<(), Box<dyn Error>> {
+        EmbeddedChain::sleep(self, seconds);
+        Ok(())
+    }
+}
+
+/// Local Celo chain with the contracts of the deployment (see `consts`).
+///
+/// Calls are estimated first, a revert in the estimation is the outcome of the step and
+/// nothing is sent.
+pub struct Devnet<'a, T: Transport> {
+    contracts: IbcContracts<'a, T>,
+    txmgr: &'a TxManager<'a, T>,
+    registered: bool,
+}
+
+impl<'a, T: Transport> Devnet<'a, T> {
+    pub fn new(transport: &'a T, txmgr: &'a TxManager<'a, T>) -> Self {
+        Devnet {
+            contracts: IbcContracts::new(transport),
+            txmgr,
+            registered: false,
+        }
+    }
+
+    /// Estimates and sends the call, the receipt or the revert reason
+    async fn transact(
+        &self,
+        method: &str,
+        data: Vec<u8>,
+    ) -> Result<Result<web3::types::TransactionReceipt, String>, Box<dyn Error>> {
+        let to = self.contracts.handler_address();
+
+        match self.txmgr.estimate_gas(method, to, &data).await {
+            Ok(_) => {}
+            Err(GasError::Rpc(web3::Error::Rpc(e))) => {
+                return Ok(Err(e
+                    .message
+                    .trim_start_matches("execution reverted: ")
+                    .to_string()))
+            }
+            Err(e @ GasError::ExceedsBlockGasLimit { .. }) => return Ok(Err(e.to_string())),
+            Err(e) => return Err(e.into()),
+        }
+
+        let receipt = self
+            .txmgr
+            .send(method, to, data, Options::default())
+            .await?;
+        if tx_succeeded(&receipt)? {
+            Ok(Ok(receipt))
+        } else {
+            Ok(Err(format!(
+                "tx {:?} reverted although its estimation succeeded",
+                receipt.transaction_hash
+            )))
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, T: Transport> Target for Devnet<'a, T> {
+    async fn create_client(
+        &mut self,
+        tm_header: &TmHeader,
+        params: &ClientParams,
+    ) -> Result<Result<String, String>, Box<dyn Error>> {
+        if !self.registered {
+            if let Err(reason) = self
+                .transact("registerClient", self.contracts.register_client_call())
+                .await?
+            {
+                warn!(%reason, "client type 07-tendermint not registered");
+            }
+            self.registered = true;
+        }
+
+        let header = tm_header
+            .signed_header
+            .as_ref()
+            .and_then(|signed_header| signed_header.header.as_ref())
+            .ok_or("light block carries no header")?;
+        let call = self.contracts.create_client_call(
+            &encoder::client_state(header, params),
+            &encoder::consensus_state(header),
+        )?;
+
+        let receipt = match self.transact("createClient", call).await? {
+            Ok(receipt) => receipt,
+            Err(reason) => return Ok(Err(reason)),
+        };
+        let client_id = receipt
+            .logs
+            .iter()
+            .filter(|log| log.address == self.contracts.host.address())
+            .find_map(|log| GeneratedClientIdentifier::from_log(log).ok())
+            .ok_or("createClient emitted no client identifier")?;
+
+        Ok(Ok(client_id.arg0))
+    }
+
+    async fn update_client(
+        &mut self,
+        client_id: &str,
+        tm_header: &TmHeader,
+    ) -> Result<Result<(), String>, Box<dyn Error>> {
+        let call = self.contracts.update_client_call(client_id, tm_header)?;
+
+        Ok(self.transact("updateClient", call).await?.map(|_| ()))
+    }
+
+    async fn client_state(
+        &mut self,
+        client_id: &str,
+    ) -> Result<Option<ClientState>, Box<dyn Error>> {
+        self.contracts.client_state(client_id).await
+    }
+
+    async fn sleep(&mut self, seconds: u64) -> Result<(), Box<dyn Error>> {
+        tokio::time::sleep(Duration::from_secs(seconds)).await;
+        Ok(())
+    }
+}
+
+/// Outcome of a step compared to the expected one
+#[derive(Clone, Debug, Serialize)]
+pub struct StepReport {
+    pub step: usize,
+    pub action: String,
+    pub expected: String,
+    pub outcome: String,
+    pub passed: bool,
+}
+
+/// Runs the steps of the scenario in order, a step not going as expected does not stop the
+/// run. `params` are the client parameters the scenario does not override.
+pub async fn run<T: Target>(
+    scenario: &Scenario,
+    params: &ClientParams,
+    target: &mut T,
+) -> Result<Vec<StepReport>, Box<dyn Error>> {
+    let params = scenario.client.apply(params);
+    let mut chains = scenario
+        .chains
+        .iter()
+        .map(|(name, source)| Ok((name.as_str(), Headers::open(source, &scenario.dir)?)))
+        .collect::<Result<HashMap<_, _>, Box<dyn Error>>>()?;
+    let mut headers = |chain: &Option<String>, height: i64| {
+        let name = chain.as_deref().unwrap_or(MAIN_CHAIN);
+        chains
+            .get_mut(name)
+            .ok_or_else(|| format!("scenario has no chain {}", name))?
+            .light_block(height)
+    };
+
+    let mut client_id: Option<String> = None;
+    let mut latest_height = 0;
+    let mut reports = Vec::new();
+    for (index, step) in scenario.steps.iter().enumerate() {
+        let outcome = match &step.action {
+            Action::CreateClient { height, chain } => {
+                let tm_header = headers(chain, *height)?;
+                target.create_client(&tm_header, &params).await?.map(|id| {
+                    client_id = Some(id);
+                    latest_height = *height;
+                })
+            }
+            Action::UpdateClient {
+                height,
+                trusted_height,
+                chain,
+            } => {
+                let id = client_id
+                    .as_deref()
+                    .ok_or("updateClient before the client is created")?;
+                let trusted_height = trusted_height.unwrap_or(latest_height);
+                // a trusted height without a header is left for the light client to reject
+                let trusted_validators = if *height == trusted_height + 1 {
+                    None
+                } else {
+                    headers(chain, trusted_height + 1)
+                        .ok()
+                        .and_then(|trusted| trusted.validator_set)
+                };
+                let tm_header = encoder::update_header(
+                    &headers(chain, *height)?,
+                    trusted_height,
+                    trusted_validators,
+                );
+
+                let outcome = target.update_client(id, &tm_header).await?;
+                if outcome.is_ok() {
+                    latest_height = latest_height.max(*height);
+                }
+                outcome
+            }
+            Action::Sleep { seconds } => {
+                target.sleep(*seconds).await?;
+                Ok(())
+            }
+        };
+
+        let frozen = match (&outcome, &client_id) {
+            (Ok(()), Some(id)) if step.expect == Expect::Frozen => target
+                .client_state(id)
+                .await?
+                .is_some_and(|state| state.frozen_height != 0),
+            _ => false,
+        };
+        let passed = match (&step.expect, &outcome) {
+            (Expect::Ok, Ok(())) => true,
+            (Expect::Frozen, Ok(())) => frozen,
+            (Expect::Revert(expected), Err(reason)) => reason.contains(expected.as_str()),
+            _ => false,
+        };
+        let outcome = match outcome {
+            Ok(()) if frozen => "frozen".to_string(),
+            Ok(()) => "ok".to_string(),
+            Err(reason) => format!("revert: {}", reason),
+        };
+
+        let report = StepReport {
+            step: index + 1,
+            action: step.action.to_string(),
+            expected: step.expect.to_string(),
+            outcome,
+            passed,
+        };
+        if passed {
+            info!(step = report.step, action = %report.action, outcome = %report.outcome, "passed");
+        } else {
+            warn!(
+                step = report.step,
+                action = %report.action,
+                expected = %report.expected,
+                outcome = %report.outcome,
+                "failed"
+            );
+        }
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use secp256k1::key::SecretKey;
+use serde_json::json;
+use web3::{
+    signing::{Key, SecretKeyRef},
+    types::{Address, Bytes, U64},
+    Transport,
+};
+
+use crate::celo::CeloTransaction;
+
+/// Signs Celo transactions on behalf of the relayer account.
+///
+/// The transactions passed to `sign_transaction` are expected to be complete (nonce, gas
+/// price and chain id set), so that a signer never needs to talk to the chain.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Address of the account the signer produces signatures for
+    fn address(&self) -> Address;
+
+    /// Signs the transaction and returns its raw (RLP encoded) form
+    async fn sign_transaction(&self, tx: CeloTransaction) -> web3::Result<Bytes>;
+}
+
+/// In-process signer holding the raw secp256k1 private key
+pub struct LocalSigner {
+    key: SecretKey,
+}
+
+impl LocalSigner {
+    pub fn new(key: SecretKey) -> Self {
+        LocalSigner { key }
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    fn address(&self) -> Address {
+        SecretKeyRef::new(&self.key).address()
+    }
+
+    async fn sign_transaction(&self, tx: CeloTransaction) -> web3::Result<Bytes> {
+        Ok(tx.sign(SecretKeyRef::new(&self.key))?.raw_transaction)
+    }
+}
+
+/// Signer delegating to a remote signing service over JSON-RPC (`eth_signTransaction`)
+///
+/// The private key never leaves the service, which is free to apply its own policy checks
+/// (e.g. allowed contracts or gas caps) before returning the signature.
+pub struct RemoteSigner<T: Transport> {
+    transport: T,
+    address: Address,
+}
+
+impl<T: Transport> RemoteSigner<T> {
+    pub fn new(transport: T, address: Address) -> Self {
+        RemoteSigner { transport, address }
+    }
+}
+
+#[async_trait]
+impl<T> Signer for RemoteSigner<T>
+where
+    T: Transport + Send + Sync,
+    T::Out: Send,
+{
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(&self, tx: CeloTransaction) -> web3::Result<Bytes> {
+        // field names follow the celo-blockchain transaction args
+        let request = json!({
+            "from": self.address,
+            "to": tx.to,
+            "gas": tx.gas,
+            "gasPrice": tx.gas_price,
+            "feeCurrency": tx.fee_currency,
+            "gatewayFeeRecipient": tx.gateway_fee_recipient,
+            "gatewayFee": tx.gateway_fee,
+            "value": tx.value,
+            "data": Bytes(tx.data),
+            "nonce": tx.nonce,
+            "chainId": U64::from(tx.chain_id),
+        });
+
+        let response = self
+            .transport
+            .execute("eth_signTransaction", vec![request])
+            .await?;
+
+        // geth-style signers return `{ raw, tx }`, others return the raw transaction only
+        let raw = match response.get("raw") {
+            Some(raw) => raw.clone(),
+            None => response,
+        };
+
+        serde_json::from_value(raw).map_err(|e| web3::Error::Decoder(e.to_string()))
+    }
+}
@@ -0,0 +1,218 @@
+use serde::Serialize;
+use std::{error::Error, fmt};
+use web3::{
+    types::{BlockId, BlockNumber},
+    Transport, Web3,
+};
+
+use crate::{
+    bindings::ibc_host::IBCHost,
+    proto::tendermint::light::{ClientState, ConsensusState, Duration, Timestamp},
+    query,
+};
+
+/// Client status, with the same meaning as in ibc-go
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Status {
+    /// Updates are accepted
+    Active,
+    /// The latest consensus state left the trusting period, the client can't verify headers
+    Expired,
+    /// Misbehaviour was detected (frozen height is set)
+    Frozen,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Status of a client at a point in time (unix seconds)
+#[derive(Clone, Debug, Serialize)]
+pub struct ClientStatus {
+    pub status: Status,
+    pub latest_height: i64,
+    pub frozen_height: i64,
+    /// Time of the latest consensus state
+    pub latest_timestamp: i64,
+    /// Time the status was evaluated at (Celo block time)
+    pub now: i64,
+    /// Time the latest consensus state leaves the trusting period
+    pub expires_at: i64,
+    /// Negative once expired
+    pub seconds_to_expiry: i64,
+    /// The latest consensus state is further in the future than `max_clock_drift` allows,
+    /// i.e. the Celo clock lags behind the counterparty
+    pub clock_drift_exceeded: bool,
+    /// Whether the client may be brought back (`allow_update_after_expiry` for an expired
+    /// client, `allow_update_after_misbehaviour` for a frozen one)
+    pub recoverable: bool,
+}
+
+fn seconds(duration: &Option<Duration>) -> i64 {
+    duration.as_ref().map(|d| d.seconds).unwrap_or_default()
+}
+
+fn timestamp_seconds(timestamp: &Option<Timestamp>) -> i64 {
+    timestamp.as_ref().map(|t| t.seconds).unwrap_or_default()
+}
+
+/// Evaluates the status of a client given its latest consensus state and the current time
+pub fn client_status(
+    client_state: &ClientState,
+    latest_consensus_state: &ConsensusState,
+    now: i64,
+) -> ClientStatus {
+    let latest_timestamp = timestamp_seconds(&latest_consensus_state.timestamp);
+    let expires_at = latest_timestamp.saturating_add(seconds(&client_state.trusting_period));
+
+    let (status, recoverable) = if client_state.frozen_height != 0 {
+        (Status::Frozen, client_state.allow_update_after_misbehaviour)
+    } else if expires_at <= now {
+        (Status::Expired, client_state.allow_update_after_expiry)
+    } else {
+        (Status::Active, true)
+    };
+
+    ClientStatus {
+        status,
+        latest_height: client_state.latest_height,
+        frozen_height: client_state.frozen_height,
+        latest_timestamp,
+        now,
+        expires_at,
+        seconds_to_expiry: expires_at.saturating_sub(now),
+        clock_drift_exceeded: latest_timestamp
+            > now.saturating_add(seconds(&client_state.max_clock_drift)),
+        recoverable,
+    }
+}
+
+/// Timestamp of the latest Celo block
+pub async fn celo_block_time<T: Transport>(transport: &T) -> web3::Result<i64> {
+    let block = Web3::new(transport)
+        .eth()
+        .block(BlockId::Number(BlockNumber::Latest))
+        .await?
+        .ok_or_else(|| web3::Error::InvalidResponse("latest block not found".to_string()))?;
+
+    Ok(block.timestamp.low_u64() as i64)
+}
+
+/// Reads the client and its latest consensus state from IBCHost and evaluates its status
+/// against the latest Celo block time
+pub async fn get_client_status<T: Transport, C: Transport>(
+    transport: &T,
+    host_contract: &IBCHost<C>,
+    client_id: &str,
+) -> Result<(ClientState, ClientStatus), Box<dyn Error>> {
+    let client_state = query::get_client_state(host_contract, client_id)
+        .await?
+        .ok_or_else(|| format!("client {} not found", client_id))?;
+    let consensus_state =
+        query::get_consensus_state(host_contract, client_id, client_state.latest_height as u64)
+            .await?
+            .ok_or_else(|| {
+                format!(
+                    "consensus state of client {} at its latest height {} not found",
+                    client_id, client_state.latest_height
+                )
+            })?;
+    let now = celo_block_time(transport).await?;
+
+    let status = client_status(&client_state, &consensus_state, now);
+
+    Ok((client_state, status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRUSTING_PERIOD: i64 = 14 * 24 * 3600;
+    const TIMESTAMP: i64 = 1_650_000_000;
+
+    fn client_state(frozen_height: i64) -> ClientState {
+        ClientState {
+            latest_height: 28,
+            frozen_height,
+            trusting_period: Some(Duration {
+                seconds: TRUSTING_PERIOD,
+                nanos: 0,
+            }),
+            max_clock_drift: Some(Duration {
+                seconds: 10,
+                nanos: 0,
+            }),
+            allow_update_after_expiry: false,
+            allow_update_after_misbehaviour: true,
+            ..Default::default()
+        }
+    }
+
+    fn consensus_state() -> ConsensusState {
+        ConsensusState {
+            timestamp: Some(Timestamp {
+                seconds: TIMESTAMP,
+                nanos: 0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn active_within_the_trusting_period() {
+        let now = TIMESTAMP + 3600;
+        let status = client_status(&client_state(0), &consensus_state(), now);
+
+        assert_eq!(status.status, Status::Active);
+        assert!(status.recoverable);
+        assert_eq!(status.expires_at, TIMESTAMP + TRUSTING_PERIOD);
+        assert_eq!(status.seconds_to_expiry, TRUSTING_PERIOD - 3600);
+        assert!(!status.clock_drift_exceeded);
+    }
+
+    #[test]
+    fn expired_at_the_end_of_the_trusting_period() {
+        let expires_at = TIMESTAMP + TRUSTING_PERIOD;
+
+        let status = client_status(&client_state(0), &consensus_state(), expires_at - 1);
+        assert_eq!(status.status, Status::Active);
+        assert_eq!(status.seconds_to_expiry, 1);
+
+        let status = client_status(&client_state(0), &consensus_state(), expires_at);
+        assert_eq!(status.status, Status::Expired);
+        assert_eq!(status.seconds_to_expiry, 0);
+        assert!(!status.recoverable);
+    }
+
+    #[test]
+    fn expired_after_the_trusting_period() {
+        let now = TIMESTAMP + TRUSTING_PERIOD + 60;
+        let status = client_status(&client_state(0), &consensus_state(), now);
+
+        assert_eq!(status.status, Status::Expired);
+        assert_eq!(status.seconds_to_expiry, -60);
+    }
+
+    #[test]
+    fn frozen_takes_precedence_over_expired() {
+        let now = TIMESTAMP + TRUSTING_PERIOD + 60;
+        let status = client_status(&client_state(25), &consensus_state(), now);
+
+        assert_eq!(status.status, Status::Frozen);
+        assert_eq!(status.frozen_height, 25);
+        // `allow_update_after_misbehaviour`, not `allow_update_after_expiry`
+        assert!(status.recoverable);
+    }
+
+    #[test]
+    fn clock_drift() {
+        let status = client_status(&client_state(0), &consensus_state(), TIMESTAMP - 11);
+        assert!(status.clock_drift_exceeded);
+
+        let status = client_status(&client_state(0), &consensus_state(), TIMESTAMP - 10);
+        assert!(!status.clock_drift_exceeded);
+    }
+}
@@ -0,0 +1,523 @@
+//! Local record of the relayer's work (`--state-db`, a sled database): the clients it created,
+//! every transaction it submitted with its outcome and cost, and the heights the clients trust
+//! after its updates.
+//!
+//! The relayer resumes relaying to the latest client of the store after a restart instead of
+//! creating a new one, and the `history` subcommand answers what was submitted, when and at
+//! what cost. Records are JSON values, submissions are keyed by a sequence number, trusted
+//! states by `<client_id>/<height>` (height big-endian, so they are ordered).
+//!
+//! The [`crate::indexer::Indexer`] keeps the IBC events it decoded there too, keyed by block
+//! number and log index, along with the next block to scan and the chain id of every client.
+//! Those are kept per deployment of the contracts (IBCHost and IBCHandler address), so that a
+//! store used against a redeployment starts indexing from scratch.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    error::Error,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use web3::types::{Address, H256, U256};
+
+use crate::indexer::IndexedEvent;
+
+const CLIENTS: &str = "clients";
+const SUBMISSIONS: &str = "submissions";
+const TRUSTED: &str = "trusted";
+const EVENTS: &str = "events";
+const CLIENT_CHAINS: &str = "client_chains";
+const CURSORS: &str = "cursors";
+
+/// Unix time (seconds)
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Client created by the relayer
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ClientRecord {
+    /// Position in the order the clients were recorded in
+    #[serde(default)]
+    pub id: u64,
+    pub client_id: String,
+    pub chain_id: String,
+    /// Height of the header the client was created with
+    pub height: u64,
+    pub created_at: u64,
+}
+
+/// Confirmed transaction
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Outcome {
+    /// Hash of the version that got mined (replacements change the hash)
+    pub tx_hash: H256,
+    pub success: bool,
+    pub gas_used: U256,
+    /// In units of `currency`, gateway fee included
+    pub fee: f64,
+    pub currency: String,
+    pub confirmed_at: u64,
+}
+
+/// Transaction submitted by the relayer, `outcome` is missing until it is confirmed
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Submission {
+    #[serde(default)]
+    pub id: u64,
+    pub method: String,
+    /// Missing for `registerClient`, and for `createClient` until it is confirmed
+    pub client_id: Option<String>,
+    pub height: Option<u64>,
+    pub trusted_height: Option<u64>,
+    pub nonce: U256,
+    pub submitted_at: u64,
+    pub outcome: Option<Outcome>,
+}
+
+/// Consensus state a client holds thanks to a confirmed update (or its creation)
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TrustedState {
+    pub height: u64,
+    /// Unix time (seconds) of the header
+    pub time: f64,
+    pub tx_hash: H256,
+}
+
+/// Deployment of the contracts the indexed data belongs to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IndexScope {
+    pub host: Address,
+    pub handler: Address,
+}
+
+impl IndexScope {
+    /// Prefix of the keys of the deployment
+    fn key(&self, suffix: &[u8]) -> Vec<u8> {
+        let mut key = self.host.as_bytes().to_vec();
+        key.extend_from_slice(self.handler.as_bytes());
+        key.extend_from_slice(suffix);
+        key
+    }
+}
+
+pub struct Store {
+    db: sled::Db,
+    clients: sled::Tree,
+    submissions: sled::Tree,
+    trusted: sled::Tree,
+    events: sled::Tree,
+    client_chains: sled::Tree,
+    cursors: sled::Tree,
+}
+
+fn trusted_key(client_id: &str, height: u64) -> Vec<u8> {
+    let mut key = format!("{}/", client_id).into_bytes();
+    key.extend_from_slice(&height.to_be_bytes());
+    key
+}
+
+fn event_key(block_number: u64, log_index: u64) -> Vec<u8> {
+    let mut key = block_number.to_be_bytes().to_vec();
+    key.extend_from_slice(&log_index.to_be_bytes());
+    key
+}
+
+fn decode<V: DeserializeOwned>(value: &[u8]) -> Result<V, Box<dyn Error>> {
+    Ok(serde_json::from_slice(value)?)
+}
+
+impl Store {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let db =
+            sled::open(path.as_ref()).map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+
+        Self::with_db(db)
+    }
+
+    /// Store deleted when dropped, for runs without `--state-db`
+    pub fn temporary() -> Result<Self, Box<dyn Error>> {
+        Self::with_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn with_db(db: sled::Db) -> Result<Self, Box<dyn Error>> {
+        Ok(Store {
+            clients: db.open_tree(CLIENTS)?,
+            submissions: db.open_tree(SUBMISSIONS)?,
+            trusted: db.open_tree(TRUSTED)?,
+            events: db.open_tree(EVENTS)?,
+            client_chains: db.open_tree(CLIENT_CHAINS)?,
+            cursors: db.open_tree(CURSORS)?,
+            db,
+        })
+    }
+
+    /// Writes are flushed right away, the record has to survive the crash that follows it
+    fn put<V: Serialize>(
+        &self,
+        tree: &sled::Tree,
+        key: &[u8],
+        value: &V,
+    ) -> Result<(), Box<dyn Error>> {
+        tree.insert(key, serde_json::to_vec(value)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Records the client, returns its id
+    pub fn add_client(&self, client: &ClientRecord) -> Result<u64, Box<dyn Error>> {
+        let id = self.db.generate_id()?;
+        let client = ClientRecord {
+            id,
+            ..client.clone()
+        };
+        self.put(&self.clients, client.client_id.as_bytes(), &client)?;
+
+        Ok(id)
+    }
+
+    /// Clients created so far, oldest first (records predating the id by creation time)
+    pub fn clients(&self) -> Result<Vec<ClientRecord>, Box<dyn Error>> {
+        let mut clients = self
+            .clients
+            .iter()
+            .values()
+            .map(|value| decode(&value?))
+            .collect::<Result<Vec<ClientRecord>, _>>()?;
+        clients.sort_by_key(|client| (client.id, client.created_at));
+
+        Ok(clients)
+    }
+
+    /// Records the submission, returns its id
+    pub fn add_submission(&self, submission: &Submission) -> Result<u64, Box<dyn Error>> {
+        let id = self.db.generate_id()?;
+        let submission = Submission {
+            id,
+            ..submission.clone()
+        };
+        self.put(&self.submissions, &id.to_be_bytes(), &submission)?;
+
+        Ok(id)
+    }
+
+    /// Records the outcome of the submission, and the client it created if it is a
+    /// `createClient`
+    pub fn set_outcome(
+        &self,
+        id: u64,
+        client_id: Option<&str>,
+        outcome: Outcome,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut submission: Submission = decode(
+            &self
+                .submissions
+                .get(id.to_be_bytes())?
+                .ok_or_else(|| format!("no submission {} in the state db", id))?,
+        )?;
+        if let Some(client_id) = client_id {
+            submission.client_id = Some(client_id.to_string());
+        }
+        submission.outcome = Some(outcome);
+
+        self.put(&self.submissions, &id.to_be_bytes(), &submission)
+    }
+
+    /// Submissions in the order they were made, only those to `client_id` if given
+    pub fn submissions(&self, client_id: Option<&str>) -> Result<Vec<Submission>, Box<dyn Error>> {
+        let submissions = self
+            .submissions
+            .iter()
+            .values()
+            .map(|value| decode(&value?))
+            .collect::<Result<Vec<Submission>, _>>()?;
+
+        Ok(submissions
+            .into_iter()
+            .filter(|submission| {
+                client_id.is_none() || submission.client_id.as_deref() == client_id
+            })
+            .collect())
+    }
+
+    pub fn add_trusted(&self, client_id: &str, state: &TrustedState) -> Result<(), Box<dyn Error>> {
+        self.put(&self.trusted, &trusted_key(client_id, state.height), state)
+    }
+
+    /// Trusted states of the client, lowest height first
+    pub fn trusted(&self, client_id: &str) -> Result<Vec<TrustedState>, Box<dyn Error>> {
+        self.trusted
+            .scan_prefix(format!("{}/", client_id).as_bytes())
+            .values()
+            .map(|value| decode(&value?))
+            .collect()
+    }
+
+    /// Next block the indexer scans, `None` before the first scan
+    pub fn index_cursor(&self, scope: &IndexScope) -> Result<Option<u64>, Box<dyn Error>> {
+        self.cursors
+            .get(scope.key(&[]))?
+            .map(|value| decode(&value))
+            .transpose()
+    }
+
+    /// Records the events of a scanned block range and moves the cursor past it. Events are
+    /// keyed by their position in the chain, so scanning a range again after a crash between
+    /// the two writes does not duplicate them.
+    pub fn add_events(
+        &self,
+        scope: &IndexScope,
+        events: &[IndexedEvent],
+        next_block: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        for event in events {
+            self.events.insert(
+                scope.key(&event_key(event.block_number, event.log_index)),
+                serde_json::to_vec(event)?,
+            )?;
+        }
+        self.put(&self.cursors, &scope.key(&[]), &next_block)
+    }
+
+    /// Indexed events from `from_block` on, in chain order
+    pub fn events(
+        &self,
+        scope: &IndexScope,
+        from_block: u64,
+    ) -> Result<Vec<IndexedEvent>, Box<dyn Error>> {
+        self.events
+            .range(scope.key(&event_key(from_block, 0))..=scope.key(&event_key(u64::MAX, u64::MAX)))
+            .values()
+            .map(|value| decode(&value?))
+            .collect()
+    }
+
+    pub fn set_client_chain(
+        &self,
+        scope: &IndexScope,
+        client_id: &str,
+        chain_id: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.put(
+            &self.client_chains,
+            &scope.key(client_id.as_bytes()),
+            &chain_id,
+        )
+    }
+
+    /// Chain id of the client, `None` if the indexer has not seen it
+    pub fn client_chain(
+        &self,
+        scope: &IndexScope,
+        client_id: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        self.client_chains
+            .get(scope.key(client_id.as_bytes()))?
+            .map(|value| decode(&value))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::IbcEvent;
+
+    fn scope(byte: u8) -> IndexScope {
+        IndexScope {
+            host: Address::repeat_byte(byte),
+            handler: Address::repeat_byte(byte + 1),
+        }
+    }
+
+    fn client_event(block_number: u64, log_index: u64, client_id: &str) -> IndexedEvent {
+        IndexedEvent {
+            block_number,
+            log_index,
+            tx_hash: H256::repeat_byte(block_number as u8),
+            event: IbcEvent::GeneratedClientIdentifier {
+                client_id: client_id.to_string(),
+            },
+        }
+    }
+
+    fn client_ids(events: Vec<IndexedEvent>) -> Vec<String> {
+        events
+            .into_iter()
+            .map(|indexed| match indexed.event {
+                IbcEvent::GeneratedClientIdentifier { client_id } => client_id,
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect()
+    }
+
+    fn client(client_id: &str, created_at: u64) -> ClientRecord {
+        ClientRecord {
+            id: 0,
+            client_id: client_id.to_string(),
+            chain_id: "test-chain".to_string(),
+            height: 28,
+            created_at,
+        }
+    }
+
+    fn trusted_state(height: u64) -> TrustedState {
+        TrustedState {
+            height,
+            time: 1_650_000_000.0,
+            tx_hash: H256::repeat_byte(height as u8),
+        }
+    }
+
+    fn outcome() -> Outcome {
+        Outcome {
+            tx_hash: H256::repeat_byte(1),
+            success: true,
+            gas_used: 100_000.into(),
+            fee: 0.01,
+            currency: "CELO".to_string(),
+            confirmed_at: 1_650_000_000,
+        }
+    }
+
+    #[test]
+    fn trusted_states_of_a_client_only() {
+        let store = Store::temporary().unwrap();
+        for height in &[300, 2, 256] {
+            store
+                .add_trusted("07-tendermint-1", &trusted_state(*height))
+                .unwrap();
+        }
+        store
+            .add_trusted("07-tendermint-10", &trusted_state(5))
+            .unwrap();
+        store
+            .add_trusted("07-tendermint-0", &trusted_state(7))
+            .unwrap();
+
+        let heights = |client_id| -> Vec<u64> {
+            store
+                .trusted(client_id)
+                .unwrap()
+                .iter()
+                .map(|state| state.height)
+                .collect()
+        };
+        assert_eq!(heights("07-tendermint-1"), vec![2, 256, 300]);
+        assert_eq!(heights("07-tendermint-10"), vec![5]);
+        assert!(heights("07-tendermint-2").is_empty());
+    }
+
+    #[test]
+    fn adding_events_again_does_not_duplicate_them() {
+        let store = Store::temporary().unwrap();
+        let scope = scope(1);
+        let events = [
+            client_event(10, 0, "07-tendermint-0"),
+            client_event(10, 1, "07-tendermint-1"),
+            client_event(256, 0, "07-tendermint-2"),
+        ];
+
+        store.add_events(&scope, &events[..2], 200).unwrap();
+        // the range is scanned again after a crash
+        store.add_events(&scope, &events, 300).unwrap();
+
+        assert_eq!(store.index_cursor(&scope).unwrap(), Some(300));
+        assert_eq!(
+            client_ids(store.events(&scope, 0).unwrap()),
+            vec!["07-tendermint-0", "07-tendermint-1", "07-tendermint-2"]
+        );
+        assert_eq!(
+            client_ids(store.events(&scope, 11).unwrap()),
+            vec!["07-tendermint-2"]
+        );
+    }
+
+    #[test]
+    fn outcome_of_a_submission() {
+        let store = Store::temporary().unwrap();
+        let id = store
+            .add_submission(&Submission {
+                id: 0,
+                method: "createClient".to_string(),
+                client_id: None,
+                height: Some(28),
+                trusted_height: None,
+                nonce: 3.into(),
+                submitted_at: 1_650_000_000,
+                outcome: None,
+            })
+            .unwrap();
+
+        assert!(store.set_outcome(id + 1, None, outcome()).is_err());
+        assert!(store.submissions(None).unwrap()[0].outcome.is_none());
+
+        store
+            .set_outcome(id, Some("07-tendermint-0"), outcome())
+            .unwrap();
+        let submissions = store.submissions(Some("07-tendermint-0")).unwrap();
+        assert_eq!(submissions.len(), 1);
+        assert_eq!(submissions[0].id, id);
+        assert!(submissions[0].outcome.as_ref().unwrap().success);
+    }
+
+    #[test]
+    fn clients_in_creation_order() {
+        let store = Store::temporary().unwrap();
+        // created within the same second, and in an order their ids do not sort in
+        for client_id in &["07-tendermint-9", "07-tendermint-10", "07-tendermint-1"] {
+            store.add_client(&client(client_id, 1_650_000_000)).unwrap();
+        }
+
+        let clients: Vec<String> = store
+            .clients()
+            .unwrap()
+            .into_iter()
+            .map(|client| client.client_id)
+            .collect();
+        assert_eq!(
+            clients,
+            vec!["07-tendermint-9", "07-tendermint-10", "07-tendermint-1"]
+        );
+    }
+
+    #[test]
+    fn index_is_kept_per_deployment() {
+        let store = Store::temporary().unwrap();
+        let (old, new) = (scope(1), scope(3));
+
+        store
+            .add_events(&old, &[client_event(10, 0, "07-tendermint-0")], 100)
+            .unwrap();
+        store
+            .set_client_chain(&old, "07-tendermint-0", "a")
+            .unwrap();
+        assert_eq!(store.index_cursor(&new).unwrap(), None);
+        assert!(store.events(&new, 0).unwrap().is_empty());
+        assert_eq!(store.client_chain(&new, "07-tendermint-0").unwrap(), None);
+
+        store
+            .add_events(&new, &[client_event(50, 0, "07-tendermint-0")], 60)
+            .unwrap();
+        store
+            .set_client_chain(&new, "07-tendermint-0", "b")
+            .unwrap();
+        assert_eq!(store.index_cursor(&old).unwrap(), Some(100));
+        assert_eq!(store.index_cursor(&new).unwrap(), Some(60));
+        assert_eq!(store.events(&old, 0).unwrap()[0].block_number, 10);
+        assert_eq!(store.events(&new, 0).unwrap()[0].block_number, 50);
+        assert_eq!(
+            store
+                .client_chain(&old, "07-tendermint-0")
+                .unwrap()
+                .as_deref(),
+            Some("a")
+        );
+        assert_eq!(
+            client_ids(store.events(&new, 0).unwrap()),
+            vec!["07-tendermint-0"]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Synthetic tendermint chain: validator keys, headers and signed commits generated locally,
+//! for validator sets and voting patterns real chains rarely produce.
+//!
+//! Headers carry the real `validators_hash`/`next_validators_hash` and the commits are
+//! `CanonicalVote`s signed with the validators' ed25519 keys, so the light client verifies them
+//! exactly like headers of a live chain. The generation is deterministic for a given seed.
+use ed25519_dalek::{Keypair, PublicKey as DalekPublicKey, SecretKey, Signer};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    error::Error,
+    str::FromStr,
+};
+use tendermint::{
+    account,
+    block::{self, header::Version, parts, signed_header::SignedHeader, Commit, CommitSig, Header},
+    chain, merkle, validator, vote, AppHash, Hash, PublicKey, Time,
+};
+
+use crate::{proto::tendermint::light::TmHeader, types};
+
+/// Unix time of the first generated block
+pub const DEFAULT_GENESIS_TIME: i64 = 1_640_000_000;
+
+/// How the voting power is spread over the validators (`i` is the validator's slot)
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerDistribution {
+    /// Every validator has the same power
+    Equal,
+    /// Power decreasing linearly with the slot, `n - i`
+    Linear,
+    /// Long tail like the Cosmos Hub, power proportional to `1 / (i + 1)`
+    Zipf,
+}
+
+impl PowerDistribution {
+    fn power(&self, slot: usize, validators: usize) -> u64 {
+        match self {
+            PowerDistribution::Equal => 1_000,
+            PowerDistribution::Linear => 1_000 * (validators - slot) as u64,
+            PowerDistribution::Zipf => 1_000_000 / (slot as u64 + 1),
+        }
+    }
+}
+
+impl FromStr for PowerDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equal" => Ok(PowerDistribution::Equal),
+            "linear" => Ok(PowerDistribution::Linear),
+            "zipf" => Ok(PowerDistribution::Zipf),
+            _ => Err(format!(
+                "unknown power distribution {}, expected equal, linear or zipf",
+                s
+            )),
+        }
+    }
+}
+
+/// Parameters of the generated chain
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ChainConfig {
+    pub chain_id: String,
+    pub validators: usize,
+    pub power: PowerDistribution,
+    /// Part of the validators (by count) missing from every commit
+    pub absent: f64,
+    /// Part of the validators (by count) precommitting nil in every commit
+    pub nil: f64,
+    /// Validators replaced by new ones at every height (visible in `next_validators_hash`)
+    pub churn: usize,
+    pub seed: u64,
+    /// Chains differing only in `fork` have the same validators but different application
+    /// states, so their headers conflict at every height
+    pub fork: u64,
+    pub start_height: u64,
+    /// Unix time of the block at `start_height`
+    pub start_time: i64,
+    /// Seconds between two blocks
+    pub block_time: i64,
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        ChainConfig {
+            chain_id: "synthetic".to_string(),
+            validators: 4,
+            power: PowerDistribution::Equal,
+            absent: 0.0,
+            nil: 0.0,
+            churn: 0,
+            seed: 0,
+            fork: 0,
+            start_height: 1,
+            start_time: DEFAULT_GENESIS_TIME,
+            block_time: 5,
+        }
+    }
+}
+
+/// splitmix64, enough to pick keys and votes reproducibly
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn keypair(&mut self) -> Keypair {
+        let mut seed = [0u8; 32];
+        for chunk in seed.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next().to_le_bytes());
+        }
+        let secret = SecretKey::from_bytes(&seed).expect("any 32 bytes are a valid secret key");
+        let public = DalekPublicKey::from(&secret);
+
+        Keypair { secret, public }
+    }
+
+    /// `count` distinct indices out of `0..n`
+    fn sample(&mut self, n: usize, count: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..n).collect();
+        for i in 0..count.min(n) {
+            let j = i + (self.next() % (n - i) as u64) as usize;
+            indices.swap(i, j);
+        }
+        indices.truncate(count.min(n));
+        indices
+    }
+}
+
+/// Validator slot: the key currently holding it and the power assigned to it
+struct Slot {
+    keypair: Keypair,
+    power: u64,
+}
+
+impl Slot {
+    fn info(&self) -> Result<validator::Info, Box<dyn Error>> {
+        let public_key = PublicKey::from_raw_ed25519(self.keypair.public.as_bytes())
+            .ok_or("invalid ed25519 public key")?;
+
+        Ok(validator::Info::new(
+            public_key,
+            vote::Power::try_from(self.power)?,
+        ))
+    }
+}
+
+fn validator_set(slots: &[Slot]) -> Result<validator::Set, Box<dyn Error>> {
+    Ok(validator::Set::without_proposer(
+        slots
+            .iter()
+            .map(Slot::info)
+            .collect::<Result<Vec<_>, _>>()?,
+    ))
+}
+
+/// Block produced by the generator, in tendermint and light client types
+pub struct SyntheticBlock {
+    pub signed_header: SignedHeader,
+    pub validators: validator::Set,
+    pub tm_header: TmHeader,
+}
+
+/// Generator of consecutive blocks
+pub struct SyntheticChain {
+    config: ChainConfig,
+    rng: Rng,
+    chain_id: chain::Id,
+    /// Validators of the next block
+    current: Vec<Slot>,
+    height: u64,
+    last_block_id: Option<block::Id>,
+}
+
+impl SyntheticChain {
+    pub fn new(config: ChainConfig) -> Result<Self, Box<dyn Error>> {
+        if config.validators == 0 {
+            return Err("at least one validator is required".into());
+        }
+        if config.start_height == 0 {
+            return Err("heights start at 1".into());
+        }
+        if config.absent < 0.0 || config.nil < 0.0 || config.absent + config.nil > 1.0 {
+            return Err("absent and nil parts must be non-negative and sum up to at most 1".into());
+        }
+
+        let mut rng = Rng(config.seed);
+        let current = (0..config.validators)
+            .map(|slot| Slot {
+                keypair: rng.keypair(),
+                power: config.power.power(slot, config.validators),
+            })
+            .collect();
+
+        Ok(SyntheticChain {
+            chain_id: chain::Id::try_from(config.chain_id.as_str())?,
+            height: config.start_height,
+            last_block_id: None,
+            rng,
+            current,
+            config,
+        })
+    }
+
+    /// Height of the block `next_block` returns
+    pub fn next_height(&self) -> u64 {
+        self.height
+    }
+
+    /// Validators with `churn` of them replaced by fresh keys, keeping the slots' power
+    fn churned(&mut self) -> Vec<Slot> {
+        let replaced = self.rng.sample(self.current.len(), self.config.churn);
+
+        let rng = &mut self.rng;
+        self.current
+            .iter()
+            .enumerate()
+            .map(|(slot, validator)| Slot {
+                keypair: if replaced.contains(&slot) {
+                    rng.keypair()
+                } else {
+                    Keypair::from_bytes(&validator.keypair.to_bytes()).unwrap()
+                },
+                power: validator.power,
+            })
+            .collect()
+    }
+
+    fn time(&self, height: u64, offset_seconds: i64) -> Result<Time, Box<dyn Error>> {
+        let seconds = self.config.start_time
+            + (height - self.config.start_height) as i64 * self.config.block_time
+            + offset_seconds;
+
+        Ok(Time::from_unix_timestamp(seconds, 0)?)
+    }
+
+    /// Next block, signed by the current validators
+    pub fn next_block(&mut self) -> Result<SyntheticBlock, Box<dyn Error>> {
+        let height = self.height;
+        let next = self.churned();
+        let validators = validator_set(&self.current)?;
+        let next_validators = validator_set(&next)?;
+
+        let empty_hash = Hash::Sha256(merkle::simple_hash_from_byte_vectors(Vec::new()));
+        let proposer = &validators.validators()[height as usize % validators.validators().len()];
+        let header = Header {
+            version: Version { block: 11, app: 0 },
+            chain_id: self.chain_id.clone(),
+            height: height.try_into()?,
+            time: self.time(height, 0)?,
+            last_block_id: self.last_block_id,
+            last_commit_hash: Some(empty_hash),
+            data_hash: Some(empty_hash),
+            validators_hash: validators.hash(),
+            next_validators_hash: next_validators.hash(),
+            consensus_hash: empty_hash,
+            app_hash: AppHash::try_from(
+                merkle::simple_hash_from_byte_vectors(vec![
+                    height.to_be_bytes().to_vec(),
+                    self.config.fork.to_be_bytes().to_vec(),
+                ])
+                .to_vec(),
+            )?,
+            last_results_hash: Some(empty_hash),
+            evidence_hash: Some(empty_hash),
+            proposer_address: proposer.address,
+        };
+        let block_id = block::Id {
+            hash: header.hash(),
+            part_set_header: parts::Header::new(1, empty_hash)?,
+        };
+
+        let commit = self.commit(&header, block_id, &validators)?;
+        let signed_header = SignedHeader::new(header, commit)?;
+
+        let tm_header = types::to_light_block(
+            &types::to_signed_header(&signed_header),
+            &types::to_validator_set(validators.validators()),
+        );
+
+        self.current = next;
+        self.height += 1;
+        self.last_block_id = Some(block_id);
+
+        Ok(SyntheticBlock {
+            signed_header,
+            validators,
+            tm_header,
+        })
+    }
+
+    /// Precommits of the validators (in validator set order), `absent`/`nil` parts of them
+    /// picked at random
+    fn commit(
+        &mut self,
+        header: &Header,
+        block_id: block::Id,
+        validators: &validator::Set,
+    ) -> Result<Commit, Box<dyn Error>> {
+        let n = validators.validators().len();
+        let absent_count = (self.config.absent * n as f64).round() as usize;
+        let nil_count = ((self.config.nil * n as f64).round() as usize).min(n - absent_count);
+        let skipped = self.rng.sample(n, absent_count + nil_count);
+        let (absent, nil) = skipped.split_at(absent_count);
+
+        let keys: HashMap<account::Id, &Keypair> = self
+            .current
+            .iter()
+            .map(|slot| {
+                (
+                    account::Id::from(
+                        PublicKey::from_raw_ed25519(slot.keypair.public.as_bytes())
+                            .and_then(|key| key.ed25519())
+                            .unwrap(),
+                    ),
+                    &slot.keypair,
+                )
+            })
+            .collect();
+
+        let timestamp = self.time(header.height.value(), 1)?;
+        let signatures = validators
+            .validators()
+            .iter()
+            .enumerate()
+            .map(|(index, validator)| {
+                if absent.contains(&index) {
+                    return Ok(CommitSig::BlockIdFlagAbsent);
+                }
+
+                let is_nil = nil.contains(&index);
+                let vote = vote::Vote {
+                    vote_type: vote::Type::Precommit,
+                    height: header.height,
+                    round: block::Round::default(),
+                    block_id: if is_nil { None } else { Some(block_id) },
+                    timestamp: Some(timestamp),
+                    validator_address: validator.address,
+                    validator_index: index.try_into()?,
+                    signature: None,
+                };
+                let sign_bytes = vote.to_signable_vec(self.chain_id.clone())?;
+                let signature = tendermint::Signature::new(
+                    keys[&validator.address].sign(&sign_bytes).to_bytes(),
+                )?;
+
+                Ok(if is_nil {
+                    CommitSig::BlockIdFlagNil {
+                        validator_address: validator.address,
+                        timestamp,
+                        signature,
+                    }
+                } else {
+                    CommitSig::BlockIdFlagCommit {
+                        validator_address: validator.address,
+                        timestamp,
+                        signature,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+        Ok(Commit {
+            height: header.height,
+            round: block::Round::default(),
+            block_id,
+            signatures,
+        })
+    }
+}
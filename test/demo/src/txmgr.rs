@@ -0,0 +1,842 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+use web3::{
+    contract::Options,
+    types::{Address, BlockNumber, TransactionReceipt, H256, U256},
+    Transport, Web3,
+};
+
+use crate::{
+    celo::{CeloTransaction, RpcCeloTransaction},
+    gas::{self, GasConfig, GasError, GasOracle},
+    signer::Signer,
+};
+
+/// Shape of the `txpool_content` response: account => nonce => tx
+type TxpoolContent = BTreeMap<String, BTreeMap<Address, BTreeMap<String, RpcCeloTransaction>>>;
+
+/// Tuning knobs of the transaction manager
+#[derive(Clone, Debug)]
+pub struct TxManagerConfig {
+    /// Number of blocks on top of the inclusion block before a tx is considered final
+    pub confirmations: usize,
+    /// How long a tx may sit in the mempool before it gets replaced with a higher gas price
+    pub replace_after: Duration,
+    /// Gas price increase (in percent) applied on every replacement
+    pub gas_bump_percent: u64,
+    /// Upper bound for the bumped gas price (wei), `None` means unbounded
+    pub max_gas_price: Option<U256>,
+    /// Receipt polling interval
+    pub poll_interval: Duration,
+    /// How long `wait` keeps polling and replacing a tx before giving up on it
+    pub confirm_timeout: Duration,
+    /// Token the fees are paid in (e.g. cUSD), `None` means CELO
+    pub fee_currency: Option<Address>,
+    /// Full node operator receiving the gateway fee
+    pub gateway_fee_recipient: Option<Address>,
+    /// Gateway fee paid on top of the gas (in the fee currency)
+    pub gateway_fee: U256,
+    /// Gas limit and gas price estimation
+    pub gas: GasConfig,
+}
+
+impl Default for TxManagerConfig {
+    fn default() -> Self {
+        TxManagerConfig {
+            confirmations: 1,
+            replace_after: Duration::from_secs(60),
+            gas_bump_percent: 20,
+            max_gas_price: None,
+            poll_interval: Duration::from_secs(1),
+            confirm_timeout: Duration::from_secs(30 * 60),
+            fee_currency: None,
+            gateway_fee_recipient: None,
+            gateway_fee: U256::zero(),
+            gas: GasConfig::default(),
+        }
+    }
+}
+
+/// Transaction sent by the manager that has not been confirmed yet
+#[derive(Clone, Debug)]
+pub struct PendingTx {
+    /// Most recent submission (same nonce for every replacement)
+    pub tx: CeloTransaction,
+    /// Hashes of every submitted version of the tx; any of them may end up mined
+    pub hashes: Vec<H256>,
+    /// Time of the most recent submission
+    pub sent_at: Instant,
+}
+
+/// Assigns nonces locally, so that several transactions can be in flight at once, and
+/// replaces the ones stuck in the mempool with bumped gas prices.
+pub struct TxManager<'a, T: Transport> {
+    web3: Web3<T>,
+    gas: GasOracle<T>,
+    signer: &'a dyn Signer,
+    config: TxManagerConfig,
+    chain_id: u64,
+    next_nonce: Mutex<U256>,
+    pending: Mutex<BTreeMap<U256, PendingTx>>,
+    // latest gas estimate per function selector
+    estimates: Mutex<HashMap<Vec<u8>, U256>>,
+}
+
+impl<'a, T: Transport> TxManager<'a, T> {
+    /// Creates the manager and reconciles its nonce with the node
+    pub async fn new(
+        transport: T,
+        signer: &'a dyn Signer,
+        config: TxManagerConfig,
+    ) -> web3::Result<TxManager<'a, T>> {
+        let web3 = Web3::new(transport.clone());
+        let gas = GasOracle::new(transport, config.gas.clone(), config.fee_currency);
+        let chain_id = web3.eth().chain_id().await?.as_u64();
+
+        let manager = TxManager {
+            web3,
+            gas,
+            signer,
+            config,
+            chain_id,
+            next_nonce: Mutex::new(U256::zero()),
+            pending: Mutex::new(BTreeMap::new()),
+            estimates: Mutex::new(HashMap::new()),
+        };
+        manager.reconcile().await?;
+
+        Ok(manager)
+    }
+
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Nonces of the transactions that are still awaiting confirmation
+    pub fn pending_nonces(&self) -> Vec<U256> {
+        self.pending.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Brings the local state in line with the node, e.g. after a restart.
+    ///
+    /// The next nonce is taken from the node's pending transaction count, and transactions
+    /// of ours that are still sitting in the node's pool are adopted, so that they get
+    /// replaced like any other stuck transaction once `wait` is called for them.
+    /// Returns the node's pending transaction count of the account.
+    pub async fn reconcile(&self) -> web3::Result<U256> {
+        let address = self.address();
+        let eth = self.web3.eth();
+
+        let mined = eth
+            .transaction_count(address, Some(BlockNumber::Latest))
+            .await?;
+        let queued = eth
+            .transaction_count(address, Some(BlockNumber::Pending))
+            .await?;
+
+        // txpool namespace is optional, not every node exposes it
+        let in_pool = if queued > mined {
+            let content = self
+                .web3
+                .transport()
+                .execute("txpool_content", vec![])
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_value::<TxpoolContent>(content).ok())
+                .unwrap_or_default();
+
+            content
+                .into_values()
+                .filter_map(|mut txs| txs.remove(&address))
+                .flat_map(|txs| txs.into_values())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut pending = self.pending.lock().unwrap();
+        for tx in in_pool {
+            pending.entry(tx.nonce).or_insert_with(|| PendingTx {
+                hashes: vec![tx.hash],
+                tx: tx.into_transaction(self.chain_id),
+                sent_at: Instant::now(),
+            });
+        }
+
+        let mut next_nonce = self.next_nonce.lock().unwrap();
+        *next_nonce = std::cmp::max(*next_nonce, queued);
+        if let Some(highest) = pending.keys().next_back() {
+            *next_nonce = std::cmp::max(*next_nonce, highest + 1);
+        }
+
+        Ok(queued)
+    }
+
+    /// Signs and broadcasts a call with the next free nonce, without waiting for it.
+    ///
+    /// Unless `options.gas` is set, the gas limit is estimated with `eth_estimateGas` (plus
+    /// the configured margin), and a call that does not fit into a block is refused.
+    /// Returns the assigned nonce which identifies the transaction in `wait`.
+    pub async fn submit(
+        &self,
+        method: &str,
+        to: Address,
+        data: Vec<u8>,
+        options: Options,
+    ) -> Result<U256, GasError> {
+        let value = options.value.unwrap_or_default();
+        let gas = match options.gas {
+            Some(gas) => gas,
+            None => {
+                let estimate = match self.config.gas.gas_limit {
+                    Some(_) => None,
+                    None => Some(self.estimate(to, &data, value).await?),
+                };
+                self.gas.gas_limit(method, estimate).await?
+            }
+        };
+        let gas_price = match options.gas_price {
+            Some(gas_price) => gas_price,
+            None => self.gas.gas_price().await?,
+        };
+
+        let nonce = {
+            let mut next_nonce = self.next_nonce.lock().unwrap();
+            let nonce = options.nonce.unwrap_or(*next_nonce);
+            *next_nonce = std::cmp::max(*next_nonce, nonce + 1);
+            nonce
+        };
+
+        let tx = CeloTransaction {
+            nonce,
+            gas_price,
+            gas,
+            fee_currency: self.config.fee_currency,
+            gateway_fee_recipient: self.config.gateway_fee_recipient,
+            gateway_fee: self.config.gateway_fee,
+            to: Some(to),
+            value,
+            data,
+            chain_id: self.chain_id,
+        };
+
+        let hash = match self.broadcast(tx.clone()).await {
+            Ok(hash) => hash,
+            // the node answered with an error, so the tx has not taken the nonce
+            Err(e @ web3::Error::Rpc(_)) => {
+                self.release_nonce(nonce);
+                return Err(e.into());
+            }
+            // the tx may have reached the node before the request failed, only the node's
+            // transaction count tells whether the nonce has been taken
+            Err(e) => {
+                match self.reconcile().await {
+                    Ok(queued) if queued <= nonce => self.release_nonce(nonce),
+                    Ok(_) => {}
+                    Err(reconcile_error) => warn!(
+                        nonce = %nonce,
+                        error = %reconcile_error,
+                        "could not tell whether a failed submission reached the node"
+                    ),
+                }
+                return Err(e.into());
+            }
+        };
+        debug!(
+            method,
+            nonce = %nonce,
+            gas = %gas,
+            gas_price = %gas_price,
+            tx_hash = ?hash,
+            "submitted tx"
+        );
+        self.pending.lock().unwrap().insert(
+            nonce,
+            PendingTx {
+                tx,
+                hashes: vec![hash],
+                sent_at: Instant::now(),
+            },
+        );
+
+        Ok(nonce)
+    }
+
+    /// Waits until the transaction with the given nonce is confirmed.
+    ///
+    /// Every `replace_after` without a receipt the transaction is re-signed with the same
+    /// nonce and a gas price bumped by `gas_bump_percent` (but not below the node's current
+    /// gas price), which replaces the underpriced version in the mempool. Without a receipt
+    /// after `confirm_timeout` (e.g. the tx was dropped from the pool) an error is returned, the
+    /// tx stays pending.
+    pub async fn wait(&self, nonce: U256) -> web3::Result<TransactionReceipt> {
+        let started = Instant::now();
+        loop {
+            let entry = match self.pending.lock().unwrap().get(&nonce) {
+                Some(entry) => entry.clone(),
+                None => {
+                    return Err(web3::Error::InvalidResponse(format!(
+                        "no pending transaction with nonce {}",
+                        nonce
+                    )))
+                }
+            };
+
+            for hash in entry.hashes.iter().rev() {
+                if let Some(receipt) = self.confirmed_receipt(*hash).await? {
+                    self.pending.lock().unwrap().remove(&nonce);
+                    return Ok(receipt);
+                }
+            }
+
+            if started.elapsed() >= self.config.confirm_timeout {
+                return Err(web3::Error::InvalidResponse(format!(
+                    "tx with nonce {} not confirmed after {} s (hashes {:?})",
+                    nonce,
+                    self.config.confirm_timeout.as_secs(),
+                    entry.hashes
+                )));
+            }
+            if entry.sent_at.elapsed() >= self.config.replace_after {
+                self.replace(nonce, entry).await?;
+            }
+
+            sleep(self.config.poll_interval).await;
+        }
+    }
+
+    /// Submits the call and waits for its confirmation
+    pub async fn send(
+        &self,
+        method: &str,
+        to: Address,
+        data: Vec<u8>,
+        options: Options,
+    ) -> Result<TransactionReceipt, GasError> {
+        let nonce = self.submit(method, to, data, options).await?;
+        Ok(self.wait(nonce).await?)
+    }
+
+    /// Gas limit (incl. the safety margin) the call would be sent with, without sending it.
+    ///
+    /// Unlike `submit`, the estimate is always taken against the latest state, so calls
+    /// that revert there or do not fit into a block are reported as errors.
+    pub async fn estimate_gas(
+        &self,
+        method: &str,
+        to: Address,
+        data: &[u8],
+    ) -> Result<U256, GasError> {
+        let estimate = self
+            .gas
+            .estimate(self.address(), to, data, U256::zero())
+            .await?;
+
+        self.gas.gas_limit(method, Some(estimate)).await
+    }
+
+    /// Estimates the call against the latest state.
+    ///
+    /// While other txs are in flight the call may depend on their effects (e.g. an update
+    /// trusting a height that is still being submitted) and revert against the latest state,
+    /// it is estimated again on top of the pending txs then; a call that reverts there as well
+    /// is reported. If the estimation fails on the nonce or balance of the account, which the
+    /// txs in flight change, the latest estimate of the same function is reused.
+    async fn estimate(&self, to: Address, data: &[u8], value: U256) -> web3::Result<U256> {
+        let selector = data[..std::cmp::min(4, data.len())].to_vec();
+        let in_flight = !self.pending.lock().unwrap().is_empty();
+
+        let estimate = match self.gas.estimate(self.address(), to, data, value).await {
+            Err(e) if in_flight && gas::is_revert(&e) => self
+                .gas
+                .estimate_pending(self.address(), to, data, value)
+                .await
+                .map_err(|_| e),
+            result => result,
+        };
+
+        match estimate {
+            Ok(estimate) => {
+                self.estimates.lock().unwrap().insert(selector, estimate);
+                Ok(estimate)
+            }
+            Err(e) if in_flight && gas::is_nonce_or_balance(&e) => {
+                match self.estimates.lock().unwrap().get(&selector).cloned() {
+                    Some(estimate) => Ok(estimate),
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn confirmed_receipt(&self, hash: H256) -> web3::Result<Option<TransactionReceipt>> {
+        let eth = self.web3.eth();
+
+        let receipt = match eth.transaction_receipt(hash).await? {
+            Some(receipt) => receipt,
+            None => return Ok(None),
+        };
+        let included = match receipt.block_number {
+            Some(number) => number.as_u64(),
+            None => return Ok(None),
+        };
+
+        let head = eth.block_number().await?.as_u64();
+        if head + 1 < included + self.config.confirmations as u64 {
+            return Ok(None);
+        }
+
+        Ok(Some(receipt))
+    }
+
+    async fn replace(&self, nonce: U256, entry: PendingTx) -> web3::Result<()> {
+        let old_price = entry.tx.gas_price;
+        let mut new_price = old_price * (100 + self.config.gas_bump_percent) / 100;
+
+        let node_price = self.gas.gas_price().await?;
+        if node_price > new_price {
+            new_price = node_price;
+        }
+        if let Some(max) = self.config.max_gas_price {
+            new_price = std::cmp::min(new_price, max);
+        }
+
+        let mut pending = entry;
+        pending.sent_at = Instant::now();
+
+        if new_price > old_price {
+            pending.tx.gas_price = new_price;
+
+            // errors such as "nonce too low" mean one of the previous versions got mined
+            // in the meantime, so the next receipt poll is going to pick it up
+            if let Ok(hash) = self.broadcast(pending.tx.clone()).await {
+                info!(
+                    nonce = %nonce,
+                    old_gas_price = %old_price,
+                    gas_price = %new_price,
+                    tx_hash = ?hash,
+                    "replaced stuck tx"
+                );
+                pending.hashes.push(hash);
+            }
+        }
+
+        self.pending.lock().unwrap().insert(nonce, pending);
+
+        Ok(())
+    }
+
+    /// Hands the nonce back, unless it has been taken by another submission already
+    fn release_nonce(&self, nonce: U256) {
+        let mut next_nonce = self.next_nonce.lock().unwrap();
+        if *next_nonce == nonce + 1 {
+            *next_nonce = nonce;
+        }
+    }
+
+    async fn broadcast(&self, tx: CeloTransaction) -> web3::Result<H256> {
+        let raw = self.signer.sign_transaction(tx).await?;
+
+        self.web3.eth().send_raw_transaction(raw).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gas::GasConfig, signer::LocalSigner};
+    use futures::future::{self, Ready};
+    use secp256k1::key::SecretKey;
+    use serde_json::{json, Value};
+    use std::{collections::VecDeque, sync::Arc};
+    use web3::{
+        helpers,
+        signing::keccak256,
+        types::{Bytes, TransactionReceipt, U64},
+        RequestId,
+    };
+
+    const GAS_PRICE: u64 = 1_000_000_000;
+
+    /// Answer of the mock node to the next `eth_sendRawTransaction`
+    enum SendResult {
+        Accept,
+        /// JSON-RPC error response, the tx is dropped
+        Reject,
+        /// The tx reaches the pool but the response gets lost
+        LoseResponse,
+        /// The request never reaches the node
+        Unreachable,
+    }
+
+    #[derive(Default)]
+    struct MockNode {
+        mined_count: u64,
+        pool: BTreeMap<U256, RpcCeloTransaction>,
+        sent: Vec<(U256, H256, U256)>,
+        send_results: VecDeque<SendResult>,
+        // the n-th accepted tx (counting from 1) is mined as soon as it is sent
+        mine_on_send: Option<usize>,
+        receipts: HashMap<H256, u64>,
+        head: u64,
+    }
+
+    impl MockNode {
+        fn pending_count(&self) -> u64 {
+            self.pool
+                .keys()
+                .next_back()
+                .map(|nonce| nonce.as_u64() + 1)
+                .unwrap_or(self.mined_count)
+                .max(self.mined_count)
+        }
+
+        fn send_raw(&mut self, raw: &[u8]) -> web3::Result<Value> {
+            let rlp = rlp::Rlp::new(raw);
+            let nonce: U256 = rlp.val_at(0).unwrap();
+            let gas_price: U256 = rlp.val_at(1).unwrap();
+            let hash = H256::from(keccak256(raw));
+
+            match self.send_results.pop_front().unwrap_or(SendResult::Accept) {
+                SendResult::Reject => Err(web3::Error::Rpc(jsonrpc_core::Error::invalid_params(
+                    "insufficient funds for gas * price + value",
+                ))),
+                SendResult::Unreachable => {
+                    Err(web3::Error::Transport("connection refused".to_string()))
+                }
+                result => {
+                    self.sent.push((nonce, hash, gas_price));
+                    self.pool.insert(
+                        nonce,
+                        RpcCeloTransaction {
+                            hash,
+                            nonce,
+                            block_number: None,
+                            to: None,
+                            value: U256::zero(),
+                            gas_price,
+                            gas: U256::zero(),
+                            input: Bytes::default(),
+                            fee_currency: None,
+                            gateway_fee_recipient: None,
+                            gateway_fee: U256::zero(),
+                        },
+                    );
+                    if self.mine_on_send == Some(self.sent.len()) {
+                        self.receipts.insert(hash, self.head);
+                    }
+                    if let SendResult::LoseResponse = result {
+                        return Err(web3::Error::Transport("connection reset".to_string()));
+                    }
+                    Ok(json!(hash))
+                }
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct MockTransport {
+        node: Arc<std::sync::Mutex<MockNode>>,
+        address: Address,
+    }
+
+    impl std::fmt::Debug for MockTransport {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("MockTransport")
+        }
+    }
+
+    impl Transport for MockTransport {
+        type Out = Ready<web3::Result<Value>>;
+
+        fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, jsonrpc_core::Call) {
+            (0, helpers::build_request(0, method, params))
+        }
+
+        fn send(&self, _id: RequestId, request: jsonrpc_core::Call) -> Self::Out {
+            let call = match request {
+                jsonrpc_core::Call::MethodCall(call) => call,
+                request => panic!("unexpected request {:?}", request),
+            };
+            let params: Vec<Value> = call.params.parse().unwrap();
+            let mut node = self.node.lock().unwrap();
+
+            let result = match call.method.as_str() {
+                "eth_chainId" => Ok(json!(U64::from(44787))),
+                "eth_getTransactionCount" => Ok(match params[1].as_str() {
+                    Some("pending") => json!(U256::from(node.pending_count())),
+                    _ => json!(U256::from(node.mined_count)),
+                }),
+                "txpool_content" => {
+                    let txs: BTreeMap<String, RpcCeloTransaction> = node
+                        .pool
+                        .iter()
+                        .map(|(nonce, tx)| (nonce.to_string(), tx.clone()))
+                        .collect();
+                    Ok(json!({ "pending": { format!("{:?}", self.address): txs } }))
+                }
+                "eth_sendRawTransaction" => {
+                    let raw: Bytes = serde_json::from_value(params[0].clone()).unwrap();
+                    node.send_raw(&raw.0)
+                }
+                "eth_getTransactionReceipt" => {
+                    let hash: H256 = serde_json::from_value(params[0].clone()).unwrap();
+                    Ok(match node.receipts.get(&hash) {
+                        Some(block) => json!(TransactionReceipt {
+                            transaction_hash: hash,
+                            block_number: Some((*block).into()),
+                            ..Default::default()
+                        }),
+                        None => Value::Null,
+                    })
+                }
+                "eth_blockNumber" => Ok(json!(U64::from(node.head))),
+                method => panic!("unexpected call {}", method),
+            };
+
+            future::ready(result)
+        }
+    }
+
+    fn signer() -> LocalSigner {
+        LocalSigner::new(SecretKey::from_slice(&[0x46; 32]).unwrap())
+    }
+
+    fn config() -> TxManagerConfig {
+        TxManagerConfig {
+            poll_interval: Duration::from_millis(1),
+            gas: GasConfig {
+                gas_price: Some(GAS_PRICE.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn options() -> Options {
+        Options {
+            gas: Some(100_000.into()),
+            ..Default::default()
+        }
+    }
+
+    fn transport(signer: &LocalSigner, node: MockNode) -> MockTransport {
+        MockTransport {
+            node: Arc::new(std::sync::Mutex::new(node)),
+            address: signer.address(),
+        }
+    }
+
+    async fn submit<T: Transport>(manager: &TxManager<'_, T>) -> Result<U256, GasError> {
+        manager
+            .submit(
+                "updateClient",
+                Address::repeat_byte(1),
+                vec![1, 2, 3, 4],
+                options(),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn assigns_consecutive_nonces() {
+        let signer = signer();
+        let transport = transport(
+            &signer,
+            MockNode {
+                mined_count: 5,
+                ..Default::default()
+            },
+        );
+        let manager = TxManager::new(transport.clone(), &signer, config())
+            .await
+            .unwrap();
+
+        for expected in 5..8u64 {
+            assert_eq!(submit(&manager).await.unwrap(), expected.into());
+        }
+        assert_eq!(manager.pending_nonces(), vec![5.into(), 6.into(), 7.into()]);
+
+        let explicit = manager
+            .submit(
+                "updateClient",
+                Address::repeat_byte(1),
+                vec![],
+                Options {
+                    nonce: Some(10.into()),
+                    ..options()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(explicit, 10.into());
+        assert_eq!(submit(&manager).await.unwrap(), 11.into());
+    }
+
+    #[tokio::test]
+    async fn gives_nonce_back_on_rejection() {
+        let signer = signer();
+        let transport = transport(&signer, MockNode::default());
+        let manager = TxManager::new(transport.clone(), &signer, config())
+            .await
+            .unwrap();
+
+        transport
+            .node
+            .lock()
+            .unwrap()
+            .send_results
+            .push_back(SendResult::Reject);
+        assert!(submit(&manager).await.is_err());
+        assert!(manager.pending_nonces().is_empty());
+
+        assert_eq!(submit(&manager).await.unwrap(), 0.into());
+    }
+
+    #[tokio::test]
+    async fn keeps_nonce_of_tx_that_reached_the_node() {
+        let signer = signer();
+        let transport = transport(&signer, MockNode::default());
+        let manager = TxManager::new(transport.clone(), &signer, config())
+            .await
+            .unwrap();
+
+        transport
+            .node
+            .lock()
+            .unwrap()
+            .send_results
+            .push_back(SendResult::LoseResponse);
+        assert!(submit(&manager).await.is_err());
+        // adopted from the pool, so that it gets replaced like any other tx
+        assert_eq!(manager.pending_nonces(), vec![0.into()]);
+
+        assert_eq!(submit(&manager).await.unwrap(), 1.into());
+    }
+
+    #[tokio::test]
+    async fn gives_nonce_back_when_tx_did_not_reach_the_node() {
+        let signer = signer();
+        let transport = transport(&signer, MockNode::default());
+        let manager = TxManager::new(transport.clone(), &signer, config())
+            .await
+            .unwrap();
+
+        transport
+            .node
+            .lock()
+            .unwrap()
+            .send_results
+            .push_back(SendResult::Unreachable);
+        assert!(submit(&manager).await.is_err());
+
+        assert_eq!(submit(&manager).await.unwrap(), 0.into());
+    }
+
+    #[tokio::test]
+    async fn reconcile_adopts_pool_txs() {
+        let signer = signer();
+        let transport = transport(
+            &signer,
+            MockNode {
+                mined_count: 3,
+                ..Default::default()
+            },
+        );
+        // txs of a previous run still in the pool
+        for nonce in 3..5 {
+            let raw = sign(&signer, nonce).await;
+            transport.node.lock().unwrap().send_raw(&raw).unwrap();
+        }
+
+        let manager = TxManager::new(transport.clone(), &signer, config())
+            .await
+            .unwrap();
+        assert_eq!(manager.pending_nonces(), vec![3.into(), 4.into()]);
+        assert_eq!(submit(&manager).await.unwrap(), 5.into());
+
+        // nonces taken by another process using the same account
+        transport.node.lock().unwrap().mined_count = 9;
+        assert_eq!(manager.reconcile().await.unwrap(), 9.into());
+        assert_eq!(submit(&manager).await.unwrap(), 9.into());
+    }
+
+    #[tokio::test]
+    async fn wait_replaces_stuck_tx() {
+        let signer = signer();
+        let transport = transport(
+            &signer,
+            MockNode {
+                // only the replacement gets mined
+                mine_on_send: Some(2),
+                head: 7,
+                ..Default::default()
+            },
+        );
+        let manager = TxManager::new(
+            transport.clone(),
+            &signer,
+            TxManagerConfig {
+                replace_after: Duration::from_millis(0),
+                ..config()
+            },
+        )
+        .await
+        .unwrap();
+
+        let nonce = submit(&manager).await.unwrap();
+        let receipt = manager.wait(nonce).await.unwrap();
+
+        let node = transport.node.lock().unwrap();
+        assert_eq!(node.sent.len(), 2);
+        let (replaced_nonce, hash, gas_price) = node.sent[1];
+        assert_eq!(replaced_nonce, nonce);
+        assert_eq!(receipt.transaction_hash, hash);
+        assert_eq!(receipt.block_number, Some(7.into()));
+        assert_eq!(gas_price, U256::from(GAS_PRICE) * 120 / 100);
+        assert_eq!(node.sent[0].2, GAS_PRICE.into());
+        assert!(manager.pending_nonces().is_empty());
+    }
+
+    #[tokio::test]
+    async fn wait_gives_up_after_timeout() {
+        let signer = signer();
+        let transport = transport(&signer, MockNode::default());
+        let manager = TxManager::new(
+            transport.clone(),
+            &signer,
+            TxManagerConfig {
+                confirm_timeout: Duration::from_millis(0),
+                ..config()
+            },
+        )
+        .await
+        .unwrap();
+
+        let nonce = submit(&manager).await.unwrap();
+        assert!(manager.wait(nonce).await.is_err());
+        // still pending, a later `wait` may pick it up
+        assert_eq!(manager.pending_nonces(), vec![nonce]);
+        assert!(manager.wait(nonce + 1).await.is_err());
+    }
+
+    async fn sign(signer: &LocalSigner, nonce: u64) -> Vec<u8> {
+        let tx = CeloTransaction {
+            nonce: nonce.into(),
+            gas_price: GAS_PRICE.into(),
+            gas: 100_000.into(),
+            to: Some(Address::repeat_byte(1)),
+            chain_id: 44787,
+            ..Default::default()
+        };
+
+        signer.sign_transaction(tx).await.unwrap().0
+    }
+}
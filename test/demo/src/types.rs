@@ -23,10 +23,7 @@ pub fn to_block_id(last_block_id: &tendermint::block::Id) -> BlockId {
 pub fn to_timestamp(timestamp: &tendermint::time::Time) -> Timestamp {
     let nanos = timestamp.0.timestamp_subsec_nanos().try_into().unwrap_or(0);
     let seconds = timestamp.0.timestamp();
-    Timestamp {
-        seconds: seconds,
-        nanos: nanos,
-    }
+    Timestamp { seconds, nanos }
 }
 
 pub fn to_version(version: &tendermint::block::header::Version) -> Consensus {
@@ -51,7 +48,7 @@ pub fn to_sig(sig: &tendermint::block::commit_sig::CommitSig) -> CommitSig {
         } => CommitSig {
             block_id_flag: BlockIdFlag::Nil.into(),
             validator_address: validator_address.to_owned().into(),
-            timestamp: Some(to_timestamp(&timestamp)),
+            timestamp: Some(to_timestamp(timestamp)),
             signature: signature.to_owned().unwrap().into(),
         },
         tendermint::block::commit_sig::CommitSig::BlockIdFlagCommit {
@@ -61,7 +58,7 @@ pub fn to_sig(sig: &tendermint::block::commit_sig::CommitSig) -> CommitSig {
         } => CommitSig {
             block_id_flag: BlockIdFlag::Commit.into(),
             validator_address: validator_address.to_owned().into(),
-            timestamp: Some(to_timestamp(&timestamp)),
+            timestamp: Some(to_timestamp(timestamp)),
             signature: signature.to_owned().unwrap().into(),
         },
     }
@@ -84,7 +81,7 @@ pub fn to_signed_header(
             consensus_hash: header.consensus_hash.into(),
             data_hash: header.data_hash.unwrap().into(),
             evidence_hash: header.evidence_hash.unwrap().into(),
-            last_block_id: Some(to_block_id(&header.last_block_id.unwrap())),
+            last_block_id: header.last_block_id.as_ref().map(to_block_id),
             last_commit_hash: header.last_commit_hash.unwrap().into(),
             last_results_hash: header.last_results_hash.unwrap().into(),
             proposer_address: header.proposer_address.into(),
@@ -94,7 +91,7 @@ pub fn to_signed_header(
             height: commit.height.into(),
             round: commit.round.into(),
             block_id: Some(to_block_id(&commit.block_id)),
-            signatures: commit.signatures.iter().map(|sig| to_sig(sig)).collect(),
+            signatures: commit.signatures.iter().map(to_sig).collect(),
         }),
     }
 }
@@ -119,6 +116,105 @@ pub fn to_validator_set(validators: &[tendermint::validator::Info]) -> Validator
     }
 }
 
+fn from_timestamp(timestamp: &Timestamp) -> tendermint_proto::google::protobuf::Timestamp {
+    tendermint_proto::google::protobuf::Timestamp {
+        seconds: timestamp.seconds,
+        nanos: timestamp.nanos,
+    }
+}
+
+fn from_block_id(block_id: &BlockId) -> tendermint_proto::types::BlockId {
+    tendermint_proto::types::BlockId {
+        hash: block_id.hash.to_owned(),
+        part_set_header: block_id.part_set_header.as_ref().map(|part_set_header| {
+            tendermint_proto::types::PartSetHeader {
+                total: part_set_header.total,
+                hash: part_set_header.hash.to_owned(),
+            }
+        }),
+    }
+}
+
+/// Inverse of `to_signed_header`
+pub fn from_signed_header(
+    signed_header: &SignedHeader,
+) -> Result<tendermint::block::signed_header::SignedHeader, tendermint::Error> {
+    let raw = tendermint_proto::types::SignedHeader {
+        header: signed_header
+            .header
+            .as_ref()
+            .map(|header| tendermint_proto::types::Header {
+                version: header.version.as_ref().map(|version| {
+                    tendermint_proto::version::Consensus {
+                        block: version.block,
+                        app: version.app,
+                    }
+                }),
+                chain_id: header.chain_id.to_owned(),
+                height: header.height,
+                time: header.time.as_ref().map(from_timestamp),
+                last_block_id: header.last_block_id.as_ref().map(from_block_id),
+                last_commit_hash: header.last_commit_hash.to_owned(),
+                data_hash: header.data_hash.to_owned(),
+                validators_hash: header.validators_hash.to_owned(),
+                next_validators_hash: header.next_validators_hash.to_owned(),
+                consensus_hash: header.consensus_hash.to_owned(),
+                app_hash: header.app_hash.to_owned(),
+                last_results_hash: header.last_results_hash.to_owned(),
+                evidence_hash: header.evidence_hash.to_owned(),
+                proposer_address: header.proposer_address.to_owned(),
+            }),
+        commit: signed_header
+            .commit
+            .as_ref()
+            .map(|commit| tendermint_proto::types::Commit {
+                height: commit.height,
+                round: commit.round,
+                block_id: commit.block_id.as_ref().map(from_block_id),
+                signatures: commit
+                    .signatures
+                    .iter()
+                    .map(|sig| tendermint_proto::types::CommitSig {
+                        block_id_flag: sig.block_id_flag,
+                        validator_address: sig.validator_address.to_owned(),
+                        timestamp: sig.timestamp.as_ref().map(from_timestamp),
+                        signature: sig.signature.to_owned(),
+                    })
+                    .collect(),
+            }),
+    };
+
+    raw.try_into()
+}
+
+/// Inverse of `to_validator_set`
+pub fn from_validator_set(
+    validator_set: &ValidatorSet,
+) -> Result<Vec<tendermint::validator::Info>, Box<dyn std::error::Error>> {
+    use crate::proto::tendermint::light::public_key::Sum;
+    use tendermint_proto::crypto::public_key::Sum as RawSum;
+
+    validator_set
+        .validators
+        .iter()
+        .map(|validator| {
+            let sum = match validator.pub_key.as_ref().and_then(|key| key.sum.as_ref()) {
+                Some(Sum::Ed25519(key)) => RawSum::Ed25519(key.to_owned()),
+                Some(Sum::Secp256k1(key)) => RawSum::Secp256k1(key.to_owned()),
+                _ => return Err("validator without an ed25519/secp256k1 public key".into()),
+            };
+
+            Ok(tendermint_proto::types::Validator {
+                address: validator.address.to_owned(),
+                pub_key: Some(tendermint_proto::crypto::PublicKey { sum: Some(sum) }),
+                voting_power: validator.voting_power,
+                proposer_priority: validator.proposer_priority,
+            }
+            .try_into()?)
+        })
+        .collect()
+}
+
 pub fn to_light_block(signed_header: &SignedHeader, validator_set: &ValidatorSet) -> TmHeader {
     TmHeader {
         trusted_validators: None,
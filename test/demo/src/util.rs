@@ -1,3 +1,4 @@
+use crate::celo;
 use secp256k1::{key::SecretKey, rand::rngs::OsRng, Secp256k1};
 use std::{
     error::Error,
@@ -5,6 +6,7 @@ use std::{
     io::{Read, Write},
     path::Path,
 };
+use tracing::info;
 
 pub fn get_celo_private_key(celo_private_key_path: &str) -> Result<SecretKey, Box<dyn Error>> {
     if Path::new(celo_private_key_path).exists() {
@@ -23,42 +25,95 @@ pub fn get_celo_private_key(celo_private_key_path: &str) -> Result<SecretKey, Bo
         let mut ifile = File::create(celo_private_key_path)?;
         ifile.write_all(format!("0x{}", hex::encode(secret_key.as_ref())).as_bytes())?;
 
-        println!(
-            "[0] generated a new celo private key: {}",
-            celo_private_key_path
+        info!(
+            path = celo_private_key_path,
+            "generated a new celo private key"
         );
 
         Ok(secret_key)
     }
 }
 
-pub async fn calculate_and_display_fee<'a, T: web3::Transport>(
-    prefix: &'a str,
-    client_id: String,
+/// Value as a float, which (unlike `as_u64`/`as_u128`) does not panic on large amounts
+fn u256_to_f64(value: web3::types::U256) -> f64 {
+    value.0.iter().rev().fold(0.0, |acc, &limb| {
+        acc * 18_446_744_073_709_551_616.0 + limb as f64
+    })
+}
+
+/// Fee actually debited for a transaction
+pub struct TxFee {
+    pub gas_used: web3::types::U256,
+    /// In units of `currency` (incl. the gateway fee)
+    pub fee: f64,
+    pub currency: String,
+}
+
+/// Logs the gas usage and the fee actually debited for the transaction.
+///
+/// The fee is reported in the currency the transaction paid with (CELO or the `feeCurrency`
+/// token such as cUSD), `usd_price` is expected to be the USD price of that currency.
+pub async fn calculate_and_display_fee<T: web3::Transport>(
+    message: &str,
     transport: &T,
     reciept: &web3::types::TransactionReceipt,
-    celo_usd_price: f64,
+    usd_price: f64,
     celo_gas_price: f64,
-) {
-    let web3 = web3::Web3::new(&transport);
-    let tx: web3::types::Transaction = web3
-        .eth()
-        .transaction(web3::types::TransactionId::Hash(reciept.transaction_hash))
-        .await
-        .unwrap()
-        .unwrap();
+) -> web3::Result<TxFee> {
+    let tx = celo::get_transaction(transport, reciept.transaction_hash)
+        .await?
+        .ok_or_else(|| {
+            web3::Error::InvalidResponse(format!(
+                "transaction {:?} not found",
+                reciept.transaction_hash
+            ))
+        })?;
+    let currency = celo::fee_currency_symbol(transport, tx.fee_currency).await;
 
-    let gas_price = if tx.gas_price.as_u64() == 0 {
+    let gas_price = if tx.gas_price.is_zero() {
         celo_gas_price
     } else {
-        tx.gas_price.as_u64() as f64
+        u256_to_f64(tx.gas_price)
     }; // wei
-    let gas_used = reciept.gas_used.unwrap().as_u64();
-    let fee = (gas_price * gas_used as f64) / 1e18;
-    let fee_usd = celo_usd_price * fee;
+    let gas_used = reciept.gas_used.unwrap_or_default().low_u64();
+    let gateway_fee = u256_to_f64(tx.gateway_fee);
+    let fee = (gas_price * gas_used as f64 + gateway_fee) / 1e18;
+    let fee_usd = usd_price * fee;
 
-    println!(
-        "{}[{}] gas: {}, gas_used: {}; gas_price: {}; fee(CELO): {}; fee(USD): {}",
-        prefix, client_id, tx.gas, gas_used, gas_price, fee, fee_usd
+    info!(
+        message,
+        tx_hash = ?reciept.transaction_hash,
+        gas = %tx.gas,
+        gas_used,
+        gas_price,
+        gateway_fee,
+        fee,
+        fee_currency = %currency,
+        fee_usd,
+        "fee"
     );
+
+    Ok(TxFee {
+        gas_used: gas_used.into(),
+        fee,
+        currency,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::U256;
+
+    #[test]
+    fn converts_large_amounts() {
+        assert_eq!(u256_to_f64(U256::zero()), 0.0);
+        assert_eq!(u256_to_f64(U256::from(5_000_000_000u64)), 5e9);
+        assert_eq!(
+            u256_to_f64(U256::from(u64::MAX) + 1),
+            18_446_744_073_709_551_616.0
+        );
+        assert_eq!(u256_to_f64(U256::exp10(30)), 1e30);
+        assert_eq!(u256_to_f64(U256::MAX), 2f64.powi(256));
+    }
 }
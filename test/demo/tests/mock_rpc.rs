@@ -0,0 +1,76 @@
+use std::{
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::Arc,
+};
+use tendermint_rpc::HttpClient;
+
+use client::{
+    header::{self, HeaderSource},
+    mock_rpc::{self, Fixtures},
+};
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../data")
+}
+
+/// Serves the fixtures in `test/data` on a free local port
+async fn start_mock() -> HeaderSource {
+    let addr: SocketAddr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let fixtures = Arc::new(Fixtures::open(fixtures_dir()).unwrap());
+    tokio::spawn(mock_rpc::serve(fixtures, addr));
+
+    let url = format!("http://{}", addr);
+    let source = HeaderSource::new(HttpClient::new(url.as_str()).unwrap());
+    // the server binds in the background
+    for _ in 0..50 {
+        if source.latest_height().await.is_ok() {
+            return source;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    panic!("mock RPC did not start on {}", addr)
+}
+
+#[tokio::test]
+async fn serves_recorded_headers() {
+    let source = start_mock().await;
+    let dir = fixtures_dir();
+
+    assert_eq!(source.latest_height().await.unwrap(), 8619998);
+
+    for height in [28, 29, 30, 8619996, 8619997, 8619998] {
+        let light_block = source.light_block(height).await.unwrap();
+        assert_eq!(
+            light_block,
+            header::read_light_block(&dir, height).unwrap(),
+            "light block at {}",
+            height
+        );
+
+        let signed_header = light_block.signed_header.unwrap();
+        let commit = signed_header.commit.unwrap();
+        assert_eq!(signed_header.header.unwrap().height, height);
+        assert_eq!(commit.height, height);
+        assert!(!commit.signatures.is_empty());
+        assert_eq!(
+            light_block.validator_set.unwrap().validators.len(),
+            header::read_validator_set(&dir, height)
+                .unwrap()
+                .validators
+                .len()
+        );
+    }
+}
+
+#[tokio::test]
+async fn rejects_unrecorded_heights() {
+    let source = start_mock().await;
+
+    assert!(source.signed_header(27).await.is_err());
+    assert!(source.validator_set(8619999).await.is_err());
+}